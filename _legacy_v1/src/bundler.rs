@@ -6,7 +6,7 @@
 use rolldown::{Bundler, BundlerBuilder, BundlerOptions, InputItem};
 use std::sync::Arc;
 
-use crate::plugin::ZenithPlugin;
+use crate::plugin::{DevCache, ZenithPlugin};
 
 /// Create a configured Rolldown bundler for a Zenith project
 pub fn create_zenith_bundler(entry_point: &str, components_dir: Option<&str>) -> Bundler {
@@ -47,11 +47,14 @@ pub fn create_zenith_bundler(entry_point: &str, components_dir: Option<&str>) ->
 }
 
 /// Create a configured Rolldown bundler for Dev Mode (Watch + HMR + InMemory)
+///
+/// Also returns the plugin's [`DevCache`], used to invalidate changed files
+/// before a rebuild.
 pub fn create_dev_bundler(
     entry_point: &str,
     components_dir: Option<&str>,
     store: std::sync::Arc<crate::store::AssetStore>,
-) -> Bundler {
+) -> (Bundler, DevCache) {
     // 1. Initialize the Zenith Plugin with Store and Dev Mode
     let mut plugin = ZenithPlugin::new(entry_point)
         .with_store(store)
@@ -60,6 +63,7 @@ pub fn create_dev_bundler(
     if let Some(dir) = components_dir {
         plugin = plugin.with_components_dir(dir);
     }
+    let dev_cache = plugin.dev_cache();
 
     // 2. Configure Bundler Options (Dev Optimized)
     let options = BundlerOptions {
@@ -77,5 +81,5 @@ pub fn create_dev_bundler(
         .with_options(options)
         .with_plugins(vec![Arc::new(plugin)]);
 
-    builder.build().expect("Failed to build dev bundler")
+    (builder.build().expect("Failed to build dev bundler"), dev_cache)
 }
@@ -10,7 +10,28 @@ use lightningcss::rules::CssRule;
 use lightningcss::selector::Component;
 use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
 use lightningcss::targets::Browsers;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+
+/// Which class names survived pruning, for "where did my style go" debugging
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Classes named by at least one kept selector (sorted)
+    pub kept: Vec<String>,
+    /// Classes whose every selector was dropped because none of the
+    /// selector's classes were used (sorted)
+    pub removed: Vec<String>,
+}
+
+impl PruneReport {
+    /// Build warning text for the removed classes, if any
+    pub fn unused_warning(&self) -> Option<String> {
+        if self.removed.is_empty() {
+            return None;
+        }
+        let classes: Vec<String> = self.removed.iter().map(|c| format!(".{}", c)).collect();
+        Some(format!("defined but never used: {}", classes.join(", ")))
+    }
+}
 
 /// Thread-safe CSS buffer for collecting styles from .zen files
 #[derive(Debug)]
@@ -31,58 +52,117 @@ impl CssBuffer {
         self.styles.insert(file_id, css);
     }
 
-    /// Get all buffered CSS
+    /// Get all buffered CSS, in file id order
     pub fn get_all(&self) -> Vec<String> {
-        self.styles.iter().map(|r| r.value().clone()).collect()
+        let mut entries: Vec<(String, String)> = self
+            .styles
+            .iter()
+            .map(|r| (r.key().clone(), r.value().clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.into_iter().map(|(_, css)| css).collect()
+    }
+
+    /// Stitch all CSS and prune unused classes, minified
+    ///
+    /// Shorthand for [`CssBuffer::stitch_and_prune_with`] with `minify: true`.
+    pub fn stitch_and_prune(&self, used_classes: &[String]) -> Result<String, String> {
+        self.stitch_and_prune_with(used_classes, true)
     }
 
     /// Stitch all CSS and prune unused classes
     ///
     /// Strategy:
-    /// 1. Parse the CSS into AST using lightningcss
-    /// 2. Walk the AST and remove rules/selectors that allow pruning
-    /// 3. Minify and print the result
-    pub fn stitch_and_prune(&self, used_classes: &[String]) -> Result<String, String> {
-        let all_css: String = self
+    /// 1. Parse each file's CSS into its own AST using lightningcss, so a
+    ///    parse error names the file it came from and an unterminated block
+    ///    cannot swallow the next file
+    /// 2. Merge the per-file rule lists (in file id order)
+    /// 3. Walk the AST and remove rules/selectors that allow pruning
+    /// 4. Minify (when `minify` is set) and print the result; otherwise the
+    ///    pruned rules are pretty-printed as written
+    pub fn stitch_and_prune_with(
+        &self,
+        used_classes: &[String],
+        minify: bool,
+    ) -> Result<String, String> {
+        self.stitch_and_prune_with_report(used_classes, minify)
+            .map(|(css, _)| css)
+    }
+
+    /// Like [`CssBuffer::stitch_and_prune_with`], also reporting which
+    /// classes the pruner kept and removed. Pruning itself is unchanged.
+    pub fn stitch_and_prune_with_report(
+        &self,
+        used_classes: &[String],
+        minify: bool,
+    ) -> Result<(String, PruneReport), String> {
+        let mut sources: Vec<(String, String)> = self
             .styles
             .iter()
-            .map(|r| r.value().clone())
-            .collect::<Vec<_>>()
-            .join("\n");
+            .filter(|r| !r.value().trim().is_empty())
+            .map(|r| (r.key().clone(), r.value().clone()))
+            .collect();
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
 
-        if all_css.is_empty() {
-            return Ok(String::new());
+        if sources.is_empty() {
+            return Ok((String::new(), PruneReport::default()));
         }
 
+        // 1. Parse each file separately, 2. merge ASTs
+        let mut merged: Option<StyleSheet> = None;
+        for (file_id, css) in &sources {
+            let options = ParserOptions {
+                filename: file_id.clone(),
+                ..ParserOptions::default()
+            };
+            let sheet = StyleSheet::parse(css, options)
+                .map_err(|e| format!("CSS parse error in {}: {}", file_id, e))?;
+            match merged.as_mut() {
+                Some(stylesheet) => stylesheet.rules.0.extend(sheet.rules.0),
+                None => merged = Some(sheet),
+            }
+        }
+        let mut stylesheet = merged.expect("at least one CSS source");
+
         // Build allow-list from used classes
         let used_set: HashSet<&str> = used_classes.iter().map(|s| s.as_str()).collect();
 
-        // 1. Parse CSS
-        let mut stylesheet = StyleSheet::parse(&all_css, ParserOptions::default())
-            .map_err(|e| format!("CSS parse error: {:?}", e))?;
-
-        // 2. Prune AST (Recursive)
+        // 3. Prune AST (Recursive)
         // Accessing rules directly requires ensuring we can iterate mutably
         let rules_vec = &mut stylesheet.rules.0;
-        prune_rules(rules_vec, &used_set);
-
-        // 3. Minify and Print
-
-        stylesheet
-            .minify(MinifyOptions {
-                targets: Browsers::default().into(),
-                ..Default::default()
-            })
-            .map_err(|e| format!("CSS minify error: {:?}", e))?;
+        let mut kept = BTreeSet::new();
+        let mut removed = BTreeSet::new();
+        prune_rules(rules_vec, &used_set, &mut kept, &mut removed);
+        // A class still matched by a kept selector did not disappear
+        let report = PruneReport {
+            removed: removed.difference(&kept).cloned().collect(),
+            kept: kept.into_iter().collect(),
+        };
+
+        // 4. Minify and Print
+        if minify {
+            stylesheet
+                .minify(MinifyOptions {
+                    targets: Browsers::default().into(),
+                    ..Default::default()
+                })
+                .map_err(|e| format!("CSS minify error: {:?}", e))?;
+        }
 
         let result = stylesheet
             .to_css(PrinterOptions {
-                minify: true,
+                minify,
                 ..Default::default()
             })
             .map_err(|e| format!("CSS print error: {:?}", e))?;
 
-        Ok(result.code)
+        Ok((result.code, report))
+    }
+
+    /// Drop the CSS buffered for files matching `pred` (e.g. a file that
+    /// changed and will be recompiled)
+    pub fn invalidate(&self, pred: impl Fn(&str) -> bool) {
+        self.styles.retain(|file_id, _| !pred(file_id));
     }
 
     /// Clear all buffered CSS
@@ -101,7 +181,13 @@ impl Default for CssBuffer {
 ///
 /// Returns true if rule should be kept, false if it should be removed (if single rule context)
 /// But here we operate on Vec<CssRule>, so we use retain_mut.
-fn prune_rules(rules: &mut Vec<CssRule>, used_set: &HashSet<&str>) {
+/// Class names of kept and dropped selectors are collected into `kept` / `removed`.
+fn prune_rules(
+    rules: &mut Vec<CssRule>,
+    used_set: &HashSet<&str>,
+    kept: &mut BTreeSet<String>,
+    removed: &mut BTreeSet<String>,
+) {
     rules.retain_mut(|rule| {
         match rule {
             CssRule::Style(style_rule) => {
@@ -109,10 +195,19 @@ fn prune_rules(rules: &mut Vec<CssRule>, used_set: &HashSet<&str>) {
                 // style_rule.selectors is SelectorList.
 
                 // We iterate and keep selectors that are "used"
-                style_rule
-                    .selectors
-                    .0
-                    .retain(|selector| is_selector_used(selector, used_set));
+                style_rule.selectors.0.retain(|selector| {
+                    let used = is_selector_used(selector, used_set);
+                    let classes = selector.iter().filter_map(|component| match component {
+                        Component::Class(ident) => Some(ident.as_ref().to_string()),
+                        _ => None,
+                    });
+                    if used {
+                        kept.extend(classes);
+                    } else {
+                        removed.extend(classes);
+                    }
+                    used
+                });
 
                 // Determine if we keep the rule:
                 // If NO selectors remain, the rule is empty and should be removed.
@@ -122,13 +217,13 @@ fn prune_rules(rules: &mut Vec<CssRule>, used_set: &HashSet<&str>) {
                 // Recursively prune rules inside @media
                 // media_rule.rules is CssRuleList (which wraps Vec<CssRule>).
                 // Access via .0
-                prune_rules(&mut media_rule.rules.0, used_set);
+                prune_rules(&mut media_rule.rules.0, used_set, kept, removed);
 
                 // Keep media rule only if it still has rules inside
                 !media_rule.rules.0.is_empty()
             }
             CssRule::Supports(supports_rule) => {
-                prune_rules(&mut supports_rule.rules.0, used_set);
+                prune_rules(&mut supports_rule.rules.0, used_set, kept, removed);
                 !supports_rule.rules.0.is_empty()
             }
             // For other rules (Keyframes, FontFace, etc.), we keep them ALWAYS.
@@ -183,6 +278,49 @@ mod tests {
         assert_eq!(all.len(), 2);
     }
 
+    #[test]
+    fn test_get_all_in_file_id_order() {
+        let buffer = CssBuffer::new();
+        buffer.insert("c.zen".into(), ".c {}".into());
+        buffer.insert("a.zen".into(), ".a {}".into());
+        buffer.insert("b.zen".into(), ".b {}".into());
+
+        assert_eq!(buffer.get_all(), vec![".a {}", ".b {}", ".c {}"]);
+    }
+
+    #[test]
+    fn test_stitched_output_is_deterministic() {
+        let files = [
+            ("d.zen", ".d { color: green; }"),
+            ("a.zen", ".a { color: red; }"),
+            ("c.zen", ".c { margin: 0; }"),
+            ("b.zen", ".b { color: blue; }"),
+        ];
+        let used: Vec<String> = ["a", "b", "c", "d"].iter().map(|c| c.to_string()).collect();
+
+        let forward = CssBuffer::new();
+        let backward = CssBuffer::new();
+        for (file_id, css) in files {
+            forward.insert(file_id.into(), css.into());
+        }
+        for (file_id, css) in files.iter().rev() {
+            backward.insert(file_id.to_string(), css.to_string());
+        }
+
+        let first = forward.stitch_and_prune(&used).unwrap();
+        for _ in 0..10 {
+            assert_eq!(forward.stitch_and_prune(&used).unwrap(), first);
+        }
+        assert_eq!(backward.stitch_and_prune(&used).unwrap(), first);
+
+        // Concatenated in file id order
+        let positions: Vec<usize> = [".a", ".b", ".c", ".d"]
+            .iter()
+            .map(|class| first.find(class).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", first);
+    }
+
     #[test]
     fn test_css_stitch_and_minify() {
         let buffer = CssBuffer::new();
@@ -192,6 +330,59 @@ mod tests {
         assert!(result.contains("color:") || result.contains("color:red"));
     }
 
+    #[test]
+    fn test_css_stitch_pretty_prints_without_minify() {
+        let buffer = CssBuffer::new();
+        buffer.insert(
+            "a.zen".into(),
+            ".foo { color: red; margin: 0; } .bar { color: blue; }".into(),
+        );
+        let used = ["foo".to_string()];
+
+        let pretty = buffer.stitch_and_prune_with(&used, false).unwrap();
+        assert!(pretty.contains(".foo {\n"), "{}", pretty);
+        assert!(pretty.contains("color: red;"), "{}", pretty);
+        // Pruning is independent of the output style
+        assert!(!pretty.contains("blue"), "{}", pretty);
+
+        let minified = buffer.stitch_and_prune_with(&used, true).unwrap();
+        assert_eq!(minified, buffer.stitch_and_prune(&used).unwrap());
+        assert!(!minified.contains('\n'), "{}", minified);
+        assert!(minified.len() < pretty.len());
+    }
+
+    #[test]
+    fn test_prune_report_lists_removed_classes() {
+        let buffer = CssBuffer::new();
+        buffer.insert(
+            "a.zen".into(),
+            ".foo { color: red; } .bar { color: blue; } .foo.baz { margin: 0; } \
+             @media (min-width: 1px) { .qux { color: green; } }"
+                .into(),
+        );
+        let used = ["foo".to_string()];
+
+        let (css, report) = buffer.stitch_and_prune_with_report(&used, true).unwrap();
+        assert_eq!(css, buffer.stitch_and_prune(&used).unwrap());
+        // `.baz` survives inside `.foo.baz`
+        assert_eq!(report.kept, vec!["baz", "foo"]);
+        assert_eq!(report.removed, vec!["bar", "qux"]);
+        assert_eq!(
+            report.unused_warning().as_deref(),
+            Some("defined but never used: .bar, .qux")
+        );
+
+        let all_used: Vec<String> = ["foo", "bar", "baz", "qux"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let (_, report) = buffer
+            .stitch_and_prune_with_report(&all_used, true)
+            .unwrap();
+        assert!(report.removed.is_empty());
+        assert_eq!(report.unused_warning(), None);
+    }
+
     #[test]
     fn test_css_pruning_removes_unused() {
         let buffer = CssBuffer::new();
@@ -263,4 +454,31 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn test_parse_error_names_malformed_file() {
+        let buffer = CssBuffer::new();
+        buffer.insert("a.zen".into(), ".foo { color: red; }".into());
+        buffer.insert("b.zen".into(), "..bar { color: blue; }".into());
+
+        let err = buffer.stitch_and_prune(&["foo".into()]).unwrap_err();
+        assert!(err.contains("b.zen"), "Error should name b.zen: {}", err);
+        assert!(!err.contains("a.zen"), "Error should not blame a.zen: {}", err);
+    }
+
+    #[test]
+    fn test_unterminated_block_does_not_swallow_next_file() {
+        let buffer = CssBuffer::new();
+        buffer.insert("a.zen".into(), "@media (min-width: 1px) {".into());
+        buffer.insert("b.zen".into(), ".foo { color: red; }".into());
+
+        // Concatenated, `.foo` would land inside the unclosed @media block
+        let result = buffer.stitch_and_prune(&["foo".into()]).unwrap();
+        assert!(result.contains("red"), "Should keep .foo: {}", result);
+        assert!(
+            !result.contains("@media"),
+            "b.zen must stay a top-level rule: {}",
+            result
+        );
+    }
 }
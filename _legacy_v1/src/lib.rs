@@ -24,7 +24,7 @@ pub mod store;
 
 pub use css::CssBuffer;
 pub use html::HtmlInjector;
-pub use plugin::ZenithPlugin;
+pub use plugin::{HydrationStrategy, ZenithPlugin};
 
 // Re-export Rolldown types for convenience
 pub use rolldown::{Bundler, BundlerBuilder, BundlerOptions};
@@ -37,15 +37,31 @@ use crate::store::AssetStore;
 #[cfg(feature = "napi")]
 use napi_derive::napi;
 #[cfg(feature = "napi")]
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "napi")]
 use tokio::sync::{mpsc, oneshot};
+#[cfg(feature = "napi")]
+use zenith_bundler::CancellationToken;
+
+/// A rebuild signal: the changed file (if known) and the completion reply.
+#[cfg(feature = "napi")]
+struct RebuildRequest {
+    /// Only this file is recompiled; `None` recompiles everything
+    changed: Option<String>,
+    /// Tripped when a newer request supersedes this one
+    cancel: CancellationToken,
+    /// Receives the outcome of the build that covers this request: its own,
+    /// or that of the newer request which superseded it
+    reply: oneshot::Sender<Result<(), String>>,
+}
 
 #[cfg(feature = "napi")]
 #[napi]
 pub struct ZenithDevController {
     store: Arc<AssetStore>,
-    rebuild_tx: mpsc::Sender<oneshot::Sender<()>>,
+    rebuild_tx: mpsc::Sender<RebuildRequest>,
+    /// Token of the most recent rebuild request
+    current_rebuild: Mutex<CancellationToken>,
 }
 
 #[cfg(feature = "napi")]
@@ -58,13 +74,13 @@ impl ZenithDevController {
 
         // Channel for rebuild signals (Robust HMR Pattern)
         // Main thread sends (reply_channel) -> Builder builds -> Builder replies
-        let (tx, mut rx) = mpsc::channel::<oneshot::Sender<()>>(1);
+        let (tx, mut rx) = mpsc::channel::<RebuildRequest>(1);
 
         // Spawn Watcher/Builder Thread
         std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().unwrap();
             rt.block_on(async move {
-                let mut bundler = crate::bundler::create_dev_bundler(
+                let (mut bundler, dev_cache) = crate::bundler::create_dev_bundler(
                     &format!("{}/src/main.zen", project_root),
                     Some(&format!("{}/src/components", project_root)),
                     store_clone,
@@ -76,15 +92,41 @@ impl ZenithDevController {
                     Err(_e) => {} // eprintln! removed for silence? Or keep errors? User said "all logs". I'll keep errors if critical, but silence is cleaner for "library".
                 }
 
-                // Internal Watch Loop (Driven by NAPI calls)
-                while let Some(reply_tx) = rx.recv().await {
-                    match bundler.write().await {
-                        Ok(_) => {
-                            let _ = reply_tx.send(());
-                        }
-                        Err(_e) => {
-                            let _ = reply_tx.send(());
-                        }
+                // Internal Watch Loop (Driven by NAPI calls). Requests not
+                // yet answered wait for the next build that completes
+                let mut waiting = Vec::new();
+                while let Some(RebuildRequest {
+                    changed,
+                    cancel,
+                    reply: reply_tx,
+                }) = rx.recv().await
+                {
+                    match changed {
+                        Some(path) => dev_cache.invalidate(&path),
+                        None => dev_cache.clear(),
+                    }
+                    waiting.push(reply_tx);
+                    // Superseded while queued: skip the build. The
+                    // invalidation above still applies, and the newer
+                    // request's build answers this one too
+                    if cancel.is_cancelled() {
+                        continue;
+                    }
+                    // A started write always runs to completion; dropping it
+                    // part-way would leave half-written output and an asset
+                    // store only partly replaced by `generate_bundle`
+                    let result = bundler
+                        .write()
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| format!("Rebuild failed: {:?}", e));
+                    // Superseded while building: the newer request, already
+                    // on its way, rebuilds and answers everyone waiting
+                    if cancel.is_cancelled() {
+                        continue;
+                    }
+                    for reply_tx in waiting.drain(..) {
+                        let _ = reply_tx.send(result.clone());
                     }
                 }
             });
@@ -93,6 +135,7 @@ impl ZenithDevController {
         Self {
             store,
             rebuild_tx: tx,
+            current_rebuild: Mutex::new(CancellationToken::new()),
         }
     }
 
@@ -101,28 +144,116 @@ impl ZenithDevController {
         self.store.get(&path)
     }
 
-    /// Trigger a rebuild and wait for completion
+    /// Trigger a rebuild and wait for completion. A request superseded by a
+    /// newer one resolves with the newer build's outcome
     #[napi]
     pub async fn rebuild(&self) -> napi::Result<()> {
+        self.request_rebuild(None).await
+    }
+
+    /// Rebuild after `path` changed, recompiling only that file, and wait
+    /// for completion
+    #[napi]
+    pub async fn rebuild_file(&self, path: String) -> napi::Result<()> {
+        self.request_rebuild(Some(path)).await
+    }
+
+    async fn request_rebuild(&self, changed: Option<String>) -> napi::Result<()> {
+        // A fresh token per request; the one it replaces is cancelled
+        let cancel = CancellationToken::new();
+        std::mem::replace(&mut *self.current_rebuild.lock().unwrap(), cancel.clone()).cancel();
+
         let (reply_tx, reply_rx) = oneshot::channel();
         self.rebuild_tx
-            .send(reply_tx)
+            .send(RebuildRequest {
+                changed,
+                cancel,
+                reply: reply_tx,
+            })
             .await
             .map_err(|_| napi::Error::from_reason("Builder thread disconnected"))?;
 
         reply_rx
             .await
-            .map_err(|_| napi::Error::from_reason("Builder failed to reply"))?;
+            .map_err(|_| napi::Error::from_reason("Builder failed to reply"))?
+            .map_err(napi::Error::from_reason)
+    }
+}
+
+/// Input accepted by [`bundle`]: the `BundlePlan` fields plus the
+/// JSON-representable subset of `BundleOptions`.
+#[cfg(feature = "napi")]
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct NativeBundleRequest {
+    page_path: String,
+    #[serde(default)]
+    out_dir: Option<std::path::PathBuf>,
+    mode: zenith_bundler::BuildMode,
+    #[serde(default = "default_true")]
+    strict: bool,
+    #[serde(default)]
+    write_to_disk: bool,
+    #[serde(default)]
+    minify: Option<bool>,
+    #[serde(default = "default_true")]
+    default_export: bool,
+    #[serde(default)]
+    sourcemap: Option<bool>,
+    #[serde(default)]
+    validate_js: bool,
+    #[serde(default)]
+    fail_on_codes: Vec<String>,
+}
+
+#[cfg(feature = "napi")]
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(feature = "napi")]
+impl NativeBundleRequest {
+    /// Deserialize a request and split it into the bundler's plan and options.
+    fn parse(
+        value: serde_json::Value,
+    ) -> napi::Result<(zenith_bundler::BundlePlan, zenith_bundler::BundleOptions)> {
+        let request: NativeBundleRequest = serde_json::from_value(value)
+            .map_err(|e| napi::Error::from_reason(format!("Invalid bundle plan: {}", e)))?;
 
-        Ok(())
+        let plan = zenith_bundler::BundlePlan {
+            page_path: request.page_path,
+            out_dir: request.out_dir,
+            mode: request.mode,
+        };
+        let opts = zenith_bundler::BundleOptions {
+            strict: request.strict,
+            write_to_disk: request.write_to_disk,
+            minify: request.minify.map(zenith_bundler::MinifyMode::from),
+            default_export: request.default_export,
+            sourcemap: request.sourcemap,
+            validate_js: request.validate_js,
+            fail_on_codes: request.fail_on_codes,
+            ..Default::default()
+        };
+        Ok((plan, opts))
     }
 }
 
+/// Bundle one page through the Rolldown pipeline.
+///
+/// `plan` is a `NativeBundleRequest` object
+/// (`{ pagePath, mode: "Dev" | "Prod" | "SSG", ... }`). Returns the
+/// JSON-serialized `BundleResult`.
 #[cfg(feature = "napi")]
 #[napi]
-pub fn bundle(_plan: serde_json::Value) -> napi::Result<String> {
-    // TODO: Implement native bundling logic using rolldown
-    Ok("/* Native bundle not implemented */".to_string())
+pub fn bundle(plan: serde_json::Value) -> napi::Result<String> {
+    let (plan, opts) = NativeBundleRequest::parse(plan)?;
+
+    // Sync NAPI calls run outside any Tokio runtime, so the blocking driver is safe here
+    let result = zenith_bundler::bundle_page_blocking(plan, opts)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    serde_json::to_string(&result)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to serialize bundle result: {}", e)))
 }
 
 #[cfg(feature = "napi")]
@@ -132,9 +263,18 @@ pub fn generate_runtime(_manifest: serde_json::Value) -> napi::Result<String> {
     Ok("/* Native runtime not implemented */".to_string())
 }
 
+/// Report bundle size figures for one page without writing anything.
+///
+/// Takes the same object as [`bundle`] and returns the JSON-serialized
+/// `BundleStats` of an `analyze` build.
 #[cfg(feature = "napi")]
 #[napi]
-pub fn analyze_manifest(_manifest: serde_json::Value) -> napi::Result<serde_json::Value> {
-    // TODO: Implement native manifest analysis
-    Ok(serde_json::json!({ "analyzed": true }))
+pub fn analyze_manifest(manifest: serde_json::Value) -> napi::Result<serde_json::Value> {
+    let (plan, mut opts) = NativeBundleRequest::parse(manifest)?;
+    opts.analyze = true;
+
+    let result = zenith_bundler::bundle_page_blocking(plan, opts)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    serde_json::to_value(result.stats)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to serialize bundle stats: {}", e)))
 }
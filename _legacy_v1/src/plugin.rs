@@ -6,7 +6,7 @@
 //! 3. Buffer CSS for later pruning/stitching
 //! 4. Emit optimized CSS in `generate_bundle`
 
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use dashmap::DashMap;
 use rolldown_common::{EmittedAsset, Output, ResolvedExternal, StrOrBytes};
@@ -16,7 +16,7 @@ use rolldown_plugin::{
     TransformPluginContext,
 };
 
-use crate::css::CssBuffer;
+use crate::css::{CssBuffer, PruneReport};
 use crate::store::AssetStore;
 
 // Re-export ZenManifestExport from compiler-native as our canonical Manifest type
@@ -24,6 +24,57 @@ pub use compiler_native::{
     compile_zen_internal, CompileOptions, CompileResult, ZenManifestExport as ZenManifest,
 };
 
+/// When the hydration controller imports the app logic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HydrationStrategy {
+    /// On the next idle period, or after `timeout_ms` at the latest.
+    /// Falls back to a short `setTimeout` without `requestIdleCallback`.
+    Idle { timeout_ms: u32 },
+    /// Immediately, as soon as the controller runs.
+    Eager,
+    /// On the first `pointerdown` or `keydown`, whichever comes first.
+    OnInteraction,
+}
+
+impl Default for HydrationStrategy {
+    fn default() -> Self {
+        Self::Idle { timeout_ms: 2000 }
+    }
+}
+
+/// Handle to a dev plugin's per-module state, for invalidation between
+/// rebuilds.
+///
+/// In dev mode the plugin keeps each `.zen` file's generated module code, so
+/// a rebuild only recompiles the files invalidated here. Rolldown still
+/// re-links the graph, but unchanged modules are served from the cache.
+#[derive(Debug, Clone)]
+pub struct DevCache {
+    compiled_modules: Arc<DashMap<String, String>>,
+    css_buffer: Arc<CssBuffer>,
+}
+
+impl DevCache {
+    /// Forget the compiled module and buffered CSS for `path`.
+    ///
+    /// Module ids are the specifiers `.zen` files were imported with, so
+    /// they are matched against `path` by canonical filesystem path as well.
+    pub fn invalidate(&self, path: &str) {
+        let target = std::fs::canonicalize(path).ok();
+        let matches = |id: &str| {
+            id == path || (target.is_some() && std::fs::canonicalize(id).ok() == target)
+        };
+        self.compiled_modules.retain(|id, _| !matches(id));
+        self.css_buffer.invalidate(matches);
+    }
+
+    /// Forget every compiled module and all buffered CSS.
+    pub fn clear(&self) {
+        self.compiled_modules.clear();
+        self.css_buffer.clear();
+    }
+}
+
 /// The Zenith Plugin for Rolldown
 #[derive(Debug)]
 pub struct ZenithPlugin {
@@ -41,6 +92,15 @@ pub struct ZenithPlugin {
 
     /// Dev mode flag (enables HMR footer injection)
     is_dev: bool,
+
+    /// Generated module code per `.zen` id, reused across dev rebuilds
+    compiled_modules: Arc<DashMap<String, String>>,
+
+    /// When the hydration controller loads the app logic
+    hydration_strategy: HydrationStrategy,
+
+    /// What the last `generate_bundle` pruned from the stylesheet
+    prune_report: Arc<RwLock<PruneReport>>,
 }
 
 impl ZenithPlugin {
@@ -52,6 +112,9 @@ impl ZenithPlugin {
             entry_point: entry_point.into(),
             store: None,
             is_dev: false,
+            compiled_modules: Arc::new(DashMap::new()),
+            hydration_strategy: HydrationStrategy::default(),
+            prune_report: Arc::new(RwLock::new(PruneReport::default())),
         }
     }
 
@@ -65,6 +128,11 @@ impl ZenithPlugin {
         self
     }
 
+    pub fn with_hydration_strategy(mut self, strategy: HydrationStrategy) -> Self {
+        self.hydration_strategy = strategy;
+        self
+    }
+
     pub fn with_components_dir(mut self, dir: impl Into<String>) -> Self {
         self.components_dir = Some(dir.into());
         self
@@ -75,6 +143,21 @@ impl ZenithPlugin {
         Arc::clone(&self.css_buffer)
     }
 
+    /// Get a handle for invalidating changed modules between dev rebuilds
+    pub fn dev_cache(&self) -> DevCache {
+        DevCache {
+            compiled_modules: Arc::clone(&self.compiled_modules),
+            css_buffer: Arc::clone(&self.css_buffer),
+        }
+    }
+
+    /// Get a handle to the latest CSS prune report, so a host can warn
+    /// about classes that are defined but never used
+    /// (see [`PruneReport::unused_warning`])
+    pub fn prune_report(&self) -> Arc<RwLock<PruneReport>> {
+        Arc::clone(&self.prune_report)
+    }
+
     /// Get all used CSS classes for pruning
     pub fn used_classes(&self) -> Vec<String> {
         self.used_classes.iter().map(|r| r.key().clone()).collect()
@@ -173,6 +256,16 @@ if (import.meta.hot) {{
 
         // Handle .zen files
         if id.ends_with(".zen") {
+            // Dev rebuilds reuse modules that were not invalidated
+            if self.is_dev {
+                if let Some(cached) = self.compiled_modules.get(&**id) {
+                    return Ok(Some(HookLoadOutput {
+                        code: cached.value().clone().into(),
+                        ..Default::default()
+                    }));
+                }
+            }
+
             let source = match std::fs::read_to_string(&**id) {
                 Ok(s) => s,
                 Err(e) => {
@@ -206,6 +299,9 @@ if (import.meta.hot) {{
 
             // Generate the module code (script + expressions)
             let js_code = self.generate_module_code(&manifest);
+            if self.is_dev {
+                self.compiled_modules.insert(id.to_string(), js_code.clone());
+            }
 
             return Ok(Some(HookLoadOutput {
                 code: js_code.into(),
@@ -222,10 +318,12 @@ if (import.meta.hot) {{
         ctx: &PluginContext,
         args: &mut HookGenerateBundleArgs<'_>,
     ) -> rolldown_plugin::HookNoopReturn {
-        // 1. Populate Store (if present)
+        // 1. Populate Store (if present), dropping assets from previous builds
         if let Some(store) = &self.store {
-            for output in args.bundle.iter() {
-                match output {
+            let assets = args
+                .bundle
+                .iter()
+                .map(|output| match output {
                     Output::Asset(a) => {
                         // Attempt to extract source string
                         // rolldown_common::StrOrBytes (Assuming Str/Bytes variants)
@@ -233,20 +331,21 @@ if (import.meta.hot) {{
                             StrOrBytes::Str(s) => s.to_string(),
                             StrOrBytes::Bytes(b) => String::from_utf8_lossy(b).to_string(),
                         };
-                        store.update(a.filename.to_string(), source);
-                    }
-                    Output::Chunk(c) => {
-                        store.update(c.filename.to_string(), c.code.clone());
+                        (a.filename.to_string(), source)
                     }
-                }
-            }
+                    Output::Chunk(c) => (c.filename.to_string(), c.code.clone()),
+                })
+                .collect();
+            store.replace_all(assets);
         }
 
+        // Dev keeps the stylesheet readable; production output is minified
         let used_classes = self.used_classes();
-        let css_content = self
+        let (css_content, report) = self
             .css_buffer
-            .stitch_and_prune(&used_classes)
+            .stitch_and_prune_with_report(&used_classes, !self.is_dev)
             .map_err(|e| anyhow::anyhow!("{}", e))?;
+        *self.prune_report.write().expect("prune report poisoned") = report;
 
         if !css_content.is_empty() {
             // Emit the CSS asset
@@ -269,9 +368,29 @@ impl ZenithPlugin {
     /// This is the entry point that:
     /// 1. Immediately: Sets up event delegation (zero-cost, <2KB)
     /// 2. Deferred: Imports the actual app logic via dynamic import
-    /// 3. Trigger: Idle callback or timeout
+    /// 3. Trigger: per the configured [`HydrationStrategy`]
     fn generate_hydration_controller(&self) -> String {
         let entry = &self.entry_point;
+        let trigger = match self.hydration_strategy {
+            HydrationStrategy::Idle { timeout_ms } => format!(
+                r#"// 3. Trigger: Idle
+if ('requestIdleCallback' in window) {{
+    requestIdleCallback(hydrate, {{ timeout: {timeout_ms} }});
+}} else {{
+    // Fallback for Safari/older browsers
+    setTimeout(hydrate, 200);
+}}"#
+            ),
+            HydrationStrategy::Eager => "// 3. Trigger: Eager\nhydrate();".to_string(),
+            HydrationStrategy::OnInteraction => r#"// 3. Trigger: First interaction (one-shot)
+const events = ['pointerdown', 'keydown'];
+const onInteraction = () => {
+    events.forEach((type) => window.removeEventListener(type, onInteraction, true));
+    hydrate();
+};
+events.forEach((type) => window.addEventListener(type, onInteraction, { capture: true, once: true, passive: true }));"#
+                .to_string(),
+        };
         format!(
             r#"
 // === ZENITH HYDRATION CONTROLLER ===
@@ -286,13 +405,7 @@ delegateEvents();
 // We wrap the user's entry in a dynamic import to keep it off the main thread
 const hydrate = () => import('{entry}');
 
-// 3. Trigger: Interaction or Idle
-if ('requestIdleCallback' in window) {{
-    requestIdleCallback(hydrate, {{ timeout: 2000 }});
-}} else {{
-    // Fallback for Safari/older browsers
-    setTimeout(hydrate, 200);
-}}
+{trigger}
 "#
         )
     }
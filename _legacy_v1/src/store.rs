@@ -4,6 +4,7 @@
 //! for memory-only serving in dev mode.
 
 use dashmap::DashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 /// Thread-safe in-memory asset store
@@ -23,12 +24,22 @@ impl AssetStore {
     /// Update asset content
     /// Automatically ensures path starts with /
     pub fn update(&self, path: String, content: String) {
-        let normalized = if path.starts_with('/') {
-            path
-        } else {
-            format!("/{}", path)
-        };
-        self.assets.insert(normalized, content);
+        self.assets.insert(normalize_path(path), content);
+    }
+
+    /// Replace the store's contents with one build's assets.
+    ///
+    /// New assets are inserted before stale ones are swept, so a concurrent
+    /// `get` never misses an asset of the current build. Anything not in
+    /// `assets` (e.g. `app-OLDHASH.js` from a previous rebuild) is removed.
+    pub fn replace_all(&self, assets: Vec<(String, String)>) {
+        let mut current = HashSet::with_capacity(assets.len());
+        for (path, content) in assets {
+            let normalized = normalize_path(path);
+            current.insert(normalized.clone());
+            self.assets.insert(normalized, content);
+        }
+        self.assets.retain(|path, _| current.contains(path));
     }
 
     /// Retrieve asset content
@@ -42,3 +53,35 @@ impl Default for AssetStore {
         Self::new()
     }
 }
+
+/// Ensure a store path starts with `/`.
+fn normalize_path(path: String) -> String {
+    if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_all_evicts_assets_missing_from_new_build() {
+        let store = AssetStore::new();
+        store.replace_all(vec![
+            ("app-OLDHASH.js".into(), "old".into()),
+            ("zenith.css".into(), "a{}".into()),
+        ]);
+        assert_eq!(store.get("/app-OLDHASH.js").as_deref(), Some("old"));
+
+        store.replace_all(vec![
+            ("app-NEWHASH.js".into(), "new".into()),
+            ("zenith.css".into(), "b{}".into()),
+        ]);
+        assert_eq!(store.get("/app-OLDHASH.js"), None);
+        assert_eq!(store.get("/app-NEWHASH.js").as_deref(), Some("new"));
+        assert_eq!(store.get("/zenith.css").as_deref(), Some("b{}"));
+    }
+}
@@ -4,84 +4,248 @@
 //! 1. Read and compile the `.zen` source via the ZenithLoader plugin
 //! 2. Run through Rolldown for import resolution and graph building
 //! 3. Validate output against metadata
-//! 4. Return sealed BundleResult
+//! 4. Prune and minify page CSS (Prod / SSG)
+//! 5. Return sealed BundleResult
 //!
 //! **Single emission engine.** All builds go through Rolldown.
 //! There is one graph, one emission flow, one source of truth.
 //! No inline bypass is permitted — determinism requires a unified pipeline.
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use rolldown::{BundlerBuilder, BundlerOptions, InputItem};
-use rolldown_common::OutputFormat;
+use rolldown::{BuildDiagnostic, BundlerBuilder, BundlerOptions, InputItem};
+use rolldown_common::{
+    AdvancedChunksOptions, MatchGroup, MatchGroupTest, MinifyOptionsObject, OutputFormat,
+    RawMinifyOptions,
+};
+use rolldown_utils::js_regex::HybridRegex;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 
-use crate::plugin::zenith_loader::{ZenithLoader, ZenithLoaderConfig};
-use crate::utils;
+use crate::plugin::zenith_loader::{self, ZenithLoader, ZenithLoaderConfig};
+use crate::utils::AssetKind;
 use crate::{
-    BuildMode, BundleError, BundleOptions, BundlePlan, BundleResult, Diagnostic, DiagnosticLevel,
+    codes, BuildManifest, BuildMode, BundleError, BundleOptions, BundlePlan, BundleResult,
+    BundleStats, CompilerOutput, Diagnostic, DiagnosticLevel, ManifestPage, MinifyMode,
 };
+use crate::{html, utils};
+
+/// Outputs larger than this are streamed to disk instead of written at once.
+const STREAM_WRITE_THRESHOLD: usize = 1024 * 1024;
+
+/// Size of each streamed write.
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Name of the manifest written to `out_dir` when `emit_manifest` is set.
+pub const MANIFEST_FILE: &str = "build-manifest.json";
+
+/// Output directory used when a written build's plan leaves `out_dir` unset.
+pub const DEFAULT_OUT_DIR: &str = "dist";
 
 // ---------------------------------------------------------------------------
 // Single emission engine — all builds go through Rolldown
 // ---------------------------------------------------------------------------
 
+/// Execute the bundle pipeline for a single page.
+///
+/// This is [`execute_bundle_pages`] with a one-element plan list — there is
+/// no separate single-page codepath.
+pub async fn execute_bundle(
+    plan: BundlePlan,
+    opts: BundleOptions,
+) -> Result<BundleResult, BundleError> {
+    execute_bundle_pages(vec![plan], opts)
+        .await?
+        .pop()
+        .ok_or_else(|| BundleError::BuildError("No result for bundled page".into()))
+}
+
+/// Execute the bundle pipeline for a single page, aborting with
+/// [`BundleError::Cancelled`] if `cancel` is tripped.
+///
+/// The token is checked before Rolldown's `generate()` and again once it
+/// returns; a build already inside `generate()` runs to completion first.
+pub async fn execute_bundle_cancellable(
+    plan: BundlePlan,
+    opts: BundleOptions,
+    cancel: &CancellationToken,
+) -> Result<BundleResult, BundleError> {
+    run_bundle_pages(vec![plan], opts, Some(cancel))
+        .await?
+        .pop()
+        .ok_or_else(|| BundleError::BuildError("No result for bundled page".into()))
+}
+
 /// Execute the bundle pipeline using Rolldown as the single emission engine.
 ///
-/// This creates a ZenithLoader plugin, wires it into Rolldown via
-/// `BundlerBuilder`, runs the full build, and validates the output.
+/// Every plan becomes one `InputItem` (named by its page id) in a single
+/// Rolldown graph driven by one ZenithLoader, so shared modules are loaded
+/// and compiled once and the compiled-output map and `CssCache` are shared
+/// across pages. Each entry chunk is then validated and finalized on its own.
 ///
 /// **Invariant:** There is no alternative codepath. Every build —
 /// single-page, multi-page, dev, prod — runs through this function.
-pub async fn execute_bundle(
-    plan: BundlePlan,
+/// Results are returned in plan order.
+pub async fn execute_bundle_pages(
+    plans: Vec<BundlePlan>,
     opts: BundleOptions,
-) -> Result<BundleResult, BundleError> {
-    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+) -> Result<Vec<BundleResult>, BundleError> {
+    run_bundle_pages(plans, opts, None).await
+}
 
-    let page_id = utils::canonicalize_page_id(&plan.page_path);
+async fn run_bundle_pages(
+    plans: Vec<BundlePlan>,
+    mut opts: BundleOptions,
+    cancel: Option<&CancellationToken>,
+) -> Result<Vec<BundleResult>, BundleError> {
+    let Some(mode) = plans.first().map(|plan| plan.mode) else {
+        return Ok(Vec::new());
+    };
+    if let Some(plan) = plans.iter().find(|plan| plan.mode != mode) {
+        return Err(BundleError::ValidationError(format!(
+            "All pages in one build must share a build mode: {} is {:?}, expected {:?}",
+            plan.page_path, plan.mode, mode
+        )));
+    }
 
-    // Pre-build: verify source file exists (clean IoError)
-    if !Path::new(&plan.page_path).exists() {
-        return Err(BundleError::IoError(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Source file not found: {}", plan.page_path),
+    if opts.metadata.is_some() && plans.len() > 1 {
+        return Err(BundleError::ValidationError(format!(
+            "`metadata` describes a single page, but {} pages were given",
+            plans.len()
         )));
     }
 
-    diagnostics.push(Diagnostic {
-        level: DiagnosticLevel::Info,
-        message: format!(
-            "Bundle started for page: {} (id: {})",
-            plan.page_path, page_id
-        ),
-        context: None,
-    });
+    utils::validate_contract_version(&opts.contract_version)?;
+    utils::check_scope_collisions(
+        plans.iter().map(|plan| plan.page_path.as_str()),
+        opts.project_root.as_deref(),
+    )?;
+    validate_import_map(&opts)?;
+    validate_externalize_bindings(&opts, mode)?;
+
+    // Compiled expressions are normalized in the loader; strict validation
+    // compares metadata against them in the same form
+    if opts.normalize_expression_whitespace {
+        let module_metadata = opts
+            .module_metadata
+            .iter_mut()
+            .flat_map(|map| map.values_mut());
+        for metadata in opts.metadata.iter_mut().chain(module_metadata) {
+            for expr in &mut metadata.expressions {
+                *expr = utils::normalize_expression_whitespace(expr);
+            }
+        }
+    }
+
+    let minify = opts
+        .minify
+        .unwrap_or(MinifyMode::from(mode == BuildMode::Prod));
+
+    let mut pages = Vec::with_capacity(plans.len());
+    for plan in plans {
+        let page_id = utils::canonicalize_page_id(&plan.page_path, opts.project_root.as_deref());
+
+        // Pre-build: verify source file exists (clean IoError). A content
+        // resolver or a pre-loaded component source may serve ids that
+        // never touch the filesystem.
+        if opts.content_resolver.is_none()
+            && zenith_loader::preloaded_source(opts.components.as_ref(), &plan.page_path).is_none()
+            && !Path::new(&plan.page_path).exists()
+        {
+            return Err(BundleError::IoError(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Source file not found: {}", plan.page_path),
+            )));
+        }
+
+        let diagnostics = vec![
+            Diagnostic {
+                level: DiagnosticLevel::Info,
+                message: format!(
+                    "Bundle started for page: {} (id: {})",
+                    plan.page_path, page_id
+                ),
+                context: None,
+                code: None,
+            },
+            Diagnostic {
+                level: DiagnosticLevel::Info,
+                message: format!("JS minification: {:?}", minify),
+                context: None,
+                code: Some(codes::MINIFY_MODE.into()),
+            },
+        ];
+        pages.push((plan, page_id, diagnostics));
+    }
+
+    let emit_sourcemap = opts.sourcemap.unwrap_or(mode == BuildMode::Dev);
 
-    // Create the loader plugin
+    // At most `concurrency` `.zen` modules are read and compiled at once
+    // during the graph pass, and as many pages finalized after it
+    let limit = opts
+        .concurrency
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+        .max(1);
+    let semaphore = Arc::new(Semaphore::new(limit));
+
+    // Create the loader plugin — one instance for the whole graph
     let loader = ZenithLoader::new(ZenithLoaderConfig {
         components: opts.components.clone(),
         metadata: opts.metadata.clone(),
+        module_metadata: opts.module_metadata.clone(),
         strict: opts.strict,
-        is_dev: plan.mode == BuildMode::Dev,
+        is_dev: mode == BuildMode::Dev,
+        default_export: opts.default_export,
+        sourcemap: emit_sourcemap,
+        sourcemap_sources_content: opts.sourcemap_sources_content,
+        content_resolver: opts.content_resolver.clone(),
+        compile_cache: opts.compile_cache.clone(),
+        contract_version: opts.contract_version.clone(),
+        runtime_import: opts.runtime_import.clone(),
+        external: opts.external.clone(),
+        normalize_expression_whitespace: opts.normalize_expression_whitespace,
+        project_root: opts.project_root.clone(),
+        externalize_bindings: opts.externalize_bindings,
+        compile_permits: Some(Arc::clone(&semaphore)),
     });
+    let loader = match opts.css_cache {
+        Some(ref css_cache) => loader.with_css_cache(Arc::clone(css_cache)),
+        None => loader,
+    };
 
     let compiled_outputs = loader.compiled_outputs();
+    let load_error = loader.load_error();
     let css_cache = loader.css_cache();
 
-    // Configure Rolldown — single-entry, ESM, browser
+    // Configure Rolldown — one entry per page, ESM, browser
     let rolldown_options = BundlerOptions {
-        input: Some(vec![InputItem {
-            name: Some("index".into()),
-            import: plan.page_path.clone(),
-        }]),
+        input: Some(
+            pages
+                .iter()
+                .map(|(plan, page_id, _)| InputItem {
+                    name: Some(page_id.clone()),
+                    import: plan.page_path.clone(),
+                })
+                .collect(),
+        ),
         format: Some(OutputFormat::Esm),
         platform: Some(rolldown_common::Platform::Browser),
-        minify: if opts.minify.unwrap_or(plan.mode == BuildMode::Prod) {
-            Some(Default::default())
-        } else {
-            None
-        },
+        minify: rolldown_minify(minify),
+        sourcemap: emit_sourcemap.then_some(rolldown_common::SourceMapType::Hidden),
+        advanced_chunks: manual_chunk_groups(&opts.manual_chunks)?.map(|groups| {
+            AdvancedChunksOptions {
+                groups: Some(groups),
+                ..Default::default()
+            }
+        }),
         ..Default::default()
     };
 
@@ -92,11 +256,19 @@ pub async fn execute_bundle(
         .build()
         .map_err(|e| BundleError::BuildError(format!("Rolldown init failed: {:?}", e)))?;
 
-    // Run the bundling pass
-    let bundle_output = bundler
-        .generate()
-        .await
-        .map_err(|e| BundleError::BuildError(format!("Rolldown build failed: {:?}", e)))?;
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(BundleError::Cancelled);
+    }
+
+    // Run the bundling pass. A loader error (e.g. strict validation of an
+    // imported module) is returned as-is rather than as Rolldown's message.
+    let bundle_output = bundler.generate().await.map_err(|e| {
+        load_error
+            .lock()
+            .expect("load error slot poisoned")
+            .take()
+            .unwrap_or_else(|| BundleError::BuildError(format!("Rolldown build failed: {:?}", e)))
+    })?;
 
     // Close the bundler
     bundler
@@ -104,40 +276,419 @@ pub async fn execute_bundle(
         .await
         .map_err(|e| BundleError::BuildError(format!("Rolldown close failed: {:?}", e)))?;
 
-    // Extract the entry chunk
-    let entry_js = bundle_output
+    // A newer request may have superseded this build while Rolldown ran
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(BundleError::Cancelled);
+    }
+
+    // Rolldown's own warnings are graph-wide; every page reports them
+    let warnings = rolldown_warnings(&bundle_output.warnings);
+    if opts.strict {
+        if let Some(warning) = warnings.iter().find(|d| {
+            d.code
+                .as_deref()
+                .is_some_and(|code| opts.strict_warnings.iter().any(|c| c == code))
+        }) {
+            return Err(BundleError::RolldownWarning {
+                code: warning.code.as_deref().unwrap_or_default().to_string(),
+                message: warning.message.clone(),
+            });
+        }
+    }
+
+    // Chunk filename → chunks it statically imports — never `import()` targets
+    let static_imports: HashMap<String, Vec<String>> = bundle_output
         .assets
         .iter()
-        .find_map(|asset| match asset {
-            rolldown_common::Output::Chunk(chunk) => Some(chunk.code.clone()),
+        .filter_map(|asset| match asset {
+            rolldown_common::Output::Chunk(chunk) => Some((
+                chunk.filename.to_string(),
+                chunk.imports.iter().map(|i| i.to_string()).collect(),
+            )),
             _ => None,
         })
-        .ok_or_else(|| BundleError::BuildError("No entry chunk in Rolldown output".into()))?;
+        .collect();
 
-    // Strip non-deterministic comments (Rolldown emits //#region with absolute paths)
-    // Also normalizes line endings to \n
-    let entry_js = entry_js
-        .lines()
-        .filter(|line| !line.starts_with("//#region") && !line.starts_with("//#endregion"))
-        .collect::<Vec<_>>()
-        .join("\n");
+    // Manual chunk name → emitted filename
+    let mut manual_chunks: Vec<(String, String)> = bundle_output
+        .assets
+        .iter()
+        .filter_map(|asset| match asset {
+            rolldown_common::Output::Chunk(chunk)
+                if !chunk.is_entry
+                    && opts
+                        .manual_chunks
+                        .iter()
+                        .any(|(name, _)| name == chunk.name.as_str()) =>
+            {
+                Some((chunk.name.to_string(), chunk.filename.to_string()))
+            }
+            _ => None,
+        })
+        .collect();
+    manual_chunks.sort();
+
+    // Non-entry chunks (shared, dynamic and manual), written beside the
+    // entries that import them
+    let mut shared_chunks: Vec<(String, String)> = bundle_output
+        .assets
+        .iter()
+        .filter_map(|asset| match asset {
+            rolldown_common::Output::Chunk(chunk) if !chunk.is_entry => Some((
+                chunk.filename.to_string(),
+                strip_region_comments(&chunk.code, opts.preserve_region_comments).0,
+            )),
+            _ => None,
+        })
+        .collect();
+    shared_chunks.sort();
+
+    let mut emitted = Vec::with_capacity(pages.len());
+    for (plan, page_id, mut diagnostics) in pages {
+        diagnostics.extend(warnings.iter().cloned());
+
+        // Extract the page's entry chunk
+        let entry_chunk = bundle_output
+            .assets
+            .iter()
+            .find_map(|asset| match asset {
+                rolldown_common::Output::Chunk(chunk)
+                    if chunk.is_entry && chunk.name.as_str() == page_id =>
+                {
+                    Some(chunk)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                BundleError::BuildError(format!(
+                    "No entry chunk in Rolldown output for {}",
+                    plan.page_path
+                ))
+            })?;
+
+        // An inlined entry has no file beside it for its relative chunk
+        // imports to resolve against, and the chunks are not written
+        if opts.inline_assets {
+            let chunk_imports: Vec<&str> = entry_chunk
+                .imports
+                .iter()
+                .chain(entry_chunk.dynamic_imports.iter())
+                .map(|import| import.as_str())
+                .filter(|import| static_imports.contains_key(*import))
+                .collect();
+            if !chunk_imports.is_empty() {
+                return Err(BundleError::ValidationError(format!(
+                    "`inline_assets` cannot inline {}: its entry imports the chunks {}",
+                    plan.page_path,
+                    chunk_imports.join(", ")
+                )));
+            }
+        }
 
-    // Get compiled output for the page (stored by the plugin during load)
-    let compiled = compiled_outputs
-        .get(&plan.page_path)
-        .map(|entry| entry.value().clone())
-        .unwrap_or_default();
+        emitted.push(EmittedPage {
+            entry_js: entry_chunk.code.clone(),
+            entry_map: entry_chunk.map.as_ref().map(|map| map.to_json_string()),
+            preload_chunks: utils::static_import_closure(&entry_chunk.filename, &static_imports),
+            manual_chunks: manual_chunks.clone(),
+            // Get compiled output for the page (stored by the plugin during load)
+            compiled: compiled_outputs
+                .get(&plan.page_path)
+                .map(|entry| entry.value().clone()),
+            css: css_cache.get(&page_id),
+            plan,
+            page_id,
+            diagnostics,
+        });
+    }
+
+    // Finalize pages, at most `concurrency` at once (JS validation and disk
+    // writes are the expensive part). Results are collected in plan order.
+    // Read the HTML template once for the whole build
+    let template: Option<Arc<str>> = match opts.html_template {
+        Some(ref path) if !opts.analyze && (mode == BuildMode::SSG || opts.write_to_disk) => {
+            Some(tokio::fs::read_to_string(path).await?.into())
+        }
+        _ => None,
+    };
+
+    let handles: Vec<_> = emitted
+        .into_iter()
+        .map(|page| {
+            let semaphore = Arc::clone(&semaphore);
+            let opts = opts.clone();
+            let template = template.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| BundleError::BuildError(format!("Semaphore closed: {}", e)))?;
+                finalize_page(page, &opts, template.as_deref()).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(handles.len());
+    let mut manifests: BTreeMap<PathBuf, Vec<ManifestPage>> = BTreeMap::new();
+    for handle in handles {
+        let (result, written) = handle
+            .await
+            .map_err(|e| BundleError::BuildError(format!("Page build task failed: {}", e)))??;
+        if let Some((out_dir, page)) = written {
+            manifests.entry(out_dir).or_default().push(page);
+        }
+        results.push(result);
+    }
+
+    // Entries import their chunks relatively, so each out_dir that received
+    // pages gets them in the entries' directory. Inlined documents have no
+    // entry file to sit beside.
+    let chunk_dir = match mode {
+        _ if opts.analyze || opts.inline_assets => None,
+        BuildMode::SSG => Some("assets"),
+        _ if opts.write_to_disk => Some("pages"),
+        _ => None,
+    };
+    let mut chunk_files = Vec::new();
+    if let Some(dir) = chunk_dir {
+        for out_dir in manifests.keys() {
+            for (filename, code) in &shared_chunks {
+                write_output(&out_dir.join(dir).join(filename), code).await?;
+            }
+        }
+        chunk_files = shared_chunks
+            .iter()
+            .map(|(filename, _)| format!("{}/{}", dir, filename))
+            .collect();
+    }
+
+    if opts.emit_manifest {
+        for (out_dir, mut pages) in manifests {
+            pages.sort_by(|a, b| a.page_id.cmp(&b.page_id));
+            let manifest = BuildManifest {
+                pages,
+                chunks: chunk_files.clone(),
+            };
+            let json = serde_json::to_string_pretty(&manifest).map_err(|e| {
+                BundleError::BuildError(format!("Manifest serialization failed: {}", e))
+            })?;
+            write_output(&out_dir.join(MANIFEST_FILE), &format!("{}\n", json)).await?;
+        }
+    }
+
+    Ok(results)
+}
+
+/// Convert Rolldown build warnings into `Warning` diagnostics.
+///
+/// The event kind, lowercased, becomes the code (see [`codes::UNRESOLVED_IMPORT`]);
+/// the module id, when Rolldown reports one, becomes the context. Sorted so the
+/// report does not depend on the order Rolldown raised them in.
+fn rolldown_warnings(warnings: &[BuildDiagnostic]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = warnings
+        .iter()
+        .map(|warning| Diagnostic {
+            level: DiagnosticLevel::Warning,
+            message: warning.to_diagnostic().to_string(),
+            context: warning.id(),
+            code: Some(warning.kind().to_string().to_lowercase().into()),
+        })
+        .collect();
+    diagnostics
+        .sort_by(|a, b| (&a.code, &a.context, &a.message).cmp(&(&b.code, &b.context, &b.message)));
+    diagnostics
+}
+
+/// Rolldown's minify options for a [`MinifyMode`].
+fn rolldown_minify(mode: MinifyMode) -> Option<RawMinifyOptions> {
+    match mode {
+        MinifyMode::Off => None,
+        MinifyMode::Whitespace => Some(RawMinifyOptions::Object(MinifyOptionsObject {
+            mangle: false,
+            compress: false,
+            remove_whitespace: true,
+        })),
+        MinifyMode::Full => Some(RawMinifyOptions::Bool(true)),
+    }
+}
+
+/// Check that every `BundleOptions.import_map` specifier is one the loader
+/// leaves external; a mapping for a bundled module would never be used.
+fn validate_import_map(opts: &BundleOptions) -> Result<(), BundleError> {
+    let mut specifiers: Vec<&String> = opts.import_map.keys().collect();
+    specifiers.sort();
+    for specifier in specifiers {
+        if !utils::is_external(specifier, &opts.external)
+            && opts.runtime_import.as_deref() != Some(specifier.as_str())
+        {
+            return Err(BundleError::ValidationError(format!(
+                "Import map specifier '{}' is not declared in `external`",
+                specifier
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `BundleOptions.externalize_bindings` has tables to move out
+/// (only hydration entries carry them) and a file to move them into: the
+/// sidecar is only written by `SSG` and `write_to_disk` builds.
+fn validate_externalize_bindings(opts: &BundleOptions, mode: BuildMode) -> Result<(), BundleError> {
+    if !opts.externalize_bindings {
+        return Ok(());
+    }
+    if opts.runtime_import.is_none() {
+        return Err(BundleError::ValidationError(
+            "`externalize_bindings` requires `runtime_import`".into(),
+        ));
+    }
+    if opts.inline_assets {
+        return Err(BundleError::ValidationError(
+            "`externalize_bindings` cannot be combined with `inline_assets`".into(),
+        ));
+    }
+    if mode != BuildMode::SSG && !opts.write_to_disk {
+        return Err(BundleError::ValidationError(format!(
+            "`externalize_bindings` requires an `SSG` or `write_to_disk` build to write the \
+             sidecar; {:?} builds without `write_to_disk` write no files",
+            mode
+        )));
+    }
+    Ok(())
+}
+
+/// Translate `BundleOptions.manual_chunks` into Rolldown match groups.
+///
+/// Earlier entries get a higher priority, so a module matching several
+/// chunks lands in the first one listed. `None` when no chunks are set.
+fn manual_chunk_groups(
+    chunks: &[(String, Vec<String>)],
+) -> Result<Option<Vec<MatchGroup>>, BundleError> {
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
+    let mut groups = Vec::with_capacity(chunks.len());
+    for (position, (name, patterns)) in chunks.iter().enumerate() {
+        if name.is_empty() || patterns.is_empty() {
+            return Err(BundleError::ValidationError(format!(
+                "Manual chunk '{}' needs a name and at least one module pattern",
+                name
+            )));
+        }
+        if chunks[..position].iter().any(|(other, _)| other == name) {
+            return Err(BundleError::ValidationError(format!(
+                "Manual chunk '{}' is defined more than once",
+                name
+            )));
+        }
+        let test = HybridRegex::new(&utils::manual_chunk_regex(patterns)).map_err(|e| {
+            BundleError::ValidationError(format!(
+                "Manual chunk '{}' has an invalid pattern: {}",
+                name, e
+            ))
+        })?;
+        groups.push(MatchGroup {
+            name: name.clone(),
+            test: Some(MatchGroupTest::Regex(test)),
+            priority: Some((chunks.len() - position) as u32),
+            ..Default::default()
+        });
+    }
+    Ok(Some(groups))
+}
+
+/// One page's share of the Rolldown output, before validation.
+struct EmittedPage {
+    plan: BundlePlan,
+    page_id: String,
+    entry_js: String,
+    entry_map: Option<String>,
+    preload_chunks: Vec<String>,
+    manual_chunks: Vec<(String, String)>,
+    compiled: Option<CompilerOutput>,
+    css: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Strip, validate and (optionally) write a single page's output.
+///
+/// When the page is written, also returns its `out_dir` and manifest entry.
+async fn finalize_page(
+    page: EmittedPage,
+    opts: &BundleOptions,
+    template: Option<&str>,
+) -> Result<(BundleResult, Option<(PathBuf, ManifestPage)>), BundleError> {
+    let EmittedPage {
+        plan,
+        page_id,
+        entry_js,
+        entry_map,
+        preload_chunks,
+        manual_chunks,
+        compiled,
+        css,
+        mut diagnostics,
+    } = page;
+
+    let (entry_js, dropped_lines) = strip_region_comments(&entry_js, opts.preserve_region_comments);
+
+    // Keep the sourcemap aligned with the stripped lines
+    let source_map = entry_map
+        .map(|json| utils::drop_sourcemap_lines(&json, &dropped_lines))
+        .transpose()?;
+
+    if opts.validate_js {
+        utils::validate_js_syntax(&entry_js, &plan.page_path)?;
+    }
+
+    let recorded = compiled.is_some();
+    let compiled = match compiled {
+        Some(compiled) => compiled,
+        None => {
+            diagnostics.push(Diagnostic {
+                level: DiagnosticLevel::Warning,
+                message: format!("No compiled output recorded for {}", plan.page_path),
+                context: Some("Post-build validation ran against an empty expression table".into()),
+                code: Some(codes::COMPILED_OUTPUT_MISSING.into()),
+            });
+            CompilerOutput::default()
+        }
+    };
+
+    // The same tables the loader hashed into the entry's sidecar reference
+    let bindings = if opts.externalize_bindings && recorded {
+        Some(utils::generate_bindings_json(&compiled)?)
+    } else {
+        None
+    };
 
     let expressions = compiled.expressions.clone();
+    if expressions.is_empty() {
+        diagnostics.push(Diagnostic {
+            level: DiagnosticLevel::Info,
+            message: format!("Page {} has no expressions", page_id),
+            context: None,
+            code: Some(codes::STATIC_PAGE.into()),
+        });
+    }
 
     // Post-build strict validation
     if opts.strict {
         // 1. Verify expressions match metadata
         if let Some(ref metadata) = opts.metadata {
-            utils::validate_expressions(&expressions, &metadata.expressions)?;
+            utils::validate_expressions(&expressions, &metadata.expressions, &compiled.html)?;
         }
 
-        // 2. Verify HTML contains required placeholders
+        // 2. Verify marker bindings cover the expression table
+        utils::validate_marker_bindings(
+            compiled.marker_bindings.iter().map(|marker| marker.index),
+            expressions.len(),
+        )?;
+
+        // 3. Flag event handlers that cannot be called
+        diagnostics.extend(utils::check_event_handlers(&compiled));
+
+        // 4. Verify HTML contains required placeholders
         if !expressions.is_empty() {
             if let Err(diags) = utils::validate_placeholders(&compiled.html, expressions.len()) {
                 return Err(BundleError::ValidationError(
@@ -151,8 +702,17 @@ pub async fn execute_bundle(
         }
     }
 
-    // Collect CSS
-    let css = css_cache.get(&page_id);
+    // Tree-shake CSS outside of Dev (skipped for speed there)
+    let css = match css {
+        Some(css) if plan.mode != BuildMode::Dev => {
+            match crate::css::collect_used_classes(&compiled.html) {
+                Some(used) => Some(crate::css::prune_css(&css, &plan.page_path, &used)?)
+                    .filter(|pruned| !pruned.is_empty()),
+                None => Some(css),
+            }
+        }
+        css => css,
+    };
 
     diagnostics.push(Diagnostic {
         level: DiagnosticLevel::Info,
@@ -163,35 +723,332 @@ pub async fn execute_bundle(
             css.as_ref().map_or(0, |c| c.len()),
         ),
         context: None,
+        code: None,
     });
 
-    // Write to disk if requested
-    if opts.write_to_disk {
-        let out_dir = plan
-            .out_dir
-            .unwrap_or_else(|| Path::new("dist").to_path_buf());
+    // Promote configured diagnostic codes to failures (before anything hits disk)
+    if let Some(diag) = diagnostics.iter().find(|d| {
+        d.code
+            .as_deref()
+            .is_some_and(|code| opts.fail_on_codes.iter().any(|c| c == code))
+    }) {
+        return Err(BundleError::FailOnCode {
+            code: diag.code.as_deref().unwrap_or_default().to_string(),
+            message: diag.message.clone(),
+        });
+    }
+
+    // Size budgets, against the bytes that ship
+    for (kind, limit, actual) in [
+        (AssetKind::Entry, opts.max_entry_bytes, entry_js.len()),
+        (
+            AssetKind::Css,
+            opts.max_css_bytes,
+            css.as_ref().map_or(0, |c| c.len()),
+        ),
+    ] {
+        if let Some(limit) = limit.filter(|limit| actual > *limit) {
+            return Err(BundleError::BudgetExceeded {
+                kind,
+                limit,
+                actual,
+            });
+        }
+    }
+
+    let stats = opts.analyze.then(|| BundleStats {
+        expression_count: expressions.len(),
+        entry_js_bytes: entry_js.len(),
+        css_bytes: css.as_ref().map_or(0, |c| c.len()),
+        chunk_count: 1 + preload_chunks.len(),
+        component_instance_count: compiled.component_instances.len(),
+    });
+
+    // SSG always writes a full site layout: hashed assets plus an HTML
+    // document, or a single document with `inline_assets`. Analyze builds
+    // write nothing.
+    let mut html = None;
+    let mut written = None;
+    let mut assets = Vec::new();
+    if opts.analyze {
+        diagnostics.push(Diagnostic {
+            level: DiagnosticLevel::Info,
+            message: format!("Analyze build: no files written for {}", page_id),
+            context: None,
+            code: None,
+        });
+    } else if opts.inline_assets && (plan.mode == BuildMode::SSG || opts.write_to_disk) {
+        let out_dir = plan_out_dir(&plan);
+        // One self-contained document: at the page's route for SSG, under
+        // `pages/` for `write_to_disk`
+        let html_file = if plan.mode == BuildMode::SSG {
+            utils::route_to_output_path(&utils::page_route(&plan.page_path))
+                .to_string_lossy()
+                .replace('\\', "/")
+        } else {
+            format!("pages/{}.html", page_id)
+        };
+        let document =
+            html::render_inline_page_document(template, &compiled.html, &entry_js, css.as_deref());
+        let document = html::inject_import_map(&document, &opts.import_map);
+        let html_path = out_dir.join(&html_file);
+        if let Some(parent) = html_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_output(&html_path, &document).await?;
+
+        diagnostics.push(Diagnostic {
+            level: DiagnosticLevel::Info,
+            message: format!("Written to {}", html_path.display()),
+            context: None,
+            code: None,
+        });
+        html = Some(document);
+        assets.push((format!("{}.html", page_id), html_file.clone()));
+        written = Some((out_dir, (html_file, None)));
+    } else if plan.mode == BuildMode::SSG {
+        let out_dir = plan_out_dir(&plan);
+        let (document, html_path, (entry_file, css_file)) = write_ssg_page(
+            &out_dir,
+            &plan.page_path,
+            &page_id,
+            template,
+            &compiled.html,
+            &entry_js,
+            css.as_deref(),
+            &preload_chunks,
+            &opts.import_map,
+        )
+        .await?;
+
+        diagnostics.push(Diagnostic {
+            level: DiagnosticLevel::Info,
+            message: format!("Written to {}", html_path.display()),
+            context: None,
+            code: None,
+        });
+        html = Some(document);
+        assets.push((format!("{}.js", page_id), entry_file.clone()));
+        if let Some(ref css_file) = css_file {
+            assets.push((format!("{}.css", page_id), css_file.clone()));
+        }
+        if let Some(ref json) = bindings {
+            let file = write_bindings(&out_dir, "assets", &page_id, json).await?;
+            assets.push((format!("{}.bindings.json", page_id), file));
+        }
+        written = Some((out_dir, (entry_file, css_file)));
+    } else if opts.write_to_disk {
+        let out_dir = plan_out_dir(&plan);
         let pages_dir = out_dir.join("pages");
         tokio::fs::create_dir_all(&pages_dir).await?;
 
-        let js_path = pages_dir.join(format!("{}.js", page_id));
-        tokio::fs::write(&js_path, &entry_js).await?;
+        // `{id}.js`, or `{id}.{hash}.js` with hashed filenames
+        let file_name = |content: &str, ext: &str| {
+            if opts.hashed_filenames {
+                format!("pages/{}.{}.{}", page_id, utils::content_hash(content), ext)
+            } else {
+                format!("pages/{}.{}", page_id, ext)
+            }
+        };
+
+        let js_file = file_name(&entry_js, "js");
+        write_output(&out_dir.join(&js_file), &entry_js).await?;
+        assets.push((format!("{}.js", page_id), js_file.clone()));
+
+        if let Some(ref map) = source_map {
+            let map_file = format!("{}.map", js_file);
+            write_output(&out_dir.join(&map_file), map).await?;
+            assets.push((format!("{}.js.map", page_id), map_file));
+        }
 
-        if let Some(ref css_content) = css {
-            let css_path = pages_dir.join(format!("{}.css", page_id));
-            tokio::fs::write(&css_path, css_content).await?;
+        let css_file = css.as_deref().map(|css| file_name(css, "css"));
+        if let (Some(css_content), Some(css_file)) = (&css, &css_file) {
+            write_output(&out_dir.join(css_file), css_content).await?;
+            assets.push((format!("{}.css", page_id), css_file.clone()));
+        }
+
+        if let Some(ref json) = bindings {
+            let file = write_bindings(&out_dir, "pages", &page_id, json).await?;
+            assets.push((format!("{}.bindings.json", page_id), file));
+        }
+
+        if let Some(template) = template {
+            let document = html::render_page_document(
+                Some(template),
+                &compiled.html,
+                &format!("/{}", js_file),
+                css_file.as_ref().map(|f| format!("/{}", f)).as_deref(),
+            );
+            let preloads: Vec<String> = preload_chunks
+                .iter()
+                .map(|chunk| format!("pages/{}", chunk))
+                .collect();
+            let document = html::inject_modulepreload(&document, &preloads);
+            let document = html::inject_import_map(&document, &opts.import_map);
+            write_output(&pages_dir.join(format!("{}.html", page_id)), &document).await?;
+            html = Some(document);
         }
 
         diagnostics.push(Diagnostic {
             level: DiagnosticLevel::Info,
             message: format!("Written to {}", pages_dir.display()),
             context: None,
+            code: None,
         });
+        written = Some((out_dir, (js_file, css_file)));
     }
 
-    Ok(BundleResult {
+    let written = written.map(|(out_dir, (entry, css))| {
+        let page = ManifestPage {
+            page_id,
+            entry,
+            hash: utils::content_hash(&entry_js),
+            css,
+            expression_count: expressions.len(),
+        };
+        (out_dir, page)
+    });
+
+    let result = BundleResult {
+        page_path: plan.page_path,
         entry_js,
+        source_map,
         css,
+        bindings,
         expressions,
+        preload_chunks,
+        manual_chunks,
+        html,
+        assets,
+        stats,
+        compiled: (opts.include_compiled && recorded).then_some(compiled),
         diagnostics,
-    })
+    };
+    Ok((result, written))
+}
+
+/// Strip non-deterministic comments from a chunk (Rolldown emits `//#region`
+/// with absolute paths), or keep them relabelled with project-relative paths
+/// for debugging when `preserve` is set. Also normalizes line endings to `\n`.
+///
+/// Returns the code and, per original line, whether it was removed.
+fn strip_region_comments(code: &str, preserve: bool) -> (String, Vec<bool>) {
+    let region_lines = utils::rolldown_region_lines(code);
+    let code = code
+        .lines()
+        .zip(&region_lines)
+        .filter_map(|(line, &region)| match region {
+            false => Some(Cow::Borrowed(line)),
+            true if preserve => Some(utils::relativize_region_comment(line)),
+            true => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let dropped = if preserve {
+        vec![false; region_lines.len()]
+    } else {
+        region_lines
+    };
+    (code, dropped)
+}
+
+/// Where a written build's files go: the plan's `out_dir`, or
+/// [`DEFAULT_OUT_DIR`].
+fn plan_out_dir(plan: &BundlePlan) -> PathBuf {
+    plan.out_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_OUT_DIR))
+}
+
+/// Write one SSG page: content-hashed JS/CSS under `assets/` and an HTML
+/// document (from `template` if given) at the page's route, referencing them
+/// and modulepreloading the entry's statically imported `preload_chunks`.
+/// External specifiers resolve through `import_map`.
+///
+/// Returns the document, the path it was written to, and the asset files
+/// (relative to `out_dir`).
+#[allow(clippy::too_many_arguments)]
+async fn write_ssg_page(
+    out_dir: &Path,
+    page_path: &str,
+    page_id: &str,
+    template: Option<&str>,
+    page_html: &str,
+    entry_js: &str,
+    css: Option<&str>,
+    preload_chunks: &[String],
+    import_map: &HashMap<String, String>,
+) -> Result<(String, PathBuf, (String, Option<String>)), BundleError> {
+    let assets_dir = out_dir.join("assets");
+    tokio::fs::create_dir_all(&assets_dir).await?;
+
+    let js_file = utils::asset_filename(page_id, entry_js, AssetKind::Entry);
+    write_output(&out_dir.join(&js_file), entry_js).await?;
+
+    let css_file = match css {
+        Some(css) => {
+            let css_file = utils::asset_filename(page_id, css, AssetKind::Css);
+            write_output(&out_dir.join(&css_file), css).await?;
+            Some(css_file)
+        }
+        None => None,
+    };
+    let document = html::render_page_document(
+        template,
+        page_html,
+        &format!("/{}", js_file),
+        css_file.as_ref().map(|f| format!("/{}", f)).as_deref(),
+    );
+    // Chunks sit beside the entry, which imports them relatively
+    let preloads: Vec<String> = preload_chunks
+        .iter()
+        .map(|chunk| format!("assets/{}", chunk))
+        .collect();
+    let document = html::inject_modulepreload(&document, &preloads);
+    let document = html::inject_import_map(&document, import_map);
+
+    let html_path = out_dir.join(utils::route_to_output_path(&utils::page_route(page_path)));
+    if let Some(parent) = html_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    write_output(&html_path, &document).await?;
+
+    Ok((document, html_path, (js_file, css_file)))
+}
+
+/// Write a page's bindings sidecar into `dir` (the entry's directory under
+/// `out_dir`), returning its path relative to `out_dir`.
+async fn write_bindings(
+    out_dir: &Path,
+    dir: &str,
+    page_id: &str,
+    bindings_json: &str,
+) -> Result<String, BundleError> {
+    let file = format!(
+        "{}/{}",
+        dir,
+        utils::bindings_filename(page_id, bindings_json)
+    );
+    write_output(&out_dir.join(&file), bindings_json).await?;
+    Ok(file)
+}
+
+/// Write an output file, streaming it in fixed-size chunks when large.
+///
+/// `tokio::fs::write` copies the whole buffer before handing it to the
+/// blocking pool, doubling peak memory for multi-megabyte pages. Above
+/// [`STREAM_WRITE_THRESHOLD`] the bytes are written chunk by chunk instead.
+/// The resulting file is identical either way.
+async fn write_output(path: &Path, contents: &str) -> Result<(), BundleError> {
+    if contents.len() <= STREAM_WRITE_THRESHOLD {
+        tokio::fs::write(path, contents).await?;
+        return Ok(());
+    }
+
+    let mut file = tokio::fs::File::create(path).await?;
+    for chunk in contents.as_bytes().chunks(WRITE_CHUNK_SIZE) {
+        file.write_all(chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
 }
@@ -0,0 +1,643 @@
+//! Input payload accepted by the `zenith-bundler` CLI.
+//!
+//! The binary reads a [`BundlerInput`] (or an array of them) as JSON. The
+//! types and checks live here so library consumers can validate a payload
+//! before invoking the binary, without duplicating the serde structs.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::parse_route;
+use crate::BundleError;
+
+/// One route's input to the bundler CLI: the compiled IR plus routing flags.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BundlerInput {
+    pub route: String,
+    pub file: String,
+    pub ir: CompilerIr,
+    #[serde(default)]
+    pub router: bool,
+    /// Raw head fragments for this route (`<title>`, `<meta>`, `<link
+    /// rel="canonical">`, ...), inserted before `</head>` in order.
+    #[serde(default)]
+    pub head: Vec<String>,
+    /// Optional expected kind per expression index. Looser than strict
+    /// content matching — survives expression renames.
+    #[serde(default)]
+    pub expression_schema: Option<Vec<ExpressionKind>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpressionKind {
+    Value,
+    Event,
+    Component,
+}
+
+/// Compiler IR versions the CLI can bundle. The binary routes each one to its
+/// own codegen, and the runtime accepts the same list.
+pub const SUPPORTED_IR_VERSIONS: &[u32] = &[1];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerIr {
+    pub ir_version: u32,
+    pub html: String,
+    pub expressions: Vec<String>,
+    #[serde(default)]
+    pub hoisted: CompilerHoisted,
+    #[serde(default)]
+    pub components_scripts: BTreeMap<String, CompilerComponentScript>,
+    #[serde(default)]
+    pub component_instances: Vec<CompilerComponentInstance>,
+    #[serde(default)]
+    pub signals: Vec<CompilerSignal>,
+    #[serde(default)]
+    pub expression_bindings: Vec<CompilerExpressionBinding>,
+    #[serde(default)]
+    pub marker_bindings: Vec<MarkerBinding>,
+    #[serde(default)]
+    pub event_bindings: Vec<EventBinding>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerHoisted {
+    #[serde(default)]
+    pub imports: Vec<String>,
+    #[serde(default)]
+    pub declarations: Vec<String>,
+    #[serde(default)]
+    pub functions: Vec<String>,
+    #[serde(default)]
+    pub signals: Vec<String>,
+    #[serde(default)]
+    pub state: Vec<CompilerStateBinding>,
+    #[serde(default)]
+    pub code: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerStateBinding {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerComponentScript {
+    pub hoist_id: String,
+    pub factory: String,
+    #[serde(default)]
+    pub imports: Vec<String>,
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerComponentInstance {
+    pub instance: String,
+    pub hoist_id: String,
+    pub selector: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerSignal {
+    pub id: usize,
+    pub kind: String,
+    pub state_index: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompilerExpressionBinding {
+    pub marker_index: usize,
+    #[serde(default)]
+    pub signal_index: Option<usize>,
+    #[serde(default)]
+    pub state_index: Option<usize>,
+    #[serde(default)]
+    pub component_instance: Option<String>,
+    #[serde(default)]
+    pub component_binding: Option<String>,
+    #[serde(default)]
+    pub literal: Option<String>,
+    /// Name of the hoisted state key this binding reads, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerKind {
+    Text,
+    Attr,
+    Event,
+    /// Two-way `value`/`checked` binding between an input and a signal.
+    Model,
+    /// Toggles the node's `hidden` attribute on the expression's truthiness.
+    Show,
+    /// Renders one clone of the container's `<template>` per array item,
+    /// reusing rows by the items' `key` field.
+    List,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkerBinding {
+    pub index: usize,
+    pub kind: MarkerKind,
+    pub selector: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attr: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventBinding {
+    pub index: usize,
+    pub event: String,
+    pub selector: String,
+}
+
+/// Parse one JSON payload and run [`validate_payload`] on it.
+///
+/// Malformed JSON and failed checks are both reported as
+/// [`BundleError::ValidationError`].
+pub fn parse_and_validate(json: &str) -> Result<BundlerInput, BundleError> {
+    let payload: BundlerInput = serde_json::from_str(json)
+        .map_err(|e| BundleError::ValidationError(format!("invalid input JSON: {e}")))?;
+    validate_payload(&payload)?;
+    Ok(payload)
+}
+
+/// Check a parsed payload's internal consistency: route shape, IR version,
+/// and that every binding table agrees with the expression and state tables.
+pub fn validate_payload(payload: &BundlerInput) -> Result<(), BundleError> {
+    check_payload(payload).map_err(BundleError::ValidationError)
+}
+
+/// Error message for an IR version outside [`SUPPORTED_IR_VERSIONS`].
+pub fn unsupported_ir_version(version: u32) -> String {
+    let supported: Vec<String> = SUPPORTED_IR_VERSIONS.iter().map(u32::to_string).collect();
+    format!(
+        "unsupported input.ir.ir_version {version} (supported: {})",
+        supported.join(", ")
+    )
+}
+
+fn check_payload(payload: &BundlerInput) -> Result<(), String> {
+    if !SUPPORTED_IR_VERSIONS.contains(&payload.ir.ir_version) {
+        return Err(unsupported_ir_version(payload.ir.ir_version));
+    }
+    if payload.route.trim().is_empty() {
+        return Err("input.route must be a non-empty string".into());
+    }
+    if !payload.route.starts_with('/') {
+        return Err("input.route must start with '/'".into());
+    }
+    parse_route(&payload.route).map_err(|e| format!("input.route is invalid ({e})"))?;
+    if payload.file.trim().is_empty() {
+        return Err("input.file must be a non-empty string".into());
+    }
+    if payload.ir.html.trim().is_empty() {
+        return Err("input.ir.html must be a non-empty string".into());
+    }
+    for (position, fragment) in payload.head.iter().enumerate() {
+        let fragment = fragment.trim();
+        if !fragment.starts_with('<') {
+            return Err(format!("input.head[{position}] must be an HTML tag"));
+        }
+        if fragment.to_ascii_lowercase().contains("</head") {
+            return Err(format!("input.head[{position}] must not close the head"));
+        }
+    }
+    if !payload.ir.expression_bindings.is_empty()
+        && payload.ir.expression_bindings.len() != payload.ir.expressions.len()
+    {
+        return Err(format!(
+            "input.ir.expression_bindings length ({}) must match input.ir.expressions length ({})",
+            payload.ir.expression_bindings.len(),
+            payload.ir.expressions.len()
+        ));
+    }
+    if !payload.ir.marker_bindings.is_empty()
+        && payload.ir.marker_bindings.len() != payload.ir.expressions.len()
+    {
+        return Err(format!(
+            "input.ir.marker_bindings length ({}) must match input.ir.expressions length ({})",
+            payload.ir.marker_bindings.len(),
+            payload.ir.expressions.len()
+        ));
+    }
+    for (position, signal) in payload.ir.signals.iter().enumerate() {
+        // `signal_index` is a position in this table; ids must agree with it
+        // so bindings resolve the same signal either way.
+        if signal.id != position {
+            return Err(format!(
+                "input.ir.signals[{position}].id must equal its position, got {}",
+                signal.id
+            ));
+        }
+        if signal.kind != "signal" {
+            return Err(format!(
+                "input.ir.signals[].kind must be 'signal', got '{}'",
+                signal.kind
+            ));
+        }
+        if signal.state_index >= payload.ir.hoisted.state.len() {
+            return Err(format!(
+                "input.ir.signals[{}].state_index out of bounds: {}",
+                signal.id, signal.state_index
+            ));
+        }
+    }
+    for (position, binding) in payload.ir.expression_bindings.iter().enumerate() {
+        if binding.marker_index >= payload.ir.expressions.len() {
+            return Err(format!(
+                "input.ir.expression_bindings[{position}].marker_index out of bounds: {}",
+                binding.marker_index
+            ));
+        }
+        if let Some(state_index) = binding.state_index {
+            if state_index >= payload.ir.hoisted.state.len() {
+                return Err(format!(
+                    "input.ir.expression_bindings[{position}].state_index out of bounds: {}",
+                    state_index
+                ));
+            }
+        }
+        if let Some(ref key) = binding.state_key {
+            let key_index = payload
+                .ir
+                .hoisted
+                .state
+                .iter()
+                .position(|state| state.key == *key)
+                .ok_or_else(|| {
+                    format!(
+                        "input.ir.expression_bindings[{position}].state_key references undefined state key '{key}'"
+                    )
+                })?;
+            if binding.state_index.is_some_and(|index| index != key_index) {
+                return Err(format!(
+                    "input.ir.expression_bindings[{position}].state_key '{key}' is at index {key_index}, but state_index is {}",
+                    binding.state_index.unwrap_or_default()
+                ));
+            }
+        }
+        if let Some(signal_index) = binding.signal_index {
+            if signal_index >= payload.ir.signals.len() {
+                return Err(format!(
+                    "input.ir.expression_bindings[{position}].signal_index out of bounds: {}",
+                    signal_index
+                ));
+            }
+        }
+    }
+    for (hoist_id, script) in &payload.ir.components_scripts {
+        if hoist_id.trim().is_empty() {
+            return Err("input.ir.components_scripts contains an empty hoist_id key".into());
+        }
+        if script.code.trim().is_empty() {
+            return Err(format!(
+                "input.ir.components_scripts['{}'].code must be non-empty",
+                hoist_id
+            ));
+        }
+        if script.factory.trim().is_empty() {
+            return Err(format!(
+                "input.ir.components_scripts['{}'].factory must be non-empty",
+                hoist_id
+            ));
+        }
+        if script.hoist_id != *hoist_id {
+            return Err(format!(
+                "input.ir.components_scripts key '{}' mismatches hoist_id '{}'",
+                hoist_id, script.hoist_id
+            ));
+        }
+    }
+    for instance in &payload.ir.component_instances {
+        if instance.instance.trim().is_empty() {
+            return Err("input.ir.component_instances[].instance must be non-empty".into());
+        }
+        if instance.selector.trim().is_empty() {
+            return Err("input.ir.component_instances[].selector must be non-empty".into());
+        }
+        if !payload
+            .ir
+            .components_scripts
+            .contains_key(&instance.hoist_id)
+        {
+            return Err(format!(
+                "input.ir.component_instances references unknown hoist_id '{}'",
+                instance.hoist_id
+            ));
+        }
+    }
+
+    if !payload.ir.marker_bindings.is_empty() {
+        let mut seen = BTreeMap::new();
+        for marker in &payload.ir.marker_bindings {
+            if marker.index >= payload.ir.expressions.len() {
+                return Err(format!(
+                    "input.ir.marker_bindings index out of bounds: {}",
+                    marker.index
+                ));
+            }
+            if seen.insert(marker.index, true).is_some() {
+                return Err(format!(
+                    "input.ir.marker_bindings contains duplicate index {}",
+                    marker.index
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Derive the marker and event tables of a page whose compiler reported
+/// none, from the `data-zx-*` attributes in its `html`: one marker per
+/// expression. Fails when an expression has no marker, or two.
+pub fn derive_binding_tables(
+    html: &str,
+    expression_count: usize,
+) -> Result<(Vec<MarkerBinding>, Vec<EventBinding>), String> {
+    if expression_count == 0 {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let mut marker_slots: Vec<Option<MarkerBinding>> = vec![None; expression_count];
+    let mut event_bindings = Vec::new();
+
+    let attr_re = Regex::new(r#"data-zx-([A-Za-z0-9_-]+)=(?:"([^"]+)"|'([^']+)'|([^\s>"']+))"#)
+        .map_err(|e| format!("failed to compile binding regex: {e}"))?;
+
+    for captures in attr_re.captures_iter(html) {
+        let attr_name = captures
+            .get(1)
+            .map(|m| m.as_str())
+            .ok_or_else(|| "failed to parse data-zx attribute name".to_string())?;
+        let raw_value = captures
+            .get(2)
+            .or_else(|| captures.get(3))
+            .or_else(|| captures.get(4))
+            .map(|m| m.as_str())
+            .unwrap_or("");
+
+        if attr_name == "e" {
+            for part in raw_value.split_whitespace() {
+                let index = parse_expression_index(part, expression_count, "data-zx-e")?;
+                insert_marker(
+                    &mut marker_slots,
+                    MarkerBinding {
+                        index,
+                        kind: MarkerKind::Text,
+                        selector: format!(r#"[data-zx-e~="{index}"]"#),
+                        attr: None,
+                    },
+                )?;
+            }
+            continue;
+        }
+
+        // Component hosts and list row fields are not page expressions
+        if attr_name == "c" || attr_name == "item" || attr_name.starts_with("item-on-") {
+            continue;
+        }
+
+        if attr_name == "model" {
+            let index = parse_expression_index(raw_value, expression_count, "data-zx-model")?;
+            insert_marker(
+                &mut marker_slots,
+                MarkerBinding {
+                    index,
+                    kind: MarkerKind::Model,
+                    selector: format!(r#"[data-zx-model="{index}"]"#),
+                    attr: None,
+                },
+            )?;
+            continue;
+        }
+
+        if attr_name == "show" {
+            let index = parse_expression_index(raw_value, expression_count, "data-zx-show")?;
+            insert_marker(
+                &mut marker_slots,
+                MarkerBinding {
+                    index,
+                    kind: MarkerKind::Show,
+                    selector: format!(r#"[data-zx-show="{index}"]"#),
+                    attr: None,
+                },
+            )?;
+            continue;
+        }
+
+        if attr_name == "list" {
+            let index = parse_expression_index(raw_value, expression_count, "data-zx-list")?;
+            insert_marker(
+                &mut marker_slots,
+                MarkerBinding {
+                    index,
+                    kind: MarkerKind::List,
+                    selector: format!(r#"[data-zx-list="{index}"]"#),
+                    attr: None,
+                },
+            )?;
+            continue;
+        }
+
+        if let Some(event_name) = attr_name.strip_prefix("on-") {
+            let index = parse_expression_index(raw_value, expression_count, "data-zx-on-*")?;
+            let selector = format!(r#"[data-zx-on-{event_name}="{index}"]"#);
+            insert_marker(
+                &mut marker_slots,
+                MarkerBinding {
+                    index,
+                    kind: MarkerKind::Event,
+                    selector: selector.clone(),
+                    attr: None,
+                },
+            )?;
+            event_bindings.push(EventBinding {
+                index,
+                event: event_name.to_string(),
+                selector,
+            });
+            continue;
+        }
+
+        let index = parse_expression_index(raw_value, expression_count, "data-zx-*")?;
+        insert_marker(
+            &mut marker_slots,
+            MarkerBinding {
+                index,
+                kind: MarkerKind::Attr,
+                selector: format!(r#"[data-zx-{attr_name}="{index}"]"#),
+                attr: Some(attr_name.to_string()),
+            },
+        )?;
+    }
+
+    let mut markers = Vec::with_capacity(expression_count);
+    for (index, marker) in marker_slots.into_iter().enumerate() {
+        if let Some(binding) = marker {
+            markers.push(binding);
+            continue;
+        }
+        return Err(format!(
+            "marker/expression mismatch: missing marker for expression index {index}"
+        ));
+    }
+
+    Ok((markers, event_bindings))
+}
+
+fn parse_expression_index(
+    raw: &str,
+    expression_count: usize,
+    context: &str,
+) -> Result<usize, String> {
+    let parsed = raw
+        .parse::<usize>()
+        .map_err(|_| format!("invalid expression index '{raw}' in {context}"))?;
+
+    if parsed >= expression_count {
+        return Err(format!(
+            "out-of-bounds expression index {parsed} in {context}; expression count is {expression_count}"
+        ));
+    }
+
+    Ok(parsed)
+}
+
+fn insert_marker(slots: &mut [Option<MarkerBinding>], marker: MarkerBinding) -> Result<(), String> {
+    let index = marker.index;
+
+    if index >= slots.len() {
+        return Err(format!(
+            "marker index {} out of bounds; marker slots length is {}",
+            index,
+            slots.len()
+        ));
+    }
+
+    if let Some(ref existing) = slots[index] {
+        return Err(format!(
+            "duplicate marker index {index} detected while deriving binding tables: \
+             bound by {} (selector {}) and {} (selector {})",
+            marker_attribute(existing),
+            existing.selector,
+            marker_attribute(&marker),
+            marker.selector
+        ));
+    }
+
+    slots[index] = Some(marker);
+    Ok(())
+}
+
+/// The `data-zx-*` attribute a derived marker was read from.
+pub fn marker_attribute(marker: &MarkerBinding) -> String {
+    match marker.kind {
+        MarkerKind::Text => "data-zx-e".to_string(),
+        MarkerKind::Attr => format!("data-zx-{}", marker.attr.as_deref().unwrap_or_default()),
+        MarkerKind::Model => "data-zx-model".to_string(),
+        MarkerKind::Show => "data-zx-show".to_string(),
+        MarkerKind::List => "data-zx-list".to_string(),
+        // Event selectors are `[data-zx-on-<event>="<index>"]`
+        MarkerKind::Event => marker
+            .selector
+            .trim_start_matches('[')
+            .split('=')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload_from_json(value: serde_json::Value) -> BundlerInput {
+        serde_json::from_value(value).expect("valid payload JSON")
+    }
+
+    #[test]
+    fn validate_payload_rejects_undefined_state_key() {
+        let payload = payload_from_json(serde_json::json!({
+            "route": "/",
+            "file": "index.zen",
+            "ir": {
+                "ir_version": 1,
+                "html": "<p data-zx-e=\"0\"></p>",
+                "expressions": ["count"],
+                "hoisted": { "state": [{ "key": "total", "value": "0" }] },
+                "expression_bindings": [{ "marker_index": 0, "state_key": "count" }]
+            }
+        }));
+
+        let err = validate_payload(&payload).unwrap_err();
+        assert!(
+            err.to_string().contains("undefined state key 'count'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_payload_rejects_signal_id_position_mismatch() {
+        let payload = |signal_id: usize| {
+            payload_from_json(serde_json::json!({
+                "route": "/",
+                "file": "index.zen",
+                "ir": {
+                    "ir_version": 1,
+                    "html": "<p data-zx-e=\"0\"></p>",
+                    "expressions": ["count"],
+                    "hoisted": { "state": [{ "key": "count", "value": "signal(0)" }] },
+                    "signals": [{ "id": signal_id, "kind": "signal", "state_index": 0 }],
+                    "expression_bindings": [{ "marker_index": 0, "signal_index": 0 }]
+                }
+            }))
+        };
+
+        assert!(validate_payload(&payload(0)).is_ok());
+        let err = validate_payload(&payload(7)).unwrap_err();
+        assert!(err.to_string().contains("signals[0].id"), "{}", err);
+    }
+
+    #[test]
+    fn parse_and_validate_reports_json_and_payload_errors() {
+        let payload = parse_and_validate(
+            r#"{"route":"/blog/:slug","file":"blog.zen","ir":{"ir_version":1,"html":"<p></p>","expressions":[]}}"#,
+        )
+        .unwrap();
+        assert_eq!(payload.route, "/blog/:slug");
+
+        let err = parse_and_validate(r#"{"route":"/","file":"index.zen"}"#).unwrap_err();
+        assert!(matches!(err, BundleError::ValidationError(_)), "{:?}", err);
+        assert!(err.to_string().contains("invalid input JSON"), "{}", err);
+
+        let err = parse_and_validate(
+            r#"{"route":"blog","file":"blog.zen","ir":{"ir_version":1,"html":"<p></p>","expressions":[]}}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, BundleError::ValidationError(_)), "{:?}", err);
+        assert!(
+            err.to_string().contains("input.route must start with '/'"),
+            "{}",
+            err
+        );
+    }
+}
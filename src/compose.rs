@@ -0,0 +1,708 @@
+//! Layout composition: one [`CompilerOutput`] spliced into a slot of another.
+//!
+//! The composed output is what the compiler would have produced for the
+//! combined markup: the outer (layout) tables come first, and every index the
+//! inner (page) output carries — expression indices in its index-carrying
+//! `data-zx-*` attributes and binding selectors, marker and event binding
+//! indices, signal ids and state indices — is shifted past the outer's.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use regex::{Captures, Regex};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{cli_input, BundleError, CompilerOutput};
+
+/// Compose a page into a layout.
+pub trait ComposeCompilerOutput: Sized {
+    /// Splice `inner` into the element of `outer` matched by
+    /// `slot_selector`, replacing that element's content.
+    ///
+    /// The selector is a single simple selector: a tag name, optionally
+    /// followed by `#id`, `[attr]` or `[attr="value"]` (e.g. `slot`,
+    /// `main#content`, `[data-slot]`). It must match exactly one element,
+    /// and the content it replaces must not carry bindings of its own.
+    ///
+    /// `inner`'s expressions follow `outer`'s, so its indices are offset
+    /// by `outer.expressions.len()`; its signals and state likewise follow
+    /// `outer`'s. Hoisted `code` and `state` are concatenated (state keys
+    /// and the names `code` declares at top level must be distinct),
+    /// component instances too (instance ids must be distinct), and
+    /// component scripts merged.
+    ///
+    /// When only one side lists its marker or expression bindings, the
+    /// other side's implicit entries are spelled out first (markers derived
+    /// from its HTML, expressions bound as literals), so the composed table
+    /// still covers every expression.
+    fn compose(outer: &Self, inner: &Self, slot_selector: &str) -> Result<Self, BundleError>;
+}
+
+impl ComposeCompilerOutput for CompilerOutput {
+    fn compose(outer: &Self, inner: &Self, slot_selector: &str) -> Result<Self, BundleError> {
+        if outer.ir_version != inner.ir_version {
+            return Err(BundleError::ValidationError(format!(
+                "Cannot compose IR version {} into IR version {}",
+                inner.ir_version, outer.ir_version
+            )));
+        }
+
+        let outer_keys: HashSet<&str> = outer
+            .hoisted
+            .state
+            .iter()
+            .map(|binding| binding.key.as_str())
+            .collect();
+        if let Some(binding) = inner
+            .hoisted
+            .state
+            .iter()
+            .find(|binding| outer_keys.contains(binding.key.as_str()))
+        {
+            return Err(BundleError::ValidationError(format!(
+                "Cannot compose: state key '{}' is declared by both outputs",
+                binding.key
+            )));
+        }
+        let outer_instances: HashSet<&str> = outer
+            .component_instances
+            .iter()
+            .map(|instance| instance.instance.as_str())
+            .collect();
+        if let Some(instance) = inner
+            .component_instances
+            .iter()
+            .find(|instance| outer_instances.contains(instance.instance.as_str()))
+        {
+            return Err(BundleError::ValidationError(format!(
+                "Cannot compose: component instance '{}' exists in both outputs",
+                instance.instance
+            )));
+        }
+        let outer_names: HashSet<String> = outer
+            .hoisted
+            .code
+            .iter()
+            .flat_map(|code| top_level_declarations(code))
+            .collect();
+        if let Some(name) = inner
+            .hoisted
+            .code
+            .iter()
+            .flat_map(|code| top_level_declarations(code))
+            .find(|name| outer_names.contains(name))
+        {
+            return Err(BundleError::ValidationError(format!(
+                "Cannot compose: '{}' is declared by the hoisted code of both outputs",
+                name
+            )));
+        }
+
+        let fill_markers = outer.marker_bindings.is_empty() != inner.marker_bindings.is_empty();
+        let fill_bindings =
+            outer.expression_bindings.is_empty() != inner.expression_bindings.is_empty();
+        let outer = &*with_implicit_tables(outer, fill_markers, fill_bindings)?;
+        let inner = &*with_implicit_tables(inner, fill_markers, fill_bindings)?;
+
+        let expression_offset = outer.expressions.len();
+        let signal_offset = outer.signals.len();
+        let state_offset = outer.hoisted.state.len();
+
+        let bound_attributes = bound_attributes(inner);
+        let inner_html = offset_placeholders(&inner.html, expression_offset, &bound_attributes);
+        let html = splice_into_slot(&outer.html, slot_selector, &inner_html)?;
+
+        let mut composed = outer.clone();
+        composed.html = html;
+        composed
+            .expressions
+            .extend(inner.expressions.iter().cloned());
+
+        composed
+            .marker_bindings
+            .extend(inner.marker_bindings.iter().map(|marker| {
+                let mut marker = marker.clone();
+                marker.index += expression_offset;
+                marker.selector =
+                    offset_placeholders(&marker.selector, expression_offset, &bound_attributes);
+                marker
+            }));
+        composed
+            .event_bindings
+            .extend(inner.event_bindings.iter().map(|event| {
+                let mut event = event.clone();
+                event.index += expression_offset;
+                event.selector =
+                    offset_placeholders(&event.selector, expression_offset, &bound_attributes);
+                event
+            }));
+        composed
+            .expression_bindings
+            .extend(inner.expression_bindings.iter().map(|binding| {
+                let mut binding = binding.clone();
+                binding.marker_index += expression_offset;
+                binding.signal_index = binding.signal_index.map(|index| index + signal_offset);
+                binding.state_index = binding.state_index.map(|index| index + state_offset);
+                binding
+            }));
+        composed.signals.extend(inner.signals.iter().map(|signal| {
+            let mut signal = signal.clone();
+            signal.id += signal_offset;
+            signal.state_index += state_offset;
+            signal
+        }));
+
+        composed
+            .hoisted
+            .code
+            .extend(inner.hoisted.code.iter().cloned());
+        composed
+            .hoisted
+            .state
+            .extend(inner.hoisted.state.iter().cloned());
+        composed
+            .component_instances
+            .extend(inner.component_instances.iter().cloned());
+        for (hoist_id, script) in &inner.components_scripts {
+            composed
+                .components_scripts
+                .entry(hoist_id.clone())
+                .or_insert_with(|| script.clone());
+        }
+
+        Ok(composed)
+    }
+}
+
+/// `output` with its empty marker table (and event table) derived from its
+/// HTML when `markers` is set, and its empty expression binding table
+/// filled with one literal binding per expression when `bindings` is set:
+/// the entries the runtime assumes for an omitted table.
+fn with_implicit_tables(
+    output: &CompilerOutput,
+    markers: bool,
+    bindings: bool,
+) -> Result<Cow<'_, CompilerOutput>, BundleError> {
+    let markers = markers && output.marker_bindings.is_empty();
+    let bindings = bindings && output.expression_bindings.is_empty();
+    if output.expressions.is_empty() || !(markers || bindings) {
+        return Ok(Cow::Borrowed(output));
+    }
+
+    let mut output = output.clone();
+    if markers {
+        let (markers, events) =
+            cli_input::derive_binding_tables(&output.html, output.expressions.len())
+                .map_err(|e| BundleError::ValidationError(format!("Cannot compose: {}", e)))?;
+        output.marker_bindings = convert_table("marker", &markers)?;
+        if output.event_bindings.is_empty() {
+            output.event_bindings = convert_table("event", &events)?;
+        }
+    }
+    if bindings {
+        let literals: Vec<serde_json::Value> = output
+            .expressions
+            .iter()
+            .enumerate()
+            .map(|(index, value)| serde_json::json!({ "marker_index": index, "literal": value }))
+            .collect();
+        output.expression_bindings = convert_table("expression", &literals)?;
+    }
+    Ok(Cow::Owned(output))
+}
+
+/// The `data-zx-*` attribute names (without the prefix) that the marker
+/// selectors of `output` bind, e.g. `href` for `[data-zx-href="1"]`.
+fn bound_attributes(output: &CompilerOutput) -> Vec<String> {
+    let re = Regex::new(r"^\[data-zx-([A-Za-z0-9_-]+)").unwrap();
+    let mut names: Vec<String> = output
+        .marker_bindings
+        .iter()
+        .filter_map(|marker| re.captures(&marker.selector))
+        .map(|cap| cap[1].to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Re-read a binding table through its JSON form as the compiler's type.
+fn convert_table<T: Serialize, U: DeserializeOwned>(
+    table: &str,
+    value: &T,
+) -> Result<U, BundleError> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .map_err(|e| BundleError::BuildError(format!("Failed to convert {} table: {}", table, e)))
+}
+
+/// Names declared at the top level of a hoisted code block (`const`, `let`,
+/// `var`, `function` and `class` statements starting a line). Destructuring
+/// patterns are not inspected.
+fn top_level_declarations(code: &str) -> Vec<String> {
+    let re = Regex::new(
+        r"(?m)^(?:export\s+)?(?:const|let|var|class|(?:async\s+)?function\s*\*?)\s+([A-Za-z_$][\w$]*)",
+    )
+    .unwrap();
+    re.captures_iter(code)
+        .map(|cap| cap[1].to_string())
+        .collect()
+}
+
+/// Attributes the compiler writes expression indices into: text markers,
+/// event handlers, and the class, show, list and model directives.
+const INDEX_ATTRIBUTES: &str = "e|class|show|list|model|on-[A-Za-z0-9_-]+";
+
+/// Shift every numeric index in the index-carrying `data-zx-*` attribute
+/// values of `html` (or of a selector such as `[data-zx-on-click="0"]`) by
+/// `offset`. Those are the compiler's own marker, event and directive
+/// attributes plus the attribute bindings named
+/// in `bound_attributes` (e.g. `href` for `data-zx-href`); any other
+/// `data-zx-*` attribute, such as an author-written `data-zx-key="3"`, and
+/// non-numeric parts of a value are kept as they are.
+pub fn offset_placeholders(html: &str, offset: usize, bound_attributes: &[String]) -> String {
+    if offset == 0 {
+        return html.to_string();
+    }
+    let names = std::iter::once(INDEX_ATTRIBUTES.to_string())
+        .chain(bound_attributes.iter().map(|name| regex::escape(name)))
+        .collect::<Vec<_>>()
+        .join("|");
+    let re = Regex::new(&format!(
+        r#"(data-zx-(?:{names})[~|^$*]?=)(?:"([^"]*)"|'([^']*)'|([^\s>"'\]]+))"#
+    ))
+    .unwrap();
+    re.replace_all(html, |cap: &Captures| {
+        let (quote, value) = match (cap.get(2), cap.get(3), cap.get(4)) {
+            (Some(value), _, _) => ("\"", value.as_str()),
+            (_, Some(value), _) => ("'", value.as_str()),
+            (_, _, value) => ("", value.map_or("", |m| m.as_str())),
+        };
+        let shifted = value
+            .split(' ')
+            .map(|part| match part.parse::<usize>() {
+                Ok(index) => (index + offset).to_string(),
+                Err(_) => part.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{}{quote}{shifted}{quote}", &cap[1])
+    })
+    .into_owned()
+}
+
+/// A parsed slot selector: `tag`, `#id`, `[attr]` or `[attr="value"]`, the
+/// latter three optionally after a tag name.
+struct SlotSelector {
+    tag: Option<String>,
+    attr: Option<(String, Option<String>)>,
+}
+
+impl SlotSelector {
+    fn parse(selector: &str) -> Result<Self, BundleError> {
+        let re = Regex::new(
+            r#"^([A-Za-z][A-Za-z0-9-]*)?(?:#([A-Za-z0-9_-]+)|\[([A-Za-z_:][A-Za-z0-9_:.-]*)(?:=(?:"([^"]*)"|'([^']*)'|([A-Za-z0-9_-]+)))?\])?$"#,
+        )
+        .unwrap();
+        let cap = re
+            .captures(selector.trim())
+            .filter(|cap| cap.get(1).is_some() || cap.get(2).is_some() || cap.get(3).is_some())
+            .ok_or_else(|| {
+                BundleError::ValidationError(format!(
+                    "Unsupported slot selector '{}' (expected `tag`, `#id`, `[attr]` or `[attr=\"value\"]`)",
+                    selector
+                ))
+            })?;
+        let attr = match (cap.get(2), cap.get(3)) {
+            (Some(id), _) => Some(("id".to_string(), Some(id.as_str().to_string()))),
+            (_, Some(name)) => Some((
+                name.as_str().to_ascii_lowercase(),
+                cap.get(4)
+                    .or(cap.get(5))
+                    .or(cap.get(6))
+                    .map(|value| value.as_str().to_string()),
+            )),
+            _ => None,
+        };
+        Ok(Self {
+            tag: cap.get(1).map(|tag| tag.as_str().to_ascii_lowercase()),
+            attr,
+        })
+    }
+
+    /// Whether an opening tag named `tag` with attribute list `attrs` matches.
+    fn matches(&self, tag: &str, attrs: &str) -> bool {
+        if self.tag.as_deref().is_some_and(|t| t != tag) {
+            return false;
+        }
+        let Some((ref name, ref value)) = self.attr else {
+            return true;
+        };
+        let attr_re =
+            Regex::new(r#"([^\s=/>]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+)))?"#).unwrap();
+        attr_re.captures_iter(attrs).any(|cap| {
+            cap[1].eq_ignore_ascii_case(name)
+                && value.as_deref().is_none_or(|expected| {
+                    cap.get(2).or(cap.get(3)).or(cap.get(4)).map(|m| m.as_str()) == Some(expected)
+                })
+        })
+    }
+}
+
+/// Replace the content of the one element of `html` matching `selector`
+/// with `content`.
+fn splice_into_slot(html: &str, selector: &str, content: &str) -> Result<String, BundleError> {
+    let slot = SlotSelector::parse(selector)?;
+    let tag_re = Regex::new(r"<([A-Za-z][A-Za-z0-9-]*)([^<>]*)>").unwrap();
+
+    let mut matches = tag_re
+        .captures_iter(html)
+        .filter(|cap| slot.matches(&cap[1].to_ascii_lowercase(), &cap[2]));
+    let open = matches.next().ok_or_else(|| {
+        BundleError::ValidationError(format!("No element matches slot selector '{}'", selector))
+    })?;
+    if matches.next().is_some() {
+        return Err(BundleError::ValidationError(format!(
+            "Slot selector '{}' matches more than one element",
+            selector
+        )));
+    }
+    let tag = open[1].to_ascii_lowercase();
+    if open[2].trim_end().ends_with('/') {
+        return Err(BundleError::ValidationError(format!(
+            "Slot element <{}> matched by '{}' is self-closing",
+            tag, selector
+        )));
+    }
+
+    let content_start = open.get(0).unwrap().end();
+    let content_end = matching_close(html, content_start, &tag).ok_or_else(|| {
+        BundleError::ValidationError(format!(
+            "Slot element <{}> matched by '{}' is never closed",
+            tag, selector
+        ))
+    })?;
+    if html[content_start..content_end].contains("data-zx-") {
+        return Err(BundleError::ValidationError(format!(
+            "Slot element <{}> matched by '{}' has bound content, which composition would drop",
+            tag, selector
+        )));
+    }
+
+    Ok(format!(
+        "{}{}{}",
+        &html[..content_start],
+        content,
+        &html[content_end..]
+    ))
+}
+
+/// Offset of the `</tag>` closing the element whose content starts at
+/// `from`, skipping nested elements of the same name.
+fn matching_close(html: &str, from: usize, tag: &str) -> Option<usize> {
+    let re = Regex::new(&format!(r"(?i)<(/?){}(?:[\s/>])", regex::escape(tag))).unwrap();
+    let mut depth = 0usize;
+    for cap in re.captures_iter(&html[from..]) {
+        let whole = cap.get(0).unwrap();
+        if cap[1].is_empty() {
+            // A self-closing twin does not nest
+            let rest = &html[from + whole.start()..];
+            let tag_end = rest.find('>').map_or(rest.len(), |end| end);
+            if !rest[..tag_end].ends_with('/') {
+                depth += 1;
+            }
+        } else if depth == 0 {
+            return Some(from + whole.start());
+        } else {
+            depth -= 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCompilerOutput;
+
+    fn layout() -> CompilerOutput {
+        let mut layout = CompilerOutput::mock(
+            r#"<header data-zx-e="0"></header><main id="content"><p>Loading</p></main><footer data-zx-e="1"></footer>"#,
+            &["siteTitle", "year"],
+        );
+        layout.hoisted.code = vec!["const siteTitle = signal('Zenith');".into()];
+        layout
+    }
+
+    fn page() -> CompilerOutput {
+        CompilerOutput::mock(
+            r#"<h1 data-zx-e="0"></h1><button data-zx-on-click="1">+</button><p data-zx-e='0 2'></p>"#,
+            &["title", "increment", "count"],
+        )
+    }
+
+    #[test]
+    fn offsets_inner_indices_past_the_outer_table() {
+        let composed = CompilerOutput::compose(&layout(), &page(), "main#content").unwrap();
+
+        assert_eq!(
+            composed.expressions,
+            ["siteTitle", "year", "title", "increment", "count"]
+        );
+        assert_eq!(
+            composed.html,
+            r#"<header data-zx-e="0"></header><main id="content"><h1 data-zx-e="2"></h1><button data-zx-on-click="3">+</button><p data-zx-e='2 4'></p></main><footer data-zx-e="1"></footer>"#
+        );
+        assert!(crate::utils::validate_placeholders(&composed.html, 5).is_ok());
+        assert_eq!(composed.hoisted.code, layout().hoisted.code);
+    }
+
+    #[test]
+    fn keeps_attributes_that_carry_no_expression_index() {
+        fn json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+            serde_json::from_value(value).unwrap()
+        }
+        let mut page = CompilerOutput::mock(
+            r#"<ul data-zx-key="3" data-zx-list="0"><a data-zx-href="1" data-zx-item="id"></a></ul>"#,
+            &["items", "link"],
+        );
+        page.marker_bindings = json(serde_json::json!([
+            { "index": 0, "kind": "list", "selector": "[data-zx-list=\"0\"]" },
+            { "index": 1, "kind": "attr", "selector": "[data-zx-href=\"1\"]" }
+        ]));
+
+        let composed = CompilerOutput::compose(&layout(), &page, "main").unwrap();
+        assert_eq!(
+            composed.html,
+            r#"<header data-zx-e="0"></header><main id="content"><ul data-zx-key="3" data-zx-list="2"><a data-zx-href="3" data-zx-item="id"></a></ul></main><footer data-zx-e="1"></footer>"#
+        );
+        let selectors: Vec<&str> = composed
+            .marker_bindings
+            .iter()
+            .map(|m| m.selector.as_str())
+            .collect();
+        assert_eq!(
+            selectors[2..],
+            ["[data-zx-list=\"2\"]", "[data-zx-href=\"3\"]"]
+        );
+    }
+
+    #[test]
+    fn offsets_bindings_signals_and_state() {
+        use zenith_compiler::compiler::StateBinding;
+
+        let mut outer = layout();
+        outer.hoisted.state = vec![StateBinding {
+            key: "siteTitle".into(),
+            value: "siteTitle".into(),
+        }];
+        let mut inner = page();
+        inner.hoisted.state = vec![
+            StateBinding {
+                key: "title".into(),
+                value: "'Home'".into(),
+            },
+            StateBinding {
+                key: "count".into(),
+                value: "count".into(),
+            },
+        ];
+        fn json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+            serde_json::from_value(value).unwrap()
+        }
+        outer.signals = json(serde_json::json!([{ "id": 0, "kind": "signal", "state_index": 0 }]));
+        outer.expression_bindings =
+            json(serde_json::json!([{ "marker_index": 0, "signal_index": 0, "state_index": 0 }]));
+        inner.signals = json(serde_json::json!([{ "id": 0, "kind": "signal", "state_index": 1 }]));
+        inner.expression_bindings = json(serde_json::json!([
+            { "marker_index": 0, "state_index": 0 },
+            { "marker_index": 2, "signal_index": 0, "state_index": 1 }
+        ]));
+        inner.event_bindings = json(serde_json::json!([
+            { "index": 1, "event": "click", "selector": "[data-zx-on-click=\"1\"]" }
+        ]));
+        inner.marker_bindings = json(serde_json::json!([
+            { "index": 0, "kind": "text", "selector": "[data-zx-e~=\"0\"]" },
+            { "index": 2, "kind": "text", "selector": "[data-zx-e~=\"2\"]" }
+        ]));
+
+        let composed = CompilerOutput::compose(&outer, &inner, "main").unwrap();
+
+        let state: Vec<&str> = composed
+            .hoisted
+            .state
+            .iter()
+            .map(|binding| binding.key.as_str())
+            .collect();
+        assert_eq!(state, ["siteTitle", "title", "count"]);
+
+        let signals: Vec<(usize, usize)> = composed
+            .signals
+            .iter()
+            .map(|signal| (signal.id, signal.state_index))
+            .collect();
+        assert_eq!(signals, [(0, 0), (1, 2)]);
+
+        let bindings: Vec<(usize, Option<usize>, Option<usize>)> = composed
+            .expression_bindings
+            .iter()
+            .map(|b| (b.marker_index, b.signal_index, b.state_index))
+            .collect();
+        assert_eq!(
+            bindings,
+            [
+                (0, Some(0), Some(0)),
+                (2, None, Some(1)),
+                (4, Some(1), Some(2))
+            ]
+        );
+
+        assert_eq!(composed.event_bindings[0].index, 3);
+        assert_eq!(
+            composed.event_bindings[0].selector,
+            "[data-zx-on-click=\"3\"]"
+        );
+        let markers: Vec<(usize, &str)> = composed
+            .marker_bindings
+            .iter()
+            .map(|m| (m.index, m.selector.as_str()))
+            .collect();
+        // The layout's implicit markers are spelled out ahead of the page's
+        assert_eq!(
+            markers,
+            [
+                (0, "[data-zx-e~=\"0\"]"),
+                (1, "[data-zx-e~=\"1\"]"),
+                (2, "[data-zx-e~=\"2\"]"),
+                (4, "[data-zx-e~=\"4\"]")
+            ]
+        );
+    }
+
+    #[test]
+    fn fills_in_the_implicit_side_of_mixed_tables() {
+        fn json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+            serde_json::from_value(value).unwrap()
+        }
+        let mut page = CompilerOutput::mock(
+            r#"<h1 data-zx-e="0"></h1><button data-zx-on-click="1">+</button>"#,
+            &["title", "increment"],
+        );
+        page.marker_bindings = json(serde_json::json!([
+            { "index": 0, "kind": "text", "selector": "[data-zx-e~=\"0\"]" },
+            { "index": 1, "kind": "event", "selector": "[data-zx-on-click=\"1\"]" }
+        ]));
+        page.expression_bindings = json(serde_json::json!([
+            { "marker_index": 0, "state_index": 0 },
+            { "marker_index": 1, "state_index": 1 }
+        ]));
+
+        // Implicit layout, explicit page
+        let composed = CompilerOutput::compose(&layout(), &page, "main").unwrap();
+        let markers: Vec<usize> = composed.marker_bindings.iter().map(|m| m.index).collect();
+        assert_eq!(markers, [0, 1, 2, 3]);
+        assert!(crate::utils::validate_marker_bindings(markers, 4).is_ok());
+        let bindings: Vec<(usize, Option<&str>)> = composed
+            .expression_bindings
+            .iter()
+            .map(|b| (b.marker_index, b.literal.as_deref()))
+            .collect();
+        assert_eq!(
+            bindings,
+            [
+                (0, Some("siteTitle")),
+                (1, Some("year")),
+                (2, None),
+                (3, None)
+            ]
+        );
+
+        // Explicit layout, implicit page: the page's click handler gets its
+        // event binding along with its marker
+        let mut explicit_layout = layout();
+        explicit_layout.marker_bindings = json(serde_json::json!([
+            { "index": 0, "kind": "text", "selector": "[data-zx-e~=\"0\"]" },
+            { "index": 1, "kind": "text", "selector": "[data-zx-e~=\"1\"]" }
+        ]));
+        let implicit_page = CompilerOutput::mock(page.html.as_str(), &["title", "increment"]);
+        let composed = CompilerOutput::compose(&explicit_layout, &implicit_page, "main").unwrap();
+        let markers: Vec<usize> = composed.marker_bindings.iter().map(|m| m.index).collect();
+        assert_eq!(markers, [0, 1, 2, 3]);
+        assert_eq!(composed.event_bindings.len(), 1);
+        assert_eq!(composed.event_bindings[0].index, 3);
+        assert_eq!(
+            composed.event_bindings[0].selector,
+            "[data-zx-on-click=\"3\"]"
+        );
+    }
+
+    #[test]
+    fn rejects_hoisted_code_declaring_the_same_name() {
+        let mut page = page();
+        page.hoisted.code = vec!["function helper() {}\nconst siteTitle = 'Home';".into()];
+        let err = CompilerOutput::compose(&layout(), &page, "main")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'siteTitle'"), "{}", err);
+
+        // Names only used, not declared, are fine
+        page.hoisted.code = vec!["const heading = siteTitle();".into()];
+        assert!(CompilerOutput::compose(&layout(), &page, "main").is_ok());
+    }
+
+    #[test]
+    fn nested_slot_tags_close_at_the_right_depth() {
+        let outer = CompilerOutput::mock(
+            r#"<div data-slot><div class="fallback">x</div></div><div>after</div>"#,
+            &[],
+        );
+        let composed = CompilerOutput::compose(&outer, &page(), "[data-slot]").unwrap();
+        assert_eq!(
+            composed.html,
+            r#"<div data-slot><h1 data-zx-e="0"></h1><button data-zx-on-click="1">+</button><p data-zx-e='0 2'></p></div><div>after</div>"#
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_or_unusable_slots() {
+        let err = |outer_html: &str, selector: &str| {
+            let outer = CompilerOutput::mock(outer_html, &["a"]);
+            CompilerOutput::compose(&outer, &page(), selector)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(err("<main></main>", "slot").contains("No element matches"));
+        assert!(err("<slot></slot><slot></slot>", "slot").contains("more than one"));
+        assert!(err(r#"<slot><b data-zx-e="0"></b></slot>"#, "slot").contains("bound content"));
+        assert!(err("<slot/>", "slot").contains("self-closing"));
+        assert!(err("<slot>", "slot").contains("never closed"));
+        assert!(err("<slot></slot>", "main > slot").contains("Unsupported slot selector"));
+    }
+
+    #[test]
+    fn rejects_colliding_state_and_versions() {
+        use zenith_compiler::compiler::StateBinding;
+
+        let mut outer = layout();
+        let mut inner = page();
+        for output in [&mut outer, &mut inner] {
+            output.hoisted.state = vec![StateBinding {
+                key: "count".into(),
+                value: "0".into(),
+            }];
+        }
+        let err = CompilerOutput::compose(&outer, &inner, "main")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("state key 'count'"), "{}", err);
+
+        let mut newer = page();
+        newer.ir_version = 2;
+        let err = CompilerOutput::compose(&layout(), &newer, "main")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("IR version 2"), "{}", err);
+    }
+}
@@ -0,0 +1,171 @@
+//! CSS tree-shaking.
+//!
+//! Prunes page CSS against the class names the compiled HTML actually uses,
+//! then minifies it with lightningcss. Runs for `Prod` and `SSG` builds;
+//! `Dev` ships the collected CSS untouched.
+//!
+//! The selector policy is conservative: a rule is only dropped when every
+//! class it mentions is known to be unused.
+
+use std::collections::HashSet;
+
+use lightningcss::rules::CssRule;
+use lightningcss::selector::{Component, Selector};
+use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
+use lightningcss::targets::Browsers;
+use regex::Regex;
+
+use crate::BundleError;
+
+/// Collect the static class names used by a page's HTML.
+///
+/// Returns `None` when a class is bound to an expression (`data-zx-class`),
+/// since the classes it produces are only known at runtime — pruning must
+/// be skipped for such pages.
+pub fn collect_used_classes(html: &str) -> Option<HashSet<String>> {
+    let dynamic_re = Regex::new(r"\sdata-zx-class\s*=").unwrap();
+    if dynamic_re.is_match(html) {
+        return None;
+    }
+
+    let class_re = Regex::new(r#"\sclass\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>"']+))"#).unwrap();
+    let classes = class_re
+        .captures_iter(html)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)).or_else(|| caps.get(3)))
+        .flat_map(|m| m.as_str().split_whitespace())
+        .map(str::to_string)
+        .collect();
+    Some(classes)
+}
+
+/// Prune rules whose classes are all unused, then minify.
+///
+/// `filename` names the CSS source in parse errors. Element, id and
+/// universal selectors are always kept, as are at-rules other than
+/// `@media` / `@supports` (keyframes, font faces, ...).
+pub fn prune_css(
+    css: &str,
+    filename: &str,
+    used_classes: &HashSet<String>,
+) -> Result<String, BundleError> {
+    let options = ParserOptions {
+        filename: filename.to_string(),
+        ..ParserOptions::default()
+    };
+    let mut stylesheet = StyleSheet::parse(css, options)
+        .map_err(|e| BundleError::CssError(format!("parse error in {}: {}", filename, e)))?;
+
+    prune_rules(&mut stylesheet.rules.0, used_classes);
+
+    stylesheet
+        .minify(MinifyOptions {
+            targets: Browsers::default().into(),
+            ..Default::default()
+        })
+        .map_err(|e| BundleError::CssError(format!("minify error in {}: {}", filename, e)))?;
+
+    let printed = stylesheet
+        .to_css(PrinterOptions {
+            minify: true,
+            ..Default::default()
+        })
+        .map_err(|e| BundleError::CssError(format!("print error in {}: {}", filename, e)))?;
+
+    Ok(printed.code)
+}
+
+/// Recursively drop unused selectors, and rules left with none.
+fn prune_rules(rules: &mut Vec<CssRule>, used_classes: &HashSet<String>) {
+    rules.retain_mut(|rule| match rule {
+        CssRule::Style(style_rule) => {
+            style_rule
+                .selectors
+                .0
+                .retain(|selector| is_selector_used(selector, used_classes));
+            !style_rule.selectors.0.is_empty()
+        }
+        CssRule::Media(media_rule) => {
+            prune_rules(&mut media_rule.rules.0, used_classes);
+            !media_rule.rules.0.is_empty()
+        }
+        CssRule::Supports(supports_rule) => {
+            prune_rules(&mut supports_rule.rules.0, used_classes);
+            !supports_rule.rules.0.is_empty()
+        }
+        // Keyframes, font faces, etc. are kept — usage is not tracked for them
+        _ => true,
+    });
+}
+
+/// A selector is used if it mentions no classes, or at least one used class.
+fn is_selector_used(selector: &Selector, used_classes: &HashSet<String>) -> bool {
+    let mut has_classes = false;
+    for component in selector.iter() {
+        if let Component::Class(ident) = component {
+            if used_classes.contains(ident.as_ref()) {
+                return true;
+            }
+            has_classes = true;
+        }
+    }
+    !has_classes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn used(classes: &[&str]) -> HashSet<String> {
+        classes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn collect_used_classes_reads_static_attributes() {
+        let html = r#"<div class="card  hero"><p class='lead'>{x}</p><i class=icon></i></div>"#;
+        assert_eq!(
+            collect_used_classes(html),
+            Some(used(&["card", "hero", "lead", "icon"]))
+        );
+    }
+
+    #[test]
+    fn collect_used_classes_bails_on_dynamic_class() {
+        let html = r#"<div class="card" data-zx-class="0"></div>"#;
+        assert_eq!(collect_used_classes(html), None);
+    }
+
+    #[test]
+    fn prune_removes_unused_classes() {
+        let css = ".foo { color: red; } .bar { color: blue; } .baz { color: green; }";
+        let result = prune_css(css, "page.zen", &used(&["foo"])).unwrap();
+        assert!(
+            result.contains("red"),
+            "Should keep .foo (used): {}",
+            result
+        );
+        assert!(!result.contains("blue"), "Should prune .bar: {}", result);
+        assert!(!result.contains("green"), "Should prune .baz: {}", result);
+    }
+
+    #[test]
+    fn prune_keeps_element_and_id_selectors() {
+        let css = "body { margin: 0; } #app { display: flex; }";
+        let result = prune_css(css, "page.zen", &used(&[])).unwrap();
+        assert!(result.contains("margin"), "Should keep body: {}", result);
+        assert!(result.contains("flex"), "Should keep #app: {}", result);
+    }
+
+    #[test]
+    fn prune_keeps_compound_with_any_used_class() {
+        let css = ".foo.bar { color: red; }";
+        let result = prune_css(css, "page.zen", &used(&["foo"])).unwrap();
+        assert!(result.contains("red"), "Should keep .foo.bar: {}", result);
+    }
+
+    #[test]
+    fn prune_drops_emptied_media_rule() {
+        let css = "@media (min-width: 1px) { .gone { color: red; } }";
+        let result = prune_css(css, "page.zen", &used(&[])).unwrap();
+        assert!(!result.contains("@media"), "{}", result);
+    }
+}
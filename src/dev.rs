@@ -0,0 +1,215 @@
+//! Native dev server core — watch a project and rebuild on change.
+//!
+//! [`DevServer`] is the Rust-side counterpart of the Node dev controller:
+//! it watches a project root with `notify`, debounces bursts of changes, and
+//! rebuilds every page in one Rolldown graph via [`bundle_pages`]. The
+//! compile cache and CSS cache live as long as the server, so a rebuild only
+//! recompiles the `.zen` sources that changed.
+//!
+//! Subscribers receive a [`RebuildEvent`] per build over a
+//! `tokio::sync::broadcast` channel.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::bundle::DEFAULT_OUT_DIR;
+use crate::{
+    bundle_pages, BundleError, BundleOptions, BundlePlan, BundleResult, CancellationToken,
+    CompileCache, CssCache,
+};
+
+/// Quiet period a burst of file events must settle for before a rebuild.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Rebuild events buffered per subscriber before the oldest are dropped.
+const EVENT_CAPACITY: usize = 16;
+
+/// Directories whose changes never trigger a rebuild.
+const IGNORED_DIRS: &[&str] = &[".git", "node_modules"];
+
+/// Outcome of one build, broadcast to every subscriber.
+#[derive(Debug, Clone)]
+pub enum RebuildEvent {
+    /// Every page built. `changed` is empty for the initial build.
+    Built {
+        changed: Vec<PathBuf>,
+        results: Arc<Vec<BundleResult>>,
+    },
+    /// The build failed; the previous results stay current.
+    Failed {
+        changed: Vec<PathBuf>,
+        error: Arc<BundleError>,
+    },
+}
+
+/// Watches a project root and rebuilds its pages when files change.
+pub struct DevServer {
+    root: PathBuf,
+    plans: Vec<BundlePlan>,
+    opts: BundleOptions,
+    debounce: Duration,
+    events: broadcast::Sender<RebuildEvent>,
+}
+
+impl DevServer {
+    /// Server for `plans`, watching everything under `root`.
+    ///
+    /// A compile cache and CSS cache are created unless `opts` already
+    /// carries them; either way they are reused by every rebuild.
+    pub fn new(root: impl Into<PathBuf>, plans: Vec<BundlePlan>, mut opts: BundleOptions) -> Self {
+        let root = root.into();
+        let root = std::path::absolute(&root).unwrap_or(root);
+        opts.compile_cache.get_or_insert_with(CompileCache::default);
+        opts.css_cache
+            .get_or_insert_with(|| Arc::new(CssCache::new()));
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            root,
+            plans,
+            opts,
+            debounce: DEFAULT_DEBOUNCE,
+            events,
+        }
+    }
+
+    /// Wait for `debounce` without file events before rebuilding (default:
+    /// [`DEFAULT_DEBOUNCE`]).
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Receive a [`RebuildEvent`] for every build from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<RebuildEvent> {
+        self.events.subscribe()
+    }
+
+    /// The CSS cache shared by every rebuild, for serving CSS live reloads.
+    pub fn css_cache(&self) -> Arc<CssCache> {
+        Arc::clone(self.opts.css_cache.as_ref().expect("set in DevServer::new"))
+    }
+
+    /// Build every page once and broadcast the outcome.
+    pub async fn rebuild(&self, changed: Vec<PathBuf>) -> RebuildEvent {
+        let event = match bundle_pages(self.plans.clone(), self.opts.clone()).await {
+            Ok(results) => RebuildEvent::Built {
+                changed,
+                results: Arc::new(results),
+            },
+            Err(error) => RebuildEvent::Failed {
+                changed,
+                error: Arc::new(error),
+            },
+        };
+        // No subscribers is not an error
+        let _ = self.events.send(event.clone());
+        event
+    }
+
+    /// Build once, then rebuild after each debounced burst of changes under
+    /// the root until `cancel` is tripped.
+    ///
+    /// Changes inside output directories, `.git` and `node_modules` are
+    /// ignored, so builds that write to disk do not retrigger themselves.
+    pub async fn run(&self, cancel: CancellationToken) -> Result<(), BundleError> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                let _ = tx.send(event);
+            }
+        })
+        .map_err(watch_error)?;
+        watcher
+            .watch(&self.root, RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+
+        self.rebuild(Vec::new()).await;
+
+        loop {
+            let mut changed = Vec::new();
+            // Block until something relevant changes, then drain the burst
+            while changed.is_empty() {
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    event = rx.recv() => match event {
+                        Some(event) => self.collect_changes(event, &mut changed),
+                        None => return Ok(()),
+                    },
+                }
+            }
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    _ = tokio::time::sleep(self.debounce) => break,
+                    event = rx.recv() => match event {
+                        Some(event) => self.collect_changes(event, &mut changed),
+                        None => break,
+                    },
+                }
+            }
+            changed.sort();
+            changed.dedup();
+            self.rebuild(changed).await;
+        }
+    }
+
+    fn collect_changes(&self, event: notify::Event, changed: &mut Vec<PathBuf>) {
+        if matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            changed.extend(event.paths.into_iter().filter(|path| self.is_watched(path)));
+        }
+    }
+
+    /// Whether a change to `path` should trigger a rebuild.
+    fn is_watched(&self, path: &Path) -> bool {
+        let in_ignored_dir = path.components().any(|component| match component {
+            Component::Normal(name) => IGNORED_DIRS.iter().any(|dir| name == *dir),
+            _ => false,
+        });
+        !in_ignored_dir && !self.output_dirs().any(|out_dir| path.starts_with(out_dir))
+    }
+
+    /// Directories the pages are written to, absolute.
+    fn output_dirs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.plans.iter().map(|plan| {
+            let out_dir = plan
+                .out_dir
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_OUT_DIR));
+            std::path::absolute(&out_dir).unwrap_or(out_dir)
+        })
+    }
+}
+
+fn watch_error(e: notify::Error) -> BundleError {
+    BundleError::IoError(std::io::Error::other(format!("File watcher failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildMode;
+
+    #[test]
+    fn output_and_vendor_dirs_are_not_watched() {
+        let root = std::env::temp_dir().join("zenith-dev-server");
+        let plan = BundlePlan {
+            page_path: root.join("src/index.zen").to_string_lossy().to_string(),
+            out_dir: Some(root.join("public")),
+            mode: BuildMode::Dev,
+        };
+        let server = DevServer::new(&root, vec![plan], BundleOptions::default());
+
+        assert!(server.is_watched(&root.join("src/index.zen")));
+        assert!(server.is_watched(&root.join("src/lib/util.js")));
+        assert!(!server.is_watched(&root.join("public/assets/index.js")));
+        assert!(!server.is_watched(&root.join("node_modules/gsap/index.js")));
+        assert!(!server.is_watched(&root.join(".git/index")));
+    }
+}
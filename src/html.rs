@@ -0,0 +1,718 @@
+//! HTML Injection
+//!
+//! Injects hashed asset references into a page document:
+//! - `<link rel="modulepreload">` for chunks the entry statically imports
+//! - `<link rel="preconnect">` / `<link rel="dns-prefetch">` for external origins
+//! - `<link rel="stylesheet">` and the entry `<script type="module">` (SSG)
+//! - a `<script type="importmap">` for external dependencies, ahead of every
+//!   module script
+//! - or, for self-contained pages, the entry JS and CSS inline
+//!
+//! Hints and stylesheets are placed before `</head>` so the browser can start
+//! fetching in parallel with the entry script, which goes before `</body>`.
+//! Every injector is idempotent.
+//!
+//! `</body>` is located with a minimal scanner that skips comments, CDATA
+//! and `<script>`/`<style>` text, so a commented-out `</body>` is never an
+//! injection point.
+
+use std::collections::{BTreeMap, HashMap};
+
+use regex::Regex;
+
+use crate::BundleError;
+
+/// Wrap an HTML fragment in a minimal document shell.
+///
+/// Input that already contains an `<html` element is returned unchanged.
+pub fn ensure_document_html(fragment_or_doc: &str) -> String {
+    if fragment_or_doc.contains("<html") {
+        return fragment_or_doc.to_string();
+    }
+    wrap_document_html(fragment_or_doc)
+}
+
+/// Wrap `html` in a minimal document shell, even if it is already a document.
+pub fn wrap_document_html(html: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head></head><body>{}</body></html>",
+        html
+    )
+}
+
+/// Inject a `<link rel="stylesheet">` for `href` before `</head>`.
+///
+/// Skipped if the document already references `href`. Documents without a
+/// `</head>` are returned unchanged.
+pub fn inject_stylesheet(html: &str, href: &str) -> String {
+    if html.contains(&format!(r#"href="{}""#, href)) {
+        return html.to_string();
+    }
+    let tag = format!(r#"<link rel="stylesheet" href="{}">"#, href);
+    html.replacen("</head>", &format!("{}</head>", tag), 1)
+}
+
+/// Inject a `<script type="module">` for `src` before `</body>`, or at the
+/// end of the document if it has no `</body>`.
+///
+/// Skipped if the document already references `src`.
+pub fn inject_module_script(html: &str, src: &str) -> String {
+    if html.contains(&format!(r#"src="{}""#, src)) {
+        return html.to_string();
+    }
+    let tag = format!(r#"<script type="module" src="{}"></script>"#, src);
+    insert_before_body_close(html, &tag)
+}
+
+/// Byte offset of the document's closing `</body>` tag.
+///
+/// Comments, CDATA sections and `<script>`/`<style>` text are skipped. Errors
+/// unless exactly one `</body>` remains.
+pub fn body_close_offset(html: &str) -> Result<usize, BundleError> {
+    // ASCII lowercasing keeps byte offsets, so positions map back to `html`
+    let lower = html.to_ascii_lowercase();
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(rel) = lower[pos..].find('<') {
+        let start = pos + rel;
+        let rest = &lower[start..];
+        pos = if rest.starts_with("<!--") {
+            skip_past(&lower, start + 4, "-->")
+        } else if rest.starts_with("<![cdata[") {
+            skip_past(&lower, start + 9, "]]>")
+        } else if let Some(name) = ["script", "style"]
+            .into_iter()
+            .find(|name| is_tag(&rest[1..], name))
+        {
+            skip_past(&lower, start + 1 + name.len(), &format!("</{}", name))
+        } else {
+            if is_tag(&rest[1..], "/body") {
+                found.push(start);
+            }
+            start + 1
+        };
+    }
+
+    match found[..] {
+        [offset] => Ok(offset),
+        [] => Err(BundleError::ValidationError(
+            "Document has no `</body>` outside comments".to_string(),
+        )),
+        _ => Err(BundleError::ValidationError(format!(
+            "Document has {} `</body>` tags outside comments; expected exactly one",
+            found.len()
+        ))),
+    }
+}
+
+/// Insert `tag` before the document's `</body>` (see [`body_close_offset`]),
+/// or append it at the end if there is not exactly one.
+pub fn insert_before_body_close(html: &str, tag: &str) -> String {
+    match body_close_offset(html) {
+        Ok(offset) => {
+            let mut out = html.to_string();
+            out.insert_str(offset, tag);
+            out
+        }
+        Err(_) => format!("{}{}", html, tag),
+    }
+}
+
+/// Whether `rest` starts with the tag name `name` (followed by the end of
+/// the tag, whitespace or `/`).
+fn is_tag(rest: &str, name: &str) -> bool {
+    rest.starts_with(name)
+        && matches!(
+            rest.as_bytes().get(name.len()),
+            None | Some(b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+        )
+}
+
+/// Offset just past the first `terminator` at or after `from`, or the end
+/// of `html` if there is none.
+fn skip_past(html: &str, from: usize, terminator: &str) -> usize {
+    html[from..]
+        .find(terminator)
+        .map_or(html.len(), |i| from + i + terminator.len())
+}
+
+/// Marker in an HTML template that is replaced by the page's markup.
+pub const PAGE_OUTLET: &str = "<!-- zenith:page -->";
+
+/// Build a page document and inject its stylesheet and entry script.
+///
+/// With a `template` (e.g. a project's `index.html`), the first
+/// [`PAGE_OUTLET`] is replaced by `page_html`; a template without an outlet
+/// only receives the asset tags. Without a template, `page_html` is wrapped
+/// by [`ensure_document_html`].
+pub fn render_page_document(
+    template: Option<&str>,
+    page_html: &str,
+    script_src: &str,
+    css_href: Option<&str>,
+) -> String {
+    let mut document = match template {
+        Some(template) => template.replacen(PAGE_OUTLET, page_html, 1),
+        None => ensure_document_html(page_html),
+    };
+    if let Some(href) = css_href {
+        document = inject_stylesheet(&document, href);
+    }
+    inject_module_script(&document, script_src)
+}
+
+/// Build a self-contained page document: like [`render_page_document`], but
+/// with `css` inlined as a `<style>` block before `</head>` (any case) and
+/// `entry_js` as a `<script type="module">` before `</body>`. A document
+/// without a head gets the `<style>` just before the script instead.
+///
+/// Both are escaped with [`escape_inline_script`] / [`escape_inline_style`]
+/// so their contents cannot close the element early. A `sourceMappingURL`
+/// comment is dropped from the script: it would resolve against the page,
+/// where no map file is written.
+pub fn render_inline_page_document(
+    template: Option<&str>,
+    page_html: &str,
+    entry_js: &str,
+    css: Option<&str>,
+) -> String {
+    let mut document = match template {
+        Some(template) => template.replacen(PAGE_OUTLET, page_html, 1),
+        None => ensure_document_html(page_html),
+    };
+    let mut tags = String::new();
+    if let Some(css) = css {
+        let style = format!("<style>{}</style>", escape_inline_style(css));
+        match head_close_offset(&document) {
+            Some(offset) => document.insert_str(offset, &style),
+            None => tags.push_str(&style),
+        }
+    }
+    tags.push_str(&format!(
+        r#"<script type="module">{}</script>"#,
+        escape_inline_script(&strip_source_mapping_url(entry_js))
+    ));
+    insert_before_body_close(&document, &tags)
+}
+
+/// Offset of the document's first `</head>` (any case, whitespace allowed
+/// before the `>`).
+fn head_close_offset(html: &str) -> Option<usize> {
+    let close_re = Regex::new(r"(?i)</head\s*>").expect("valid head close regex");
+    close_re.find(html).map(|m| m.start())
+}
+
+/// Remove `//# sourceMappingURL=` (or legacy `//@`) comment lines from JS.
+fn strip_source_mapping_url(js: &str) -> String {
+    let map_re = Regex::new(r"(?m)^[ \t]*//[#@] sourceMappingURL=[^\n]*\n?")
+        .expect("valid sourceMappingURL regex");
+    map_re.replace_all(js, "").into_owned()
+}
+
+/// Escape JS for an inline `<script>` element.
+///
+/// `</script` (any case) would end the element and `<!--` would switch the
+/// HTML parser into its escaped script state. Both can only legally appear
+/// inside strings, templates, regexes or comments, so they are rewritten to
+/// forms that evaluate the same there: `<\/script` and `\x3C!--` (`\!` is
+/// not a valid escape in a `/u` regex, `\x3C` is).
+pub fn escape_inline_script(js: &str) -> String {
+    let close_re = Regex::new(r"(?i)</(script)").expect("valid script close regex");
+    close_re
+        .replace_all(js, r"<\/${1}")
+        .replace("<!--", r"\x3C!--")
+}
+
+/// Escape CSS for an inline `<style>` element (`</style` → `<\/style`).
+pub fn escape_inline_style(css: &str) -> String {
+    let close_re = Regex::new(r"(?i)</(style)").expect("valid style close regex");
+    close_re.replace_all(css, r"<\/${1}").into_owned()
+}
+
+/// Inject `<link rel="modulepreload">` tags for `preload_chunks` before `</head>`.
+///
+/// Chunks that already have a modulepreload link are skipped, so the call is
+/// idempotent. Documents without a `</head>` are returned unchanged.
+pub fn inject_modulepreload(html: &str, preload_chunks: &[String]) -> String {
+    let mut preload_links = String::new();
+
+    for chunk in preload_chunks {
+        let tag = format!(r#"<link rel="modulepreload" href="/{}">"#, chunk);
+        if html.contains(&tag) || preload_links.contains(&tag) {
+            continue;
+        }
+        preload_links.push_str("    ");
+        preload_links.push_str(&tag);
+        preload_links.push('\n');
+    }
+
+    let mut out = html.to_string();
+    if let Some(pos) = out.find("</head>") {
+        out.insert_str(pos, &preload_links);
+    }
+    out
+}
+
+/// Inject a `<script type="importmap">` mapping bare specifiers to URLs.
+///
+/// Entries are sorted by specifier so the map is byte-stable. It goes before
+/// the first module script or modulepreload link (browsers ignore an import
+/// map that follows them), else before `</head>`, else before `</body>`.
+/// Skipped if `imports` is empty or the document already has an import map.
+pub fn inject_import_map(html: &str, imports: &HashMap<String, String>) -> String {
+    if imports.is_empty() || html.contains(r#"<script type="importmap">"#) {
+        return html.to_string();
+    }
+    let sorted: BTreeMap<&str, &str> = imports
+        .iter()
+        .map(|(specifier, url)| (specifier.as_str(), url.as_str()))
+        .collect();
+    // `<` escaped so no URL can close the element early
+    let json = serde_json::json!({ "imports": sorted })
+        .to_string()
+        .replace('<', "\\u003c");
+    let tag = format!(r#"<script type="importmap">{}</script>"#, json);
+
+    let module_re = Regex::new(
+        r#"(?i)<script\b[^>]*\stype\s*=\s*["']?module\b|<link\b[^>]*\srel\s*=\s*["']?modulepreload\b"#,
+    )
+    .expect("valid module tag regex");
+    let offset = module_re
+        .find(html)
+        .map(|m| m.start())
+        .or_else(|| html.find("</head>"));
+    match offset {
+        Some(offset) => {
+            let mut out = html.to_string();
+            out.insert_str(offset, &tag);
+            out
+        }
+        None => insert_before_body_close(html, &tag),
+    }
+}
+
+/// Inject `preconnect` and `dns-prefetch` hints for each external origin.
+///
+/// Origins must be bare `http(s)://host[:port]` values (a trailing `/` is
+/// tolerated). Duplicate origins and origins already hinted in `html` are
+/// skipped. Documents without a `</head>` are returned unchanged.
+pub fn inject_resource_hints(html: &str, origins: &[String]) -> Result<String, BundleError> {
+    let origin_re =
+        Regex::new(r"^https?://[A-Za-z0-9](?:[A-Za-z0-9.-]*[A-Za-z0-9])?(?::\d{1,5})?$")
+            .expect("valid origin regex");
+
+    let mut hints = String::new();
+    for origin in origins {
+        let origin = origin.trim().trim_end_matches('/');
+        if !origin_re.is_match(origin) {
+            return Err(BundleError::ValidationError(format!(
+                "External origin `{}` is not a well-formed http(s) origin",
+                origin
+            )));
+        }
+
+        let preconnect = format!(r#"<link rel="preconnect" href="{}">"#, origin);
+        if html.contains(&preconnect) || hints.contains(&preconnect) {
+            continue;
+        }
+        hints.push_str("    ");
+        hints.push_str(&preconnect);
+        hints.push('\n');
+        hints.push_str(&format!(
+            "    <link rel=\"dns-prefetch\" href=\"{}\">\n",
+            origin
+        ));
+    }
+
+    let mut out = html.to_string();
+    if let Some(pos) = out.find("</head>") {
+        out.insert_str(pos, &hints);
+    }
+    Ok(out)
+}
+
+/// Inject a page's own head fragments (`<title>`, `<meta>`, `<link
+/// rel="canonical">`, ...) before `</head>` (any case), in the order given.
+///
+/// A `<title>` replaces the head's existing one (or one injected by an
+/// earlier fragment), since a document has only one. Other fragments already in the head, and repeats, are skipped;
+/// matching markup elsewhere in the document does not count. Fails when
+/// there are fragments but no `</head>` to put them before.
+pub fn inject_head_fragments(html: &str, fragments: &[String]) -> Result<String, String> {
+    let fragments: Vec<&str> = fragments
+        .iter()
+        .map(|fragment| fragment.trim())
+        .filter(|fragment| !fragment.is_empty())
+        .collect();
+    if fragments.is_empty() {
+        return Ok(html.to_string());
+    }
+    let Some(close) = head_close_offset(html) else {
+        return Err(format!(
+            "document has no </head>; {} head fragment(s) were not injected",
+            fragments.len()
+        ));
+    };
+    let open_re = Regex::new(r"(?i)<head\b[^>]*>").expect("valid head open regex");
+    let open = open_re.find(&html[..close]).map_or(0, |m| m.end());
+    let title_re = Regex::new(r"(?is)<title\b[^>]*>.*?</title>").expect("valid title regex");
+
+    let mut head = html[open..close].to_string();
+    let mut tags = String::new();
+    for fragment in fragments {
+        if head.contains(fragment) || tags.contains(fragment) {
+            continue;
+        }
+        if title_re.is_match(fragment) {
+            if let Some(existing) = title_re.find(&head).map(|m| m.range()) {
+                head.replace_range(existing, fragment);
+                continue;
+            }
+            if let Some(existing) = title_re.find(&tags).map(|m| m.range()) {
+                tags.replace_range(existing, fragment);
+                continue;
+            }
+        }
+        tags.push_str("    ");
+        tags.push_str(fragment);
+        tags.push('\n');
+    }
+
+    Ok(format!(
+        "{}{}{}{}",
+        &html[..open],
+        head,
+        tags,
+        &html[close..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_document_html_wraps_fragments_only() {
+        let doc = ensure_document_html("<h1>hi</h1>");
+        assert_eq!(
+            doc,
+            "<!DOCTYPE html><html><head></head><body><h1>hi</h1></body></html>"
+        );
+        assert_eq!(ensure_document_html(&doc), doc);
+    }
+
+    #[test]
+    fn test_inject_page_assets_is_idempotent() {
+        let doc = ensure_document_html("<h1>hi</h1>");
+        let once = inject_module_script(
+            &inject_stylesheet(&doc, "/assets/home.1234.css"),
+            "/assets/home.abcd.js",
+        );
+        let twice = inject_module_script(
+            &inject_stylesheet(&once, "/assets/home.1234.css"),
+            "/assets/home.abcd.js",
+        );
+        assert_eq!(once, twice);
+
+        let link = once
+            .find(r#"<link rel="stylesheet" href="/assets/home.1234.css">"#)
+            .unwrap();
+        let script = once
+            .find(r#"<script type="module" src="/assets/home.abcd.js"></script>"#)
+            .unwrap();
+        assert!(link < once.find("</head>").unwrap());
+        assert!(script < once.find("</body>").unwrap());
+    }
+
+    #[test]
+    fn test_body_close_skips_commented_out_body() {
+        let doc = "<html><body><!-- old layout </body> --><p>x</p>\
+                   <script>const s = '</body>';</script></BODY></html>";
+        assert_eq!(
+            body_close_offset(doc).unwrap(),
+            doc.find("</BODY>").unwrap()
+        );
+
+        let out = insert_before_body_close(doc, "<i></i>");
+        assert!(out.contains("<!-- old layout </body> -->"));
+        assert!(out.ends_with("<i></i></BODY></html>"));
+    }
+
+    #[test]
+    fn test_body_close_missing_or_repeated_appends_at_end() {
+        let missing = "<html><body><p>x</p><!-- </body> --></html>";
+        assert!(body_close_offset(missing).is_err());
+        assert_eq!(
+            insert_before_body_close(missing, "<i></i>"),
+            format!("{}<i></i>", missing)
+        );
+
+        let repeated = "<body></body><body></body>";
+        let err = body_close_offset(repeated).unwrap_err();
+        assert!(err.to_string().contains("2 `</body>` tags"), "{}", err);
+        assert_eq!(
+            insert_before_body_close(repeated, "<i></i>"),
+            "<body></body><body></body><i></i>"
+        );
+
+        // `<bodyx>`-style names are not the body element
+        assert!(body_close_offset("<body></bodyx>").is_err());
+    }
+
+    #[test]
+    fn test_render_page_document_from_template() {
+        let template = "<html><head><title>Site</title></head>\
+                        <body><main><!-- zenith:page --></main></body></html>";
+        let doc = render_page_document(
+            Some(template),
+            "<h1>hi</h1>",
+            "/assets/a.js",
+            Some("/assets/a.css"),
+        );
+        assert!(doc.contains("<main><h1>hi</h1></main>"));
+        assert!(doc.contains(r#"<link rel="stylesheet" href="/assets/a.css"></head>"#));
+        assert!(doc.contains(r#"<script type="module" src="/assets/a.js"></script></body>"#));
+
+        // Re-rendering over the output does not stack tags
+        let again = render_page_document(Some(&doc), "", "/assets/a.js", Some("/assets/a.css"));
+        assert_eq!(again, doc);
+    }
+
+    #[test]
+    fn test_render_page_document_default_shell() {
+        let doc = render_page_document(None, "<p>x</p>", "/assets/a.js", None);
+        assert_eq!(
+            doc,
+            "<!DOCTYPE html><html><head></head><body><p>x</p>\
+             <script type=\"module\" src=\"/assets/a.js\"></script></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_render_inline_page_document() {
+        let template = "<html><head><title>Site</title></head>\
+                        <body><main><!-- zenith:page --></main></body></html>";
+        let doc = render_inline_page_document(
+            Some(template),
+            "<h1>hi</h1>",
+            "export const a = 1;",
+            Some(".a { color: red; }"),
+        );
+        assert_eq!(
+            doc,
+            "<html><head><title>Site</title><style>.a { color: red; }</style></head>\
+             <body><main><h1>hi</h1></main>\
+             <script type=\"module\">export const a = 1;</script></body></html>"
+        );
+
+        let doc = render_inline_page_document(
+            None,
+            "<p>x</p>",
+            "export const a = 1;\n//# sourceMappingURL=page.js.map\n",
+            None,
+        );
+        assert!(doc.contains("<script type=\"module\">export const a = 1;\n</script>"));
+        assert!(!doc.contains("sourceMappingURL"));
+
+        // The head is found in any case; without one the styles still ship
+        let doc = render_inline_page_document(
+            Some("<HTML><HEAD></HEAD ><BODY><!-- zenith:page --></BODY></HTML>"),
+            "<p>x</p>",
+            "",
+            Some(".a{}"),
+        );
+        assert!(
+            doc.starts_with("<HTML><HEAD><style>.a{}</style></HEAD >"),
+            "{}",
+            doc
+        );
+        let doc = render_inline_page_document(
+            Some("<main><!-- zenith:page --></main>"),
+            "<p>x</p>",
+            "",
+            Some(".a{}"),
+        );
+        assert!(
+            doc.ends_with("<style>.a{}</style><script type=\"module\"></script>"),
+            "{}",
+            doc
+        );
+
+        let doc = render_inline_page_document(None, "<p>x</p>", "", None);
+        assert!(!doc.contains("<style>"));
+        assert!(doc.ends_with("<script type=\"module\"></script></body></html>"));
+    }
+
+    #[test]
+    fn test_escape_inline_script_and_style() {
+        let js = r#"const s = "</script><!-- </SCRIPT >";"#;
+        assert_eq!(
+            escape_inline_script(js),
+            r#"const s = "<\/script>\x3C!-- <\/SCRIPT >";"#
+        );
+        assert_eq!(escape_inline_script("/<!--/u"), r"/\x3C!--/u");
+        assert_eq!(escape_inline_script("a < b && c > d"), "a < b && c > d");
+
+        assert_eq!(
+            escape_inline_style(r#".a::after { content: "</style>"; }"#),
+            r#".a::after { content: "<\/style>"; }"#
+        );
+    }
+
+    #[test]
+    fn test_inject_head_fragments_in_order_without_repeats() {
+        let doc = ensure_document_html("<h1>hi</h1>");
+        let head = [
+            "<title>About</title>".to_string(),
+            r#"<meta name="description" content="About us">"#.to_string(),
+            r#"<link rel="canonical" href="https://example.com/about">"#.to_string(),
+            r#"<meta name="description" content="About us">"#.to_string(),
+        ];
+        let out = inject_head_fragments(&doc, &head).unwrap();
+        assert_eq!(
+            out,
+            "<!DOCTYPE html><html><head>    <title>About</title>\n    \
+             <meta name=\"description\" content=\"About us\">\n    \
+             <link rel=\"canonical\" href=\"https://example.com/about\">\n\
+             </head><body><h1>hi</h1></body></html>"
+        );
+        assert_eq!(inject_head_fragments(&out, &head).unwrap(), out);
+
+        // A page title replaces the shell's
+        let shell = "<html><head><title>Site</title></head><body></body></html>";
+        assert_eq!(
+            inject_head_fragments(shell, &head[..1]).unwrap(),
+            "<html><head><title>About</title></head><body></body></html>"
+        );
+
+        // Without one in the head, the last title fragment wins
+        let titles = [
+            "<title>About</title>".to_string(),
+            "<title>Team</title>".to_string(),
+        ];
+        let out = inject_head_fragments(&doc, &titles).unwrap();
+        assert_eq!(out.matches("<title>").count(), 1, "{}", out);
+        assert!(out.contains("<title>Team</title>"), "{}", out);
+    }
+
+    #[test]
+    fn test_inject_head_fragments_looks_only_at_the_head() {
+        let head = [
+            "<title>About</title>".to_string(),
+            r#"<link rel="canonical" href="/about">"#.to_string(),
+        ];
+        // Markup in the body neither dedups nor gets replaced; the closing
+        // tag matches in any case
+        let doc = "<HTML><HEAD></HEAD><BODY><svg><title>Icon</title></svg>\
+                   <template><link rel=\"canonical\" href=\"/about\"></template></BODY></HTML>";
+        let out = inject_head_fragments(doc, &head).unwrap();
+        let close = out.find("</HEAD>").unwrap();
+        assert!(out[..close].contains("<title>About</title>"), "{}", out);
+        assert!(
+            out[..close].contains(r#"<link rel="canonical" href="/about">"#),
+            "{}",
+            out
+        );
+        assert!(out[close..].contains("<title>Icon</title>"), "{}", out);
+
+        // No head to inject into
+        let err = inject_head_fragments("<h1>hi</h1>", &head).unwrap_err();
+        assert!(err.contains("2 head fragment(s)"), "{}", err);
+        assert_eq!(
+            inject_head_fragments("<h1>hi</h1>", &[]).unwrap(),
+            "<h1>hi</h1>"
+        );
+    }
+
+    #[test]
+    fn test_inject_modulepreload_before_head_close() {
+        let html = "<html><head>\n</head><body></body></html>";
+        let out = inject_modulepreload(html, &["runtime-core.js".into(), "shared.js".into()]);
+
+        let core = out
+            .find(r#"<link rel="modulepreload" href="/runtime-core.js">"#)
+            .unwrap();
+        let shared = out
+            .find(r#"<link rel="modulepreload" href="/shared.js">"#)
+            .unwrap();
+        let head_close = out.find("</head>").unwrap();
+        assert!(core < shared && shared < head_close);
+    }
+
+    #[test]
+    fn test_inject_modulepreload_is_idempotent() {
+        let html = "<html><head></head><body></body></html>";
+        let chunks = vec!["shared.js".to_string()];
+        let once = inject_modulepreload(html, &chunks);
+        let twice = inject_modulepreload(&once, &chunks);
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn test_inject_import_map_precedes_module_scripts() {
+        let imports = HashMap::from([
+            (
+                "gsap".to_string(),
+                "https://cdn.example.com/gsap.js".to_string(),
+            ),
+            ("@motion/".to_string(), "/vendor/motion/".to_string()),
+        ]);
+        let doc = render_page_document(None, "<p>x</p>", "/assets/a.js", None);
+        let doc = inject_modulepreload(&doc, &["assets/shared.js".to_string()]);
+
+        let once = inject_import_map(&doc, &imports);
+        let map = once
+            .find(r#"<script type="importmap">{"imports":{"@motion/":"/vendor/motion/","gsap":"https://cdn.example.com/gsap.js"}}</script>"#)
+            .unwrap();
+        assert!(map < once.find(r#"rel="modulepreload""#).unwrap());
+        assert!(map < once.find(r#"<script type="module""#).unwrap());
+        assert_eq!(inject_import_map(&once, &imports), once);
+
+        // Without preloads, the map lands right before the entry script
+        let doc = render_page_document(None, "<p>x</p>", "/assets/a.js", None);
+        let out = inject_import_map(&doc, &imports);
+        assert!(
+            out.contains(r#"}}</script><script type="module" src="/assets/a.js">"#),
+            "{}",
+            out
+        );
+
+        let hostile = HashMap::from([("x".to_string(), "/</script>".to_string())]);
+        assert!(!inject_import_map(&doc, &hostile).contains("/</script>"));
+        assert_eq!(inject_import_map(&doc, &HashMap::new()), doc);
+    }
+
+    #[test]
+    fn test_inject_resource_hints_dedupes_and_is_idempotent() {
+        let html = "<html><head></head><body></body></html>";
+        let origins = vec![
+            "https://fonts.gstatic.com".to_string(),
+            "https://cdn.example.com:8443/".to_string(),
+            "https://fonts.gstatic.com".to_string(),
+        ];
+
+        let once = inject_resource_hints(html, &origins).unwrap();
+        assert_eq!(once.matches(r#"rel="preconnect""#).count(), 2);
+        assert_eq!(once.matches(r#"rel="dns-prefetch""#).count(), 2);
+        assert!(once.contains(r#"<link rel="preconnect" href="https://cdn.example.com:8443">"#));
+
+        let twice = inject_resource_hints(&once, &origins).unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn test_inject_resource_hints_rejects_malformed_origin() {
+        let html = "<html><head></head><body></body></html>";
+        for bad in [
+            "fonts.gstatic.com",
+            "https://cdn.example.com/fonts",
+            "javascript:alert(1)",
+        ] {
+            assert!(
+                inject_resource_hints(html, &[bad.to_string()]).is_err(),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -3,15 +3,28 @@
 //! Deterministic bundler that consumes the sealed `CompilerOutput` from
 //! `zenith_compiler` and produces executable JS + virtual CSS.
 //!
-//! The bundler must NOT mutate, re-index, or reinterpret compiler output.
-//! It resolves modules/imports only — never components or cross-file semantics.
+//! The bundler must NOT mutate, re-index, or reinterpret compiler output,
+//! with two sanctioned exceptions: `CompilerOutput::compose` re-indexes a
+//! page spliced into a layout (see [`compose`]), and the opt-in
+//! [`BundleOptions::normalize_expression_whitespace`] canonicalizes
+//! expression whitespace. It resolves modules/imports only — never
+//! components or cross-file semantics.
 
 pub mod bundle;
+pub mod cli_input;
+pub mod compose;
+pub mod css;
+pub mod dev;
+pub mod html;
 pub mod plugin;
+pub mod router;
+pub mod testing;
 pub mod utils;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -19,6 +32,12 @@ use thiserror::Error;
 // Re-export the compiler's sealed type so consumers don't need a separate dep
 pub use zenith_compiler::compiler::CompilerOutput;
 
+pub use plugin::css_cache::CssCache;
+pub use plugin::zenith_loader::{CompileCache, ContentResolver};
+
+// Re-exported for `bundle_page_cancellable` callers
+pub use tokio_util::sync::CancellationToken;
+
 // ---------------------------------------------------------------------------
 // Build Mode
 // ---------------------------------------------------------------------------
@@ -30,10 +49,34 @@ pub enum BuildMode {
     Dev,
     /// Production — no sourcemaps by default, minification enabled.
     Prod,
-    /// Static Site Generation — write to disk, production optimizations.
+    /// Static Site Generation — production optimizations, and every page is
+    /// written to disk as hashed assets plus an HTML document at its route.
     SSG,
 }
 
+/// How Rolldown minifies the emitted JS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MinifyMode {
+    /// Output is left as generated.
+    Off,
+    /// Whitespace is collapsed; no mangling or compression. Useful for
+    /// bisecting minifier issues while keeping output compact.
+    Whitespace,
+    /// Rolldown's full minifier: whitespace, compression and mangling.
+    Full,
+}
+
+impl From<bool> for MinifyMode {
+    fn from(minify: bool) -> Self {
+        if minify {
+            MinifyMode::Full
+        } else {
+            MinifyMode::Off
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Component Definition (opaque to bundler)
 // ---------------------------------------------------------------------------
@@ -44,7 +87,8 @@ pub enum BuildMode {
 pub struct ComponentDef {
     /// Filesystem path to the component's `.zen` file.
     pub path: PathBuf,
-    /// Raw template source (if pre-loaded).
+    /// Raw `.zen` source (if pre-loaded). Used instead of reading `path`,
+    /// both when the module is compiled and when its styles are collected.
     pub source: Option<String>,
 }
 
@@ -58,6 +102,35 @@ pub struct Diagnostic {
     pub level: DiagnosticLevel,
     pub message: String,
     pub context: Option<String>,
+    /// Machine-readable code (see [`codes`]). Stable across message rewording,
+    /// so CI can gate on it via [`BundleOptions::fail_on_codes`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<Cow<'static, str>>,
+}
+
+/// Stable diagnostic codes.
+pub mod codes {
+    /// The page has no expressions — it ships as fully static HTML.
+    pub const STATIC_PAGE: &str = "static_page";
+    /// The loader recorded no compiled output for the page id; validation
+    /// ran against an empty expression table.
+    pub const COMPILED_OUTPUT_MISSING: &str = "compiled_output_missing";
+    /// Rolldown could not resolve an import. Rolldown warnings carry their
+    /// event kind, lowercased, as their code.
+    pub const UNRESOLVED_IMPORT: &str = "unresolved_import";
+    /// Rolldown found an import cycle.
+    pub const CIRCULAR_DEPENDENCY: &str = "circular_dependency";
+    /// The [`MinifyMode`](crate::MinifyMode) the page was built with.
+    pub const MINIFY_MODE: &str = "minify_mode";
+    /// Strict mode: an event binding's expression is not callable (a
+    /// literal, state, or a call where a handler was meant).
+    pub const EVENT_HANDLER_NOT_CALLABLE: &str = "event_handler_not_callable";
+    /// The document does not have exactly one `</body>` outside comments, so
+    /// injected scripts are appended at its end instead.
+    pub const MALFORMED_DOCUMENT: &str = "malformed_document";
+    /// The document has no `</head>`, so the page's head fragments were not
+    /// injected.
+    pub const MISSING_HEAD: &str = "missing_head";
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -76,7 +149,8 @@ pub enum DiagnosticLevel {
 pub struct BundlePlan {
     /// Path to the `.zen` page file (relative or absolute).
     pub page_path: String,
-    /// Output directory. Defaults to `dist/`.
+    /// Output directory for SSG and `write_to_disk` builds. Defaults to
+    /// [`bundle::DEFAULT_OUT_DIR`]; in-memory builds ignore it.
     pub out_dir: Option<PathBuf>,
     /// Build mode.
     pub mode: BuildMode,
@@ -93,14 +167,142 @@ pub struct BundleOptions {
     /// Forwarded to the loader. Bundler never resolves these.
     pub components: Option<HashMap<String, ComponentDef>>,
     /// Optional pre-compiled metadata for validation.
-    /// If provided, the bundler validates post-build expressions match.
+    /// If provided, the bundler validates the page's expressions match.
+    /// Describes one page, so it cannot be combined with several plans in
+    /// [`bundle_pages`].
     pub metadata: Option<CompilerOutput>,
+    /// Pre-compiled metadata for individual `.zen` modules, keyed by module
+    /// path (compared like [`ComponentDef::path`]). In strict mode each
+    /// loaded module with an entry, imported components included, is
+    /// validated against it as it is compiled.
+    pub module_metadata: Option<HashMap<String, CompilerOutput>>,
     /// Strict mode (default: true). Invariant violations abort the build.
     pub strict: bool,
     /// Whether to write output files to disk.
     pub write_to_disk: bool,
-    /// Explicitly enable/disable minification (overrides mode default).
-    pub minify: Option<bool>,
+    /// Minification mode (overrides the mode default: `Full` in `Prod`,
+    /// `Off` otherwise). Every page reports the mode that was applied in a
+    /// [`codes::MINIFY_MODE`] diagnostic.
+    pub minify: Option<MinifyMode>,
+    /// Emit the `__zenith_page` default export (default: true). When false,
+    /// only the named contract exports are emitted.
+    pub default_export: bool,
+    /// Explicitly enable/disable sourcemaps (overrides mode default: Dev only).
+    pub sourcemap: Option<bool>,
+    /// Embed the normalized `.zen` source as `sourcesContent` in the
+    /// sourcemap (default: false). Lets devtools show sources that are not
+    /// served, at the cost of a larger map.
+    pub sourcemap_sources_content: bool,
+    /// Parse the emitted entry JS and fail on syntax errors (default: false).
+    /// Costs a full parse per page; intended for CI.
+    pub validate_js: bool,
+    /// Keep Rolldown's `//#region` module comments in the entry JS (default:
+    /// false), relabelled with project-relative paths so the output stays
+    /// hash-stable across machines. Shows which module produced which code.
+    pub preserve_region_comments: bool,
+    /// Trim each expression and collapse its internal whitespace before
+    /// emission (default: false), so whitespace-only edits keep entry hashes
+    /// stable. Off, expressions are emitted exactly as the compiler reports
+    /// them. `metadata` expressions are compared in the same form. See
+    /// [`utils::normalize_expression_whitespace`].
+    pub normalize_expression_whitespace: bool,
+    /// Project root page ids are derived relative to (see
+    /// [`utils::canonicalize_page_id`]). Set it so asset names, scope ids
+    /// and hashes do not depend on where the project is checked out; unset,
+    /// page paths are used as given.
+    pub project_root: Option<PathBuf>,
+    /// Optional `.zen` source lookup consulted before the filesystem.
+    /// When set, the page path need not exist on disk.
+    pub content_resolver: Option<ContentResolver>,
+    /// Compile cache shared across builds. A long-lived process passes the
+    /// same cache to every build so unchanged `.zen` sources are not
+    /// recompiled.
+    pub compile_cache: Option<CompileCache>,
+    /// CSS cache shared across builds, like `compile_cache`. Unset, each
+    /// build collects CSS into a fresh cache.
+    pub css_cache: Option<Arc<CssCache>>,
+    /// Contract version emitted as `__zenith_contract` (default: `"v0"`).
+    /// Unknown versions fail the build, so a runtime never receives a
+    /// payload shape it does not understand.
+    pub contract_version: String,
+    /// Runtime module specifier (e.g. `/assets/runtime.js`). When set, each
+    /// entry also wires the page's binding tables into `hydrate({...})`,
+    /// imported from this specifier, which is left external. Unset, entries
+    /// are inert contract modules.
+    pub runtime_import: Option<String>,
+    /// Move the binding tables (expressions, markers, events, signals,
+    /// state keys) out of the entry into a JSON sidecar the entry fetches
+    /// before hydrating (default: false). Keeps critical JS small and lets
+    /// the tables cache separately. The sidecar is returned as
+    /// [`BundleResult::bindings`] and written beside the entry as
+    /// `{page_id}.bindings.{hash}.json`. Requires `runtime_import` and an
+    /// `SSG` or `write_to_disk` build; not available with `inline_assets`.
+    pub externalize_bindings: bool,
+    /// Bare import specifiers left as imports rather than bundled (e.g.
+    /// `["gsap", "@motion/*"]`). A plain name also covers its subpaths;
+    /// `*` matches any run of characters. Relative and `.zen` imports are
+    /// always bundled.
+    pub external: Vec<String>,
+    /// URLs the browser loads external specifiers from, e.g.
+    /// `gsap` → `https://cdn.example.com/gsap.js`. Written page documents
+    /// get a `<script type="importmap">` with these entries ahead of their
+    /// module scripts. Every specifier must be covered by `external` (or be
+    /// `runtime_import`).
+    pub import_map: HashMap<String, String>,
+    /// Named chunks and the modules forced into them, e.g.
+    /// `("runtime-anim", ["gsap", "@motion/*"])`. Each pattern names a
+    /// package under `node_modules`; `*` matches within one segment of the
+    /// name. A module matching several chunks goes to the first one listed.
+    /// Empty (default) leaves chunking to Rolldown.
+    pub manual_chunks: Vec<(String, Vec<String>)>,
+    /// Diagnostic codes that fail the build when emitted, at any level.
+    pub fail_on_codes: Vec<String>,
+    /// Rolldown warning codes escalated to [`BundleError::RolldownWarning`]
+    /// in strict mode (default: [`codes::UNRESOLVED_IMPORT`]). Other
+    /// Rolldown warnings are reported as `Warning` diagnostics.
+    pub strict_warnings: Vec<String>,
+    /// Optional HTML shell (e.g. the project's `index.html`) for page
+    /// documents. Used by `SSG` builds and `write_to_disk` builds; its
+    /// `<!-- zenith:page -->` marker is replaced by the page markup. `SSG`
+    /// falls back to a generated document when unset.
+    pub html_template: Option<PathBuf>,
+    /// Write `write_to_disk` assets as `{page_id}.{hash}.js` (and `.css`,
+    /// `.js.map`), `hash` being the content hash of the file (default:
+    /// false). `SSG` assets are always hashed. See [`BundleResult::assets`].
+    pub hashed_filenames: bool,
+    /// Write a `build-manifest.json` describing the written outputs into
+    /// `out_dir` (default: false). Only applies to builds that write to disk
+    /// (`write_to_disk`, or `SSG`).
+    pub emit_manifest: bool,
+    /// Run the full compile and graph pass but write nothing (default:
+    /// false): `write_to_disk`, `SSG` output and the manifest are skipped,
+    /// and each result carries [`BundleResult::stats`]. Meant for cheap
+    /// size-budget checks in CI.
+    pub analyze: bool,
+    /// Attach the page's full [`CompilerOutput`] to the result as
+    /// [`BundleResult::compiled`] (default: false), for tooling that
+    /// inspects binding metadata.
+    pub include_compiled: bool,
+    /// Write each page as one self-contained HTML document (default: false):
+    /// the entry JS is inlined as a `<script type="module">` and the CSS as a
+    /// `<style>` block instead of being written as separate files. Applies to
+    /// `SSG` and `write_to_disk` builds; the latter then always write a
+    /// document (from `html_template` if set). No sourcemap file is written,
+    /// and pages whose entry imports chunks (shared, dynamic or manual) fail
+    /// with [`BundleError::ValidationError`], since those are not inlined.
+    pub inline_assets: bool,
+    /// Maximum `.zen` modules read and compiled at once while the graph is
+    /// built, and pages [`bundle_pages`] finalizes at once afterwards
+    /// (region stripping, JS validation, budget checks and writes). Bounds
+    /// open files and memory on large sites. Defaults to the number of
+    /// available CPUs; `Some(0)` counts as 1.
+    pub concurrency: Option<usize>,
+    /// Fail the page with [`BundleError::BudgetExceeded`] when its entry JS,
+    /// as shipped, is larger than this many bytes. Checked before anything
+    /// is written.
+    pub max_entry_bytes: Option<usize>,
+    /// Like `max_entry_bytes`, for the page's CSS after pruning.
+    pub max_css_bytes: Option<usize>,
 }
 
 impl Default for BundleOptions {
@@ -108,9 +310,37 @@ impl Default for BundleOptions {
         Self {
             components: None,
             metadata: None,
+            module_metadata: None,
             strict: true,
             write_to_disk: false,
             minify: None,
+            default_export: true,
+            sourcemap: None,
+            sourcemap_sources_content: false,
+            validate_js: false,
+            preserve_region_comments: false,
+            normalize_expression_whitespace: false,
+            project_root: None,
+            content_resolver: None,
+            compile_cache: None,
+            css_cache: None,
+            contract_version: utils::DEFAULT_CONTRACT_VERSION.to_string(),
+            runtime_import: None,
+            externalize_bindings: false,
+            external: Vec::new(),
+            import_map: HashMap::new(),
+            manual_chunks: Vec::new(),
+            fail_on_codes: Vec::new(),
+            strict_warnings: vec![codes::UNRESOLVED_IMPORT.to_string()],
+            html_template: None,
+            hashed_filenames: false,
+            emit_manifest: false,
+            analyze: false,
+            include_compiled: false,
+            inline_assets: false,
+            concurrency: None,
+            max_entry_bytes: None,
+            max_css_bytes: None,
         }
     }
 }
@@ -123,16 +353,96 @@ impl Default for BundleOptions {
 /// CLI and dev server consume this as-is — no post-concat or mutation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BundleResult {
+    /// The `page_path` of the plan this result was built from.
+    pub page_path: String,
     /// Final JS (entry chunk as a string).
     pub entry_js: String,
-    /// Virtual collected CSS (if any).
+    /// Sourcemap JSON for `entry_js`: set for Dev builds, or whenever
+    /// `BundleOptions.sourcemap` enables it. Its sources name the `.zen`
+    /// files, and identical input yields byte-identical JSON.
+    pub source_map: Option<String>,
+    /// Virtual collected CSS (if any). Pruned against the page's class
+    /// names and minified in `Prod` / `SSG` builds.
     pub css: Option<String>,
+    /// The page's binding tables as JSON, set with
+    /// `BundleOptions.externalize_bindings`. The entry fetches them from
+    /// [`utils::bindings_filename`] next to itself.
+    pub bindings: Option<String>,
     /// Expression table — must exactly match metadata if provided.
     pub expressions: Vec<String>,
+    /// Chunk filenames the entry statically imports, sorted.
+    /// Dynamically-imported chunks are never included. Written HTML
+    /// documents get a `<link rel="modulepreload">` for each.
+    pub preload_chunks: Vec<String>,
+    /// Chunks produced for `BundleOptions.manual_chunks` in this build:
+    /// chunk name → filename, sorted by name. Names whose patterns matched
+    /// no module are absent.
+    pub manual_chunks: Vec<(String, String)>,
+    /// The HTML document written for the page (`SSG` builds, and
+    /// `write_to_disk` builds with an `html_template` or `inline_assets`).
+    pub html: Option<String>,
+    /// Files written for the page: logical name (`{page_id}.js`,
+    /// `{page_id}.css`, ...) → path relative to `out_dir`. Empty when
+    /// nothing was written.
+    pub assets: Vec<(String, String)>,
+    /// Size figures for the page, set in `analyze` builds.
+    pub stats: Option<BundleStats>,
+    /// The compiler output the page was built from (signals, marker, event
+    /// and expression bindings, component instances), set with
+    /// `BundleOptions.include_compiled`. `None` if none was recorded.
+    pub compiled: Option<CompilerOutput>,
     /// Diagnostics collected during the build.
     pub diagnostics: Vec<Diagnostic>,
 }
 
+/// Size figures reported by an `analyze` build.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleStats {
+    /// Length of the expression table.
+    pub expression_count: usize,
+    /// Bytes of final entry JS.
+    pub entry_js_bytes: usize,
+    /// Bytes of final (pruned, minified) CSS; 0 without styles.
+    pub css_bytes: usize,
+    /// Chunks loaded up front: the entry plus its `preload_chunks`.
+    pub chunk_count: usize,
+    /// Component instances in the compiled page.
+    pub component_instance_count: usize,
+}
+
+// ---------------------------------------------------------------------------
+// Build Manifest
+// ---------------------------------------------------------------------------
+
+/// Contents of `build-manifest.json`, written to `out_dir` when
+/// `BundleOptions.emit_manifest` is set. All lists are sorted so the file
+/// diffs cleanly between builds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildManifest {
+    /// Pages written to this `out_dir`, sorted by page id.
+    pub pages: Vec<ManifestPage>,
+    /// Shared chunk files written beside the entries (relative to
+    /// `out_dir`), sorted. Entry files are listed under `pages`.
+    pub chunks: Vec<String>,
+}
+
+/// One page's entry in the [`BuildManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestPage {
+    /// Canonical page id.
+    pub page_id: String,
+    /// Entry JS file, relative to `out_dir` — or, with
+    /// `BundleOptions.inline_assets`, the HTML document it is inlined into.
+    pub entry: String,
+    /// Content hash of the emitted entry JS.
+    pub hash: String,
+    /// Page CSS file, relative to `out_dir` (if the page has CSS and it was
+    /// not inlined).
+    pub css: Option<String>,
+    /// Number of expressions in the page's expression table.
+    pub expression_count: usize,
+}
+
 // ---------------------------------------------------------------------------
 // BundleError
 // ---------------------------------------------------------------------------
@@ -146,13 +456,22 @@ pub enum BundleError {
     #[error("Expression mismatch: expected {expected} expressions, got {got}")]
     ExpressionMismatch { expected: usize, got: usize },
 
-    #[error("Expression content mismatch at index {index}: expected `{expected}`, got `{got}`")]
+    #[error(
+        "Expression content mismatch at index {index}: expected `{expected}`, got `{got}`{}",
+        near_suffix(.near)
+    )]
     ExpressionContentMismatch {
         index: usize,
         expected: String,
         got: String,
+        /// The page element carrying the expression's placeholder (see
+        /// [`utils::expression_context`]), when it could be found.
+        near: Option<String>,
     },
 
+    #[error("Marker mismatch: expected {expected} marker bindings, got {got}")]
+    MarkerMismatch { expected: usize, got: usize },
+
     #[error("Missing data-zx-e placeholder for index {index}")]
     MissingPlaceholder { index: usize },
 
@@ -164,6 +483,56 @@ pub enum BundleError {
 
     #[error("Validation failed: {0}")]
     ValidationError(String),
+
+    #[error("CSS error: {0}")]
+    CssError(String),
+
+    #[error("Diagnostic `{code}` is configured to fail the build: {message}")]
+    FailOnCode { code: String, message: String },
+
+    #[error("Rolldown warning `{code}` is fatal in strict mode: {message}")]
+    RolldownWarning { code: String, message: String },
+
+    #[error("Build cancelled")]
+    Cancelled,
+
+    /// The shipped (minified, pruned) size of an asset is over the limit set
+    /// by `BundleOptions::max_entry_bytes` / `max_css_bytes`.
+    #[error("{kind:?} size budget exceeded: {actual} bytes (limit {limit})")]
+    BudgetExceeded {
+        kind: utils::AssetKind,
+        limit: usize,
+        actual: usize,
+    },
+}
+
+impl BundleError {
+    /// Stable code for the error's variant, e.g. `E_EXPR_COUNT`. Unlike the
+    /// message, it does not change when the wording does, so tooling can
+    /// branch on it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            BundleError::CompilerError(_) => "E_COMPILER",
+            BundleError::ExpressionMismatch { .. } => "E_EXPR_COUNT",
+            BundleError::ExpressionContentMismatch { .. } => "E_EXPR_CONTENT",
+            BundleError::MarkerMismatch { .. } => "E_MARKER_COUNT",
+            BundleError::MissingPlaceholder { .. } => "E_MISSING_PLACEHOLDER",
+            BundleError::BuildError(_) => "E_BUILD",
+            BundleError::IoError(_) => "E_IO",
+            BundleError::ValidationError(_) => "E_VALIDATION",
+            BundleError::CssError(_) => "E_CSS",
+            BundleError::FailOnCode { .. } => "E_FAIL_ON_CODE",
+            BundleError::RolldownWarning { .. } => "E_ROLLDOWN_WARNING",
+            BundleError::Cancelled => "E_CANCELLED",
+            BundleError::BudgetExceeded { .. } => "E_BUDGET",
+        }
+    }
+}
+
+fn near_suffix(near: &Option<String>) -> String {
+    near.as_ref()
+        .map(|element| format!(" (near {})", element))
+        .unwrap_or_default()
 }
 
 // ---------------------------------------------------------------------------
@@ -192,3 +561,54 @@ pub async fn bundle_page(
 ) -> Result<BundleResult, BundleError> {
     bundle::execute_bundle(plan, opts).await
 }
+
+/// Bundle a single page, abandoning the build once `cancel` is tripped.
+///
+/// Dev servers pass a fresh token per rebuild and cancel the previous one
+/// when a newer change arrives. The token is checked before and after
+/// Rolldown's `generate()`; a tripped token yields
+/// [`BundleError::Cancelled`] and nothing is written. `None` behaves
+/// exactly like [`bundle_page`].
+pub async fn bundle_page_cancellable(
+    plan: BundlePlan,
+    opts: BundleOptions,
+    cancel: Option<CancellationToken>,
+) -> Result<BundleResult, BundleError> {
+    match cancel {
+        Some(token) => bundle::execute_bundle_cancellable(plan, opts, &token).await,
+        None => bundle::execute_bundle(plan, opts).await,
+    }
+}
+
+/// Bundle a single page from synchronous code.
+///
+/// Builds a current-thread Tokio runtime, drives [`bundle_page`] to
+/// completion, and tears the runtime down. This is a thin driver over the
+/// same emission engine — output is byte-identical to the async path.
+///
+/// Must not be called from inside an existing Tokio runtime.
+pub fn bundle_page_blocking(
+    plan: BundlePlan,
+    opts: BundleOptions,
+) -> Result<BundleResult, BundleError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(bundle_page(plan, opts))
+}
+
+/// Bundle many pages in one Rolldown graph.
+///
+/// All plans become entries of a single build with one ZenithLoader, so
+/// shared modules are compiled once and the compiled-output map and CSS
+/// cache are reused across pages. Plans must share one [`BuildMode`].
+///
+/// Results are returned in the order of `plans`, each tagged with its
+/// `page_path`; output is identical for every `opts.concurrency` setting.
+/// The first failing page aborts the whole build.
+pub async fn bundle_pages(
+    plans: Vec<BundlePlan>,
+    opts: BundleOptions,
+) -> Result<Vec<BundleResult>, BundleError> {
+    bundle::execute_bundle_pages(plans, opts).await
+}
@@ -1,198 +1,408 @@
 use std::env;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs;
 use std::io::{self, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::OnceLock;
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use zenith_bundler::CompilerOutput;
+use zenith_bundler::cli_input::{
+    derive_binding_tables, unsupported_ir_version, validate_payload, BundlerInput,
+    CompilerComponentScript, CompilerExpressionBinding, CompilerIr, CompilerStateBinding,
+    EventBinding, ExpressionKind, MarkerBinding, MarkerKind, SUPPORTED_IR_VERSIONS,
+};
+use zenith_bundler::html::{
+    body_close_offset, ensure_document_html, insert_before_body_close, wrap_document_html,
+};
+use zenith_bundler::router::{RouterManifest, RouterRouteEntry, ROUTER_MANIFEST_FILE};
+use zenith_bundler::utils::{
+    asset_filename, check_route_collisions, content_hash, route_to_output_path, AssetKind,
+    CONTENT_HASH_LEN,
+};
+use zenith_bundler::{codes, BundleError, CompilerOutput, Diagnostic, DiagnosticLevel};
 
-#[derive(Debug, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct BundlerInput {
+fn main() {
+    // Argument errors are reported before the output format is known
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("[zenith-bundler] {}", err);
+            process::exit(1);
+        }
+    };
+    let format = args.format;
+    let report = args.report.clone();
+
+    match run(args).and_then(|written| {
+        if let Some(ref report) = report {
+            write_report(report, &written)?;
+        }
+        Ok(written)
+    }) {
+        Ok(written) => {
+            if format == OutputFormat::Json {
+                for file in written {
+                    print_diagnostic(&Diagnostic {
+                        level: DiagnosticLevel::Info,
+                        message: format!("Written to {}", file.path.display()),
+                        context: Some(file.route),
+                        code: None,
+                    });
+                }
+            }
+        }
+        Err(err) => {
+            match format {
+                OutputFormat::Human => eprintln!("[zenith-bundler] {}", err),
+                OutputFormat::Json => print_diagnostic(&Diagnostic {
+                    level: DiagnosticLevel::Error,
+                    message: err.message,
+                    context: None,
+                    code: err.code.map(Into::into),
+                }),
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// A failed run: the message, and the [`BundleError::code`] of the library
+/// error behind it, if there was one.
+#[derive(Debug)]
+struct CliError {
+    message: String,
+    code: Option<&'static str>,
+}
+
+impl CliError {
+    /// Prefix the message with `context`, keeping the code.
+    fn context(self, context: impl fmt::Display) -> Self {
+        Self {
+            message: format!("{context}: {}", self.message),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            code: None,
+        }
+    }
+}
+
+impl From<BundleError> for CliError {
+    fn from(err: BundleError) -> Self {
+        Self {
+            message: err.to_string(),
+            code: Some(err.code()),
+        }
+    }
+}
+
+/// Report a non-fatal problem with a route: on stderr for `--format human`,
+/// as a `warning` diagnostic for `--format json`.
+fn print_warning(format: OutputFormat, route: &str, code: &'static str, message: String) {
+    match format {
+        OutputFormat::Human => eprintln!("[zenith-bundler] warning: {route}: {message}"),
+        OutputFormat::Json => print_diagnostic(&Diagnostic {
+            level: DiagnosticLevel::Warning,
+            message,
+            context: Some(route.to_string()),
+            code: Some(code.into()),
+        }),
+    }
+}
+
+/// A [`Diagnostic`] as printed by `--format json`: the same fields, with
+/// the level in lowercase (`error`, `warning`, `info`).
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    level: &'static str,
+    message: &'a str,
+    context: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'a str>,
+}
+
+impl<'a> From<&'a Diagnostic> for JsonDiagnostic<'a> {
+    fn from(diagnostic: &'a Diagnostic) -> Self {
+        Self {
+            level: match diagnostic.level {
+                DiagnosticLevel::Error => "error",
+                DiagnosticLevel::Warning => "warning",
+                DiagnosticLevel::Info => "info",
+            },
+            message: &diagnostic.message,
+            context: diagnostic.context.as_deref(),
+            code: diagnostic.code.as_deref(),
+        }
+    }
+}
+
+/// Print one diagnostic as a JSON line on stdout (`--format json`).
+fn print_diagnostic(diagnostic: &Diagnostic) {
+    match serde_json::to_string(&JsonDiagnostic::from(diagnostic)) {
+        Ok(line) => println!("{line}"),
+        Err(e) => eprintln!("[zenith-bundler] failed to serialize diagnostic: {e}"),
+    }
+}
+
+/// What a written file is, as listed in the `--report` summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum WrittenKind {
+    Html,
+    Entry,
+    Runtime,
+    Component,
+    Router,
+    RouterManifest,
+}
+
+/// A file written for a route.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct WrittenFile {
     route: String,
-    file: String,
-    ir: CompilerIr,
-    #[serde(default)]
-    router: bool,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct CompilerIr {
-    ir_version: u32,
-    html: String,
-    expressions: Vec<String>,
-    #[serde(default)]
-    hoisted: CompilerHoisted,
-    #[serde(default)]
-    components_scripts: BTreeMap<String, CompilerComponentScript>,
-    #[serde(default)]
-    component_instances: Vec<CompilerComponentInstance>,
-    #[serde(default)]
-    signals: Vec<CompilerSignal>,
-    #[serde(default)]
-    expression_bindings: Vec<CompilerExpressionBinding>,
-    #[serde(default)]
-    marker_bindings: Vec<MarkerBinding>,
-    #[serde(default)]
-    event_bindings: Vec<EventBinding>,
-}
-
-#[derive(Debug, Clone, Deserialize, Default)]
-#[serde(deny_unknown_fields)]
-struct CompilerHoisted {
-    #[serde(default)]
-    imports: Vec<String>,
-    #[serde(default)]
-    declarations: Vec<String>,
-    #[serde(default)]
-    functions: Vec<String>,
-    #[serde(default)]
-    signals: Vec<String>,
-    #[serde(default)]
-    state: Vec<CompilerStateBinding>,
-    #[serde(default)]
-    code: Vec<String>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct CompilerStateBinding {
-    key: String,
-    value: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct CompilerComponentScript {
-    hoist_id: String,
-    factory: String,
-    #[serde(default)]
-    imports: Vec<String>,
-    code: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct CompilerComponentInstance {
-    instance: String,
-    hoist_id: String,
-    selector: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct CompilerSignal {
-    id: usize,
-    kind: String,
-    state_index: usize,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct CompilerExpressionBinding {
-    marker_index: usize,
-    #[serde(default)]
-    signal_index: Option<usize>,
-    #[serde(default)]
-    state_index: Option<usize>,
-    #[serde(default)]
-    component_instance: Option<String>,
-    #[serde(default)]
-    component_binding: Option<String>,
-    #[serde(default)]
-    literal: Option<String>,
-}
-
-#[derive(Debug, Default, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct RouterManifest {
-    routes: Vec<RouterRouteEntry>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct RouterRouteEntry {
-    path: String,
-    output: String,
-    html: String,
-    expressions: Vec<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-enum MarkerKind {
-    Text,
-    Attr,
-    Event,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MarkerBinding {
-    index: usize,
-    kind: MarkerKind,
-    selector: String,
-    #[serde(default)]
+    kind: WrittenKind,
+    path: PathBuf,
+    /// Content hash of a content-hashed asset (the one in its filename).
     #[serde(skip_serializing_if = "Option::is_none")]
-    attr: Option<String>,
+    hash: Option<String>,
+}
+
+impl WrittenFile {
+    fn new(route: &str, kind: WrittenKind, path: PathBuf) -> Self {
+        Self {
+            route: route.to_string(),
+            kind,
+            path,
+            hash: None,
+        }
+    }
+
+    /// A content-hashed asset written with `content`.
+    fn hashed(route: &str, kind: WrittenKind, path: PathBuf, content: &str) -> Self {
+        Self {
+            hash: Some(content_hash(content)),
+            ..Self::new(route, kind, path)
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct EventBinding {
-    index: usize,
-    event: String,
-    selector: String,
+/// Write the `--report` summary: every file of the run, in write order.
+fn write_report(path: &Path, written: &[WrittenFile]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&serde_json::json!({ "files": written }))
+        .map_err(|e| format!("failed to serialize report: {e}"))?;
+    fs::write(path, format!("{json}\n"))
+        .map_err(|e| format!("failed to write report '{}': {e}", path.display()))
 }
 
-fn main() {
-    if let Err(err) = run() {
-        eprintln!("[zenith-bundler] {}", err);
-        process::exit(1);
+/// Bundle every input on stdin (or in the `--input` file). Returns the
+/// written files.
+fn run(args: CliArgs) -> Result<Vec<WrittenFile>, CliError> {
+    let stdin_payload = read_payload(args.input.as_deref())?;
+
+    // The runtime and component assets are shared by every route, so each
+    // is written once
+    let mut shared = SharedAssets::default();
+    let mut written = Vec::new();
+    if is_batch_payload(&stdin_payload) {
+        let payloads = parse_batch_payload(&stdin_payload)?;
+        // Checked up front so no route's files overwrite another's
+        check_route_collisions(payloads.iter().map(|payload| payload.route.as_str()))?;
+        for (index, payload) in payloads.iter().enumerate() {
+            let files = bundle_payload(&args, payload, &mut shared)
+                .map_err(|e| e.context(format!("input[{index}] (route '{}')", payload.route)))?;
+            written.extend(files);
+        }
+    } else {
+        let payload: BundlerInput = serde_json::from_str(&stdin_payload)
+            .map_err(|e| format!("invalid input JSON: {e}"))?;
+        written.extend(bundle_payload(&args, &payload, &mut shared)?);
     }
+    Ok(written)
 }
 
-fn run() -> Result<(), String> {
-    let out_dir = parse_out_dir()?;
+/// Assets shared by the routes of one run: each is written by the first
+/// route that needs it and referenced by the rest.
+#[derive(Debug, Default)]
+struct SharedAssets {
+    /// The runtime asset path, once written.
+    runtime_rel: Option<String>,
+    /// Component asset paths already written. A path carries its module's
+    /// content hash, so the same component on another route maps to it.
+    components: BTreeSet<String>,
+    /// Whether the router manifest has been reported as written.
+    router_manifest: bool,
+}
 
-    let mut stdin_payload = String::new();
-    io::stdin()
-        .read_to_string(&mut stdin_payload)
-        .map_err(|e| format!("failed to read stdin: {e}"))?;
+/// Read the raw input JSON from `input`, or from stdin when it is `None`.
+fn read_payload(input: Option<&Path>) -> Result<String, String> {
+    let Some(path) = input else {
+        let mut payload = String::new();
+        io::stdin()
+            .read_to_string(&mut payload)
+            .map_err(|e| format!("failed to read stdin: {e}"))?;
+        if payload.trim().is_empty() {
+            return Err("stdin payload is empty".into());
+        }
+        return Ok(payload);
+    };
 
-    if stdin_payload.trim().is_empty() {
-        return Err("stdin payload is empty".into());
+    let payload = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read --input file '{}': {e}", path.display()))?;
+    if payload.trim().is_empty() {
+        return Err(format!("--input file '{}' is empty", path.display()));
     }
+    Ok(payload)
+}
 
-    let payload: BundlerInput =
-        serde_json::from_str(&stdin_payload).map_err(|e| format!("invalid input JSON: {e}"))?;
-    validate_payload(&payload)?;
+/// A top-level JSON array on stdin is a batch of inputs; an object is one.
+fn is_batch_payload(raw: &str) -> bool {
+    raw.trim_start().starts_with('[')
+}
 
-    let mut html = ensure_document_html(&payload.ir.html);
+/// Parse a batch payload, naming the element that fails to deserialize.
+fn parse_batch_payload(raw: &str) -> Result<Vec<BundlerInput>, String> {
+    let items: Vec<serde_json::Value> =
+        serde_json::from_str(raw).map_err(|e| format!("invalid input JSON: {e}"))?;
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            serde_json::from_value(item)
+                .map_err(|e| format!("invalid input JSON in input[{index}]: {e}"))
+        })
+        .collect()
+}
+
+/// Bundle one route with the codegen for its IR version.
+fn bundle_payload(
+    args: &CliArgs,
+    payload: &BundlerInput,
+    shared: &mut SharedAssets,
+) -> Result<Vec<WrittenFile>, CliError> {
+    match payload.ir.ir_version {
+        1 => bundle_route(args, payload, shared),
+        version => Err(unsupported_ir_version(version).into()),
+    }
+}
+
+/// Bundle one v1 route into `args.out_dir`, returning the files written.
+///
+/// `shared` tracks the assets already written by earlier routes of a batch.
+fn bundle_route(
+    args: &CliArgs,
+    payload: &BundlerInput,
+    shared: &mut SharedAssets,
+) -> Result<Vec<WrittenFile>, CliError> {
+    let CliArgs {
+        ref out_dir,
+        input: _,
+        ref base_path,
+        ref runtime_specifier,
+        validate_js,
+        emit_state_keys,
+        columnar_bindings,
+        strict,
+        ref external_origins,
+        format,
+        report: _,
+        legacy,
+        document_wrap,
+    } = *args;
+    let mut written = Vec::new();
+
+    validate_payload(payload)?;
+
+    let mut html = match document_wrap {
+        DocumentWrap::Auto => ensure_document_html(&payload.ir.html),
+        DocumentWrap::Never => payload.ir.html.clone(),
+        DocumentWrap::Always => wrap_document_html(&payload.ir.html),
+    };
+    match zenith_bundler::html::inject_head_fragments(&html, &payload.head) {
+        Ok(with_head) => html = with_head,
+        Err(err) => print_warning(format, &payload.route, codes::MISSING_HEAD, err),
+    }
+    if !external_origins.is_empty() {
+        html = zenith_bundler::html::inject_resource_hints(&html, external_origins)?;
+    }
 
-    fs::create_dir_all(&out_dir)
+    fs::create_dir_all(out_dir)
         .map_err(|e| format!("failed to create output dir '{}': {e}", out_dir.display()))?;
 
+    let (markers, events) = if payload.ir.marker_bindings.is_empty() {
+        derive_binding_tables(&payload.ir.html, payload.ir.expressions.len())?
+    } else {
+        (
+            payload.ir.marker_bindings.clone(),
+            payload.ir.event_bindings.clone(),
+        )
+    };
+    if let Some(ref schema) = payload.expression_schema {
+        validate_expression_schema(schema, &payload.ir, &markers)?;
+    }
+
     let runtime_required =
         !payload.ir.expressions.is_empty() || !payload.ir.component_instances.is_empty();
+    // An unwrapped fragment has no `</body>` by design
+    if (runtime_required || payload.router) && document_wrap != DocumentWrap::Never {
+        if let Err(err) = body_close_offset(&html) {
+            print_warning(
+                format,
+                &payload.route,
+                codes::MALFORMED_DOCUMENT,
+                format!("{err}; scripts are appended at the end of the document"),
+            );
+        }
+    }
     if runtime_required {
-        let (markers, events) = if payload.ir.marker_bindings.is_empty() {
-            derive_binding_tables(&payload.ir)?
-        } else {
-            (
-                payload.ir.marker_bindings.clone(),
-                payload.ir.event_bindings.clone(),
-            )
+        // An external runtime is loaded through the entry's import, so there
+        // is no asset to write or script to inject
+        let (runtime_import_spec, runtime_script_src) = match runtime_specifier {
+            Some(specifier) => (specifier.clone(), None),
+            None => {
+                let runtime_rel: &str = match shared.runtime_rel {
+                    Some(ref rel) => rel,
+                    None => {
+                        let (rel, wrote) = ensure_runtime_asset(out_dir)?;
+                        if wrote {
+                            written.push(WrittenFile::hashed(
+                                &payload.route,
+                                WrittenKind::Runtime,
+                                out_dir.join(&rel),
+                                &runtime_asset().js,
+                            ));
+                        }
+                        shared.runtime_rel.insert(rel)
+                    }
+                };
+                (
+                    runtime_import_specifier(runtime_rel)?,
+                    Some(format!("{base_path}{runtime_rel}")),
+                )
+            }
         };
-        let runtime_rel = ensure_runtime_asset(&out_dir)?;
-        let runtime_script_src = format!("/{runtime_rel}");
-        let runtime_import_spec = runtime_import_specifier(&runtime_rel)?;
         let component_assets = emit_component_assets(
-            &out_dir,
+            out_dir,
+            &payload.route,
             &payload.ir.components_scripts,
             &runtime_import_spec,
+            validate_js,
+            &mut shared.components,
+            &mut written,
         )?;
         let js = generate_entry_js(
             &payload.ir,
@@ -200,39 +410,64 @@ fn run() -> Result<(), String> {
             &markers,
             &events,
             &component_assets,
+            EntryJsOptions {
+                emit_state_keys,
+                columnar_bindings,
+            },
         )?;
-        let js_hash = stable_hash_8(&js);
-        let js_rel = format!("assets/{js_hash}.js");
+        if validate_js {
+            zenith_bundler::utils::validate_js_syntax(
+                &js,
+                &format!("entry for {}", payload.route),
+            )?;
+        }
+        let js_rel = asset_filename(&route_asset_token(&payload.route), &js, AssetKind::Entry);
         let js_path = out_dir.join(&js_rel);
         if let Some(parent) = js_path.parent() {
             fs::create_dir_all(parent)
                 .map_err(|e| format!("failed to create asset dir '{}': {e}", parent.display()))?;
         }
-        fs::write(&js_path, js)
+        fs::write(&js_path, &js)
             .map_err(|e| format!("failed to write asset '{}': {e}", js_path.display()))?;
+        written.push(WrittenFile::hashed(
+            &payload.route,
+            WrittenKind::Entry,
+            js_path,
+            &js,
+        ));
 
-        html = inject_script_once(&html, &runtime_script_src, "data-zx-runtime");
-        html = inject_script_once(&html, &format!("/{js_rel}"), "data-zx-page");
+        if let Some(ref runtime_script_src) = runtime_script_src {
+            html = inject_script_once(&html, runtime_script_src, "data-zx-runtime");
+        }
+        html = inject_script_once(&html, &format!("{base_path}{js_rel}"), "data-zx-page");
+        if legacy {
+            // Placeholder: the fallback reuses the module entry until a
+            // separate ES2015 chunk is bundled
+            html = inject_nomodule_script_once(&html, &format!("{base_path}{js_rel}"));
+        }
     }
 
     if payload.router {
-        let output_path = route_to_output_path(&payload.route)
-            .to_string_lossy()
-            .replace('\\', "/");
-
         upsert_router_manifest(
-            &out_dir,
-            RouterRouteEntry {
-                path: payload.route.clone(),
-                output: output_path,
-                html: payload.ir.html.clone(),
-                expressions: payload.ir.expressions.clone(),
-            },
+            out_dir,
+            RouterRouteEntry::new(
+                &payload.route,
+                payload.ir.html.clone(),
+                payload.ir.expressions.clone(),
+            )?,
         )?;
+        // Rewritten by every router route; reported once per run
+        if !shared.router_manifest {
+            shared.router_manifest = true;
+            written.push(WrittenFile::new(
+                &payload.route,
+                WrittenKind::RouterManifest,
+                out_dir.join(ROUTER_MANIFEST_FILE),
+            ));
+        }
 
-        let router_js = generate_router_runtime_js();
-        let router_hash = stable_hash_8(&router_js);
-        let router_rel = format!("assets/router.{router_hash}.js");
+        let router_js = generate_router_runtime_js(base_path);
+        let router_rel = asset_filename("", &router_js, AssetKind::Router);
         let router_path = out_dir.join(&router_rel);
         if let Some(parent) = router_path.parent() {
             fs::create_dir_all(parent).map_err(|e| {
@@ -242,14 +477,24 @@ fn run() -> Result<(), String> {
                 )
             })?;
         }
-        fs::write(&router_path, router_js).map_err(|e| {
+        fs::write(&router_path, &router_js).map_err(|e| {
             format!(
                 "failed to write router asset '{}': {e}",
                 router_path.display()
             )
         })?;
+        written.push(WrittenFile::hashed(
+            &payload.route,
+            WrittenKind::Router,
+            router_path,
+            &router_js,
+        ));
 
-        html = inject_script_once(&html, &format!("/{router_rel}"), "data-zx-router");
+        html = inject_script_once(&html, &format!("{base_path}{router_rel}"), "data-zx-router");
+    }
+
+    if strict {
+        validate_injected_scripts(&html)?;
     }
 
     let html_rel = route_to_output_path(&payload.route);
@@ -260,13 +505,121 @@ fn run() -> Result<(), String> {
     }
     fs::write(&html_path, html)
         .map_err(|e| format!("failed to write html '{}': {e}", html_path.display()))?;
+    written.push(WrittenFile::new(&payload.route, WrittenKind::Html, html_path));
 
-    Ok(())
+    Ok(written)
+}
+
+struct CliArgs {
+    out_dir: PathBuf,
+    /// Read the input JSON from this file instead of stdin (`--input`).
+    input: Option<PathBuf>,
+    /// URL prefix for injected asset `src`s, always `/`-delimited (`--base`).
+    base_path: String,
+    /// Import the runtime from this specifier (e.g. `@zenith/runtime`)
+    /// instead of writing a runtime asset (`--runtime-specifier`).
+    runtime_specifier: Option<String>,
+    /// Parse every generated module before writing it (`--validate-js`).
+    validate_js: bool,
+    /// Emit the `__zenith_state_keys` debug map (`--emit-state-keys`).
+    emit_state_keys: bool,
+    /// Emit expression bindings as typed-array columns (`--columnar-bindings`).
+    columnar_bindings: bool,
+    /// Fail on output invariant violations, e.g. non-module injected scripts (`--strict`).
+    strict: bool,
+    /// Origins to preconnect to (`--external-origin <origin>`, repeatable).
+    external_origins: Vec<String>,
+    /// How errors and written files are reported (`--format human|json`).
+    format: OutputFormat,
+    /// Write a JSON summary of every file written to this path (`--report`).
+    report: Option<PathBuf>,
+    /// Also emit a `<script nomodule>` fallback for the page entry (`--legacy`).
+    legacy: bool,
+    /// Whether the page HTML is wrapped in a document shell
+    /// (`--no-document-wrap`, `--force-document-wrap`).
+    document_wrap: DocumentWrap,
+}
+
+/// When the page HTML is wrapped in a minimal document shell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DocumentWrap {
+    /// Wrap fragments; HTML that already has an `<html` element is kept.
+    #[default]
+    Auto,
+    /// Write the HTML as-is. Scripts are appended at the end.
+    Never,
+    /// Wrap the HTML even if it has an `<html` element.
+    Always,
+}
+
+/// CLI reporting format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    /// `[zenith-bundler] <message>` on stderr for errors; silent on success.
+    #[default]
+    Human,
+    /// One JSON [`Diagnostic`] per line on stdout: the error on failure,
+    /// or one `info` line per written file on success.
+    Json,
+}
+
+/// Build options loaded from `--config <path>`. Explicit flags win, including
+/// the `--no-*` forms that turn a boolean the config enables back off.
+///
+/// Unlike the IR, unknown keys are tolerated (with a warning) so configs can
+/// be shared across bundler versions.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CliConfig {
+    out_dir: Option<PathBuf>,
+    base_path: Option<String>,
+    runtime_specifier: Option<String>,
+    validate_js: Option<bool>,
+    emit_state_keys: Option<bool>,
+    columnar_bindings: Option<bool>,
+    strict: Option<bool>,
+    external_origins: Vec<String>,
+    format: Option<OutputFormat>,
+    report: Option<PathBuf>,
+    legacy: Option<bool>,
+    document_wrap: Option<DocumentWrap>,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, serde_json::Value>,
+}
+
+fn load_cli_config(path: &str) -> Result<CliConfig, String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config '{path}': {e}"))?;
+    let config: CliConfig =
+        serde_json::from_str(&raw).map_err(|e| format!("invalid config '{path}': {e}"))?;
+    for key in config.unknown.keys() {
+        eprintln!("[zenith-bundler] warning: ignoring unknown config key '{key}' in {path}");
+    }
+    Ok(config)
 }
 
-fn parse_out_dir() -> Result<PathBuf, String> {
+fn parse_args() -> Result<CliArgs, String> {
+    parse_args_from(env::args().skip(1))
+}
+
+fn parse_args_from(mut args: impl Iterator<Item = String>) -> Result<CliArgs, String> {
     let mut out_dir: Option<PathBuf> = None;
-    let mut args = env::args().skip(1);
+    let mut input: Option<PathBuf> = None;
+    let mut base_path: Option<String> = None;
+    let mut runtime_specifier: Option<String> = None;
+    let mut config_path: Option<String> = None;
+    // Booleans stay unset unless a flag names them, so the config decides
+    let mut validate_js: Option<bool> = None;
+    let mut emit_state_keys: Option<bool> = None;
+    let mut columnar_bindings: Option<bool> = None;
+    let mut strict: Option<bool> = None;
+    let mut external_origins = Vec::new();
+    let mut format: Option<OutputFormat> = None;
+    let mut report: Option<PathBuf> = None;
+    let mut legacy: Option<bool> = None;
+    let mut document_wrap: Option<DocumentWrap> = None;
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
@@ -276,330 +629,227 @@ fn parse_out_dir() -> Result<PathBuf, String> {
                     .ok_or_else(|| "missing value for --out-dir".to_string())?;
                 out_dir = Some(PathBuf::from(value));
             }
+            "--input" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --input".to_string())?;
+                input = Some(PathBuf::from(value));
+            }
+            "--base" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --base".to_string())?;
+                base_path = Some(value);
+            }
+            "--runtime-specifier" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --runtime-specifier".to_string())?;
+                runtime_specifier = Some(value);
+            }
+            "--config" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --config".to_string())?;
+                config_path = Some(value);
+            }
+            "--validate-js" => validate_js = Some(true),
+            "--no-validate-js" => validate_js = Some(false),
+            "--emit-state-keys" => emit_state_keys = Some(true),
+            "--no-emit-state-keys" => emit_state_keys = Some(false),
+            "--columnar-bindings" => columnar_bindings = Some(true),
+            "--no-columnar-bindings" => columnar_bindings = Some(false),
+            "--strict" => strict = Some(true),
+            "--no-strict" => strict = Some(false),
+            "--external-origin" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --external-origin".to_string())?;
+                external_origins.push(value);
+            }
+            "--format" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --format".to_string())?;
+                format = Some(match value.as_str() {
+                    "human" => OutputFormat::Human,
+                    "json" => OutputFormat::Json,
+                    _ => return Err(format!("invalid --format '{value}' (expected human or json)")),
+                });
+            }
+            "--report" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "missing value for --report".to_string())?;
+                report = Some(PathBuf::from(value));
+            }
+            "--legacy" => legacy = Some(true),
+            "--no-legacy" => legacy = Some(false),
+            "--no-document-wrap" | "--force-document-wrap" => {
+                let mode = if arg == "--no-document-wrap" {
+                    DocumentWrap::Never
+                } else {
+                    DocumentWrap::Always
+                };
+                if document_wrap.is_some_and(|previous| previous != mode) {
+                    return Err(
+                        "--no-document-wrap and --force-document-wrap are mutually exclusive"
+                            .into(),
+                    );
+                }
+                document_wrap = Some(mode);
+            }
             _ => {
                 return Err(format!(
-                    "unknown argument '{arg}'. usage: zenith-bundler --out-dir <path>"
+                    "unknown argument '{arg}'. usage: zenith-bundler --out-dir <path> [--input <path>] [--config <path>] [--base <path>] [--runtime-specifier <specifier>] [--[no-]validate-js] [--[no-]emit-state-keys] [--[no-]columnar-bindings] [--[no-]strict] [--external-origin <origin>]... [--format human|json] [--report <path>] [--[no-]legacy] [--no-document-wrap|--force-document-wrap]"
                 ));
             }
         }
     }
 
-    out_dir.ok_or_else(|| "required flag missing: --out-dir <path>".to_string())
+    let config = match config_path {
+        Some(ref path) => load_cli_config(path)?,
+        None => CliConfig::default(),
+    };
+    if external_origins.is_empty() {
+        external_origins = config.external_origins;
+    }
+
+    let out_dir = out_dir
+        .or(config.out_dir)
+        .ok_or_else(|| "required flag missing: --out-dir <path>".to_string())?;
+    Ok(CliArgs {
+        out_dir,
+        input,
+        base_path: normalize_base_path(base_path.or(config.base_path).as_deref().unwrap_or("/")),
+        runtime_specifier: runtime_specifier
+            .or(config.runtime_specifier)
+            .map(validate_runtime_specifier)
+            .transpose()?,
+        validate_js: validate_js.or(config.validate_js).unwrap_or(false),
+        emit_state_keys: emit_state_keys.or(config.emit_state_keys).unwrap_or(false),
+        columnar_bindings: columnar_bindings
+            .or(config.columnar_bindings)
+            .unwrap_or(false),
+        strict: strict.or(config.strict).unwrap_or(false),
+        external_origins,
+        format: format.or(config.format).unwrap_or_default(),
+        report: report.or(config.report),
+        legacy: legacy.or(config.legacy).unwrap_or(false),
+        document_wrap: document_wrap.or(config.document_wrap).unwrap_or_default(),
+    })
 }
 
-fn validate_payload(payload: &BundlerInput) -> Result<(), String> {
-    if payload.ir.ir_version != 1 {
-        return Err(format!(
-            "unsupported input.ir.ir_version {} (expected 1)",
-            payload.ir.ir_version
-        ));
-    }
-    if payload.route.trim().is_empty() {
-        return Err("input.route must be a non-empty string".into());
-    }
-    if !payload.route.starts_with('/') {
-        return Err("input.route must start with '/'".into());
-    }
-    if payload.file.trim().is_empty() {
-        return Err("input.file must be a non-empty string".into());
-    }
-    if payload.ir.html.trim().is_empty() {
-        return Err("input.ir.html must be a non-empty string".into());
+/// Reject runtime specifiers that cannot be written verbatim into the
+/// single-quoted `from '...'` clauses of generated modules.
+fn validate_runtime_specifier(specifier: String) -> Result<String, String> {
+    if specifier.is_empty() {
+        return Err("--runtime-specifier must not be empty".to_string());
     }
-    if !payload.ir.expression_bindings.is_empty()
-        && payload.ir.expression_bindings.len() != payload.ir.expressions.len()
+    match specifier
+        .chars()
+        .find(|c| matches!(c, '\'' | '"' | '`' | '\\') || c.is_control())
     {
-        return Err(format!(
-            "input.ir.expression_bindings length ({}) must match input.ir.expressions length ({})",
-            payload.ir.expression_bindings.len(),
-            payload.ir.expressions.len()
-        ));
+        Some(c) => Err(format!(
+            "invalid --runtime-specifier '{}': {:?} is not allowed in a module specifier",
+            specifier.escape_default(),
+            c
+        )),
+        None => Ok(specifier),
     }
-    if !payload.ir.marker_bindings.is_empty()
-        && payload.ir.marker_bindings.len() != payload.ir.expressions.len()
-    {
+}
+
+/// Normalize a base path to start and end with `/` (`app` → `/app/`).
+fn normalize_base_path(base: &str) -> String {
+    let trimmed = base.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{trimmed}/")
+    }
+}
+
+fn validate_expression_schema(
+    schema: &[ExpressionKind],
+    ir: &CompilerIr,
+    markers: &[MarkerBinding],
+) -> Result<(), String> {
+    if schema.len() != ir.expressions.len() {
         return Err(format!(
-            "input.ir.marker_bindings length ({}) must match input.ir.expressions length ({})",
-            payload.ir.marker_bindings.len(),
-            payload.ir.expressions.len()
+            "input.expression_schema length ({}) must match input.ir.expressions length ({})",
+            schema.len(),
+            ir.expressions.len()
         ));
     }
-    for signal in &payload.ir.signals {
-        if signal.kind != "signal" {
-            return Err(format!(
-                "input.ir.signals[].kind must be 'signal', got '{}'",
-                signal.kind
-            ));
-        }
-        if signal.state_index >= payload.ir.hoisted.state.len() {
-            return Err(format!(
-                "input.ir.signals[{}].state_index out of bounds: {}",
-                signal.id, signal.state_index
-            ));
-        }
-    }
-    for (position, binding) in payload.ir.expression_bindings.iter().enumerate() {
-        if binding.marker_index >= payload.ir.expressions.len() {
-            return Err(format!(
-                "input.ir.expression_bindings[{position}].marker_index out of bounds: {}",
-                binding.marker_index
-            ));
-        }
-        if let Some(state_index) = binding.state_index {
-            if state_index >= payload.ir.hoisted.state.len() {
-                return Err(format!(
-                    "input.ir.expression_bindings[{position}].state_index out of bounds: {}",
-                    state_index
-                ));
-            }
-        }
-        if let Some(signal_index) = binding.signal_index {
-            if signal_index >= payload.ir.signals.len() {
-                return Err(format!(
-                    "input.ir.expression_bindings[{position}].signal_index out of bounds: {}",
-                    signal_index
-                ));
-            }
-        }
-    }
-    for (hoist_id, script) in &payload.ir.components_scripts {
-        if hoist_id.trim().is_empty() {
-            return Err("input.ir.components_scripts contains an empty hoist_id key".into());
-        }
-        if script.code.trim().is_empty() {
-            return Err(format!(
-                "input.ir.components_scripts['{}'].code must be non-empty",
-                hoist_id
-            ));
-        }
-        if script.factory.trim().is_empty() {
-            return Err(format!(
-                "input.ir.components_scripts['{}'].factory must be non-empty",
-                hoist_id
-            ));
-        }
-        if script.hoist_id != *hoist_id {
-            return Err(format!(
-                "input.ir.components_scripts key '{}' mismatches hoist_id '{}'",
-                hoist_id, script.hoist_id
-            ));
-        }
-    }
-    for instance in &payload.ir.component_instances {
-        if instance.instance.trim().is_empty() {
-            return Err("input.ir.component_instances[].instance must be non-empty".into());
-        }
-        if instance.selector.trim().is_empty() {
-            return Err("input.ir.component_instances[].selector must be non-empty".into());
-        }
-        if !payload
-            .ir
-            .components_scripts
-            .contains_key(&instance.hoist_id)
-        {
+
+    for (index, expected) in schema.iter().enumerate() {
+        let is_component = ir
+            .expression_bindings
+            .get(index)
+            .is_some_and(|binding| binding.component_instance.is_some());
+        let is_event = markers
+            .iter()
+            .any(|marker| marker.index == index && matches!(marker.kind, MarkerKind::Event));
+        let actual = if is_component {
+            ExpressionKind::Component
+        } else if is_event {
+            ExpressionKind::Event
+        } else {
+            ExpressionKind::Value
+        };
+        if actual != *expected {
             return Err(format!(
-                "input.ir.component_instances references unknown hoist_id '{}'",
-                instance.hoist_id
+                "input.expression_schema[{index}] expects {expected:?} but expression `{}` is bound as {actual:?}",
+                ir.expressions[index]
             ));
         }
     }
 
-    if !payload.ir.marker_bindings.is_empty() {
-        let mut seen = BTreeMap::new();
-        for marker in &payload.ir.marker_bindings {
-            if marker.index >= payload.ir.expressions.len() {
-                return Err(format!(
-                    "input.ir.marker_bindings index out of bounds: {}",
-                    marker.index
-                ));
-            }
-            if seen.insert(marker.index, true).is_some() {
-                return Err(format!(
-                    "input.ir.marker_bindings contains duplicate index {}",
-                    marker.index
-                ));
-            }
-        }
-    }
-
     Ok(())
 }
 
-fn ensure_document_html(fragment_or_doc: &str) -> String {
-    if fragment_or_doc.contains("<html") {
-        return fragment_or_doc.to_string();
-    }
-    format!(
-        "<!DOCTYPE html><html><head></head><body>{}</body></html>",
-        fragment_or_doc
-    )
-}
-
 fn inject_script_once(html: &str, script_src: &str, marker_attr: &str) -> String {
     if html.contains(script_src) {
         return html.to_string();
     }
     let script_tag =
         format!("<script type=\"module\" src=\"{script_src}\" {marker_attr}></script>");
-    if html.contains("</body>") {
-        return html.replacen("</body>", &format!("{script_tag}</body>"), 1);
-    }
-    format!("{html}{script_tag}")
+    insert_before_body_close(html, &script_tag)
 }
 
-fn route_to_output_path(route_path: &str) -> PathBuf {
-    if route_path == "/" {
-        return PathBuf::from("index.html");
-    }
-
-    let mut out = PathBuf::new();
-    for segment in route_path.split('/').filter(|s| !s.is_empty()) {
-        if segment.starts_with(':') {
-            // Dynamic segments are rewritten by preview/router to this static shell.
-            // Example: /users/:id -> dist/users/index.html
-            continue;
-        }
-        out.push(segment);
-    }
-    out.push("index.html");
-    out
-}
-
-fn stable_hash_8(content: &str) -> String {
-    let mut hash: i32 = 0;
-    for byte in content.bytes() {
-        hash = hash
-            .wrapping_shl(5)
-            .wrapping_sub(hash)
-            .wrapping_add(byte as i32);
-    }
-    let normalized = hash.wrapping_abs() as u32;
-    format!("{normalized:08x}")
-}
-
-fn derive_binding_tables(ir: &CompilerIr) -> Result<(Vec<MarkerBinding>, Vec<EventBinding>), String> {
-    let expression_count = ir.expressions.len();
-    if expression_count == 0 {
-        return Ok((Vec::new(), Vec::new()));
-    }
-
-    let mut marker_slots: Vec<Option<MarkerBinding>> = vec![None; expression_count];
-    let mut event_bindings = Vec::new();
-
-    let attr_re = Regex::new(r#"data-zx-([A-Za-z0-9_-]+)=(?:"([^"]+)"|'([^']+)'|([^\s>"']+))"#)
-        .map_err(|e| format!("failed to compile binding regex: {e}"))?;
-
-    for captures in attr_re.captures_iter(&ir.html) {
-        let attr_name = captures
-            .get(1)
-            .map(|m| m.as_str())
-            .ok_or_else(|| "failed to parse data-zx attribute name".to_string())?;
-        let raw_value = captures
-            .get(2)
-            .or_else(|| captures.get(3))
-            .or_else(|| captures.get(4))
-            .map(|m| m.as_str())
-            .unwrap_or("");
-
-        if attr_name == "e" {
-            for part in raw_value.split_whitespace() {
-                let index = parse_expression_index(part, expression_count, "data-zx-e")?;
-                insert_marker(
-                    &mut marker_slots,
-                    MarkerBinding {
-                        index,
-                        kind: MarkerKind::Text,
-                        selector: format!(r#"[data-zx-e~="{index}"]"#),
-                        attr: None,
-                    },
-                )?;
-            }
-            continue;
-        }
-
-        if attr_name == "c" {
-            continue;
-        }
-
-        if let Some(event_name) = attr_name.strip_prefix("on-") {
-            let index = parse_expression_index(raw_value, expression_count, "data-zx-on-*")?;
-            let selector = format!(r#"[data-zx-on-{event_name}="{index}"]"#);
-            insert_marker(
-                &mut marker_slots,
-                MarkerBinding {
-                    index,
-                    kind: MarkerKind::Event,
-                    selector: selector.clone(),
-                    attr: None,
-                },
-            )?;
-            event_bindings.push(EventBinding {
-                index,
-                event: event_name.to_string(),
-                selector,
-            });
-            continue;
-        }
+/// Marker attribute of the injected `nomodule` fallback script.
+const NOMODULE_MARKER: &str = "data-zx-nomodule";
 
-        let index = parse_expression_index(raw_value, expression_count, "data-zx-*")?;
-        insert_marker(
-            &mut marker_slots,
-            MarkerBinding {
-                index,
-                kind: MarkerKind::Attr,
-                selector: format!(r#"[data-zx-{attr_name}="{index}"]"#),
-                attr: Some(attr_name.to_string()),
-            },
-        )?;
-    }
-
-    let mut markers = Vec::with_capacity(expression_count);
-    for (index, marker) in marker_slots.into_iter().enumerate() {
-        if let Some(binding) = marker {
-            markers.push(binding);
-            continue;
-        }
-        return Err(format!(
-            "marker/expression mismatch: missing marker for expression index {index}"
-        ));
-    }
-
-    Ok((markers, event_bindings))
-}
-
-fn parse_expression_index(raw: &str, expression_count: usize, context: &str) -> Result<usize, String> {
-    let parsed = raw
-        .parse::<usize>()
-        .map_err(|_| format!("invalid expression index '{raw}' in {context}"))?;
-
-    if parsed >= expression_count {
-        return Err(format!(
-            "out-of-bounds expression index {parsed} in {context}; expression count is {expression_count}"
-        ));
+/// Inject a `<script nomodule>` fallback for browsers without ES module
+/// support. Deduplicated by its marker rather than its `src`, since the
+/// fallback may point at the same chunk as the module script.
+fn inject_nomodule_script_once(html: &str, script_src: &str) -> String {
+    if html.contains(NOMODULE_MARKER) {
+        return html.to_string();
     }
-
-    Ok(parsed)
+    let script_tag = format!("<script nomodule src=\"{script_src}\" {NOMODULE_MARKER}></script>");
+    insert_before_body_close(html, &script_tag)
 }
 
-fn insert_marker(slots: &mut [Option<MarkerBinding>], marker: MarkerBinding) -> Result<(), String> {
-    let index = marker.index;
-
-    if index >= slots.len() {
-        return Err(format!(
-            "marker index {} out of bounds; marker slots length is {}",
-            index,
-            slots.len()
-        ));
-    }
-
-    if slots[index].is_some() {
-        return Err(format!(
-            "duplicate marker index {} detected while deriving binding tables",
-            index
-        ));
+/// Check that every `<script>` the bundler injected (tagged with a
+/// `data-zx-*` marker attribute) is an ES module. Author-written scripts
+/// are left alone.
+fn validate_injected_scripts(html: &str) -> Result<(), String> {
+    let script_re = Regex::new(r"(?i)<script\b[^>]*>")
+        .map_err(|e| format!("failed to compile script regex: {e}"))?;
+    let marker_re = Regex::new(r"\sdata-zx-(?:runtime|page|router)\b")
+        .map_err(|e| format!("failed to compile script marker regex: {e}"))?;
+    let module_re = Regex::new(r#"\stype\s*=\s*(?:"module"|'module'|module\b)"#)
+        .map_err(|e| format!("failed to compile script type regex: {e}"))?;
+
+    for tag in script_re.find_iter(html) {
+        let tag = tag.as_str();
+        if marker_re.is_match(tag) && !module_re.is_match(tag) {
+            return Err(format!(
+                "strict: injected script is not an ES module (missing type=\"module\"): {tag}"
+            ));
+        }
     }
-
-    slots[index] = Some(marker);
     Ok(())
 }
 
@@ -612,36 +862,64 @@ fn runtime_import_specifier(runtime_rel: &str) -> Result<String, String> {
     Ok(format!("./{file_name}"))
 }
 
-fn ensure_runtime_asset(out_dir: &PathBuf) -> Result<String, String> {
-    let runtime_js = generate_runtime_module_js();
-    let runtime_hash = stable_hash_8(&runtime_js);
-    let runtime_rel = format!("assets/runtime.{runtime_hash}.js");
-    let runtime_path = out_dir.join(&runtime_rel);
+/// The runtime module and its content-hashed asset path.
+struct RuntimeAsset {
+    js: String,
+    rel: String,
+}
 
-    if !runtime_path.exists() {
-        if let Some(parent) = runtime_path.parent() {
-            fs::create_dir_all(parent).map_err(|e| {
-                format!(
-                    "failed to create runtime asset dir '{}': {e}",
-                    parent.display()
-                )
-            })?;
-        }
-        fs::write(&runtime_path, runtime_js).map_err(|e| {
+/// The runtime never varies within a process, so it is generated and hashed
+/// once and shared by every route bundled.
+fn runtime_asset() -> &'static RuntimeAsset {
+    static RUNTIME: OnceLock<RuntimeAsset> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        let js = generate_runtime_module_js();
+        let rel = asset_filename("", &js, AssetKind::Runtime);
+        RuntimeAsset { js, rel }
+    })
+}
+
+/// Write the runtime asset unless an earlier run already did. Returns its
+/// path and whether it was written now.
+fn ensure_runtime_asset(out_dir: &PathBuf) -> Result<(String, bool), String> {
+    let RuntimeAsset {
+        js: runtime_js,
+        rel: runtime_rel,
+    } = runtime_asset();
+    let runtime_path = out_dir.join(runtime_rel);
+    if runtime_path.exists() {
+        return Ok((runtime_rel.clone(), false));
+    }
+
+    if let Some(parent) = runtime_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
             format!(
-                "failed to write runtime asset '{}': {e}",
-                runtime_path.display()
+                "failed to create runtime asset dir '{}': {e}",
+                parent.display()
             )
         })?;
     }
+    fs::write(&runtime_path, runtime_js).map_err(|e| {
+        format!(
+            "failed to write runtime asset '{}': {e}",
+            runtime_path.display()
+        )
+    })?;
 
-    Ok(runtime_rel)
+    Ok((runtime_rel.clone(), true))
 }
 
+/// Write each component module as a content-hashed asset and map its
+/// `hoist_id` to the asset path. Modules whose path is already in `emitted`
+/// (written for an earlier route) are referenced, not rewritten.
 fn emit_component_assets(
     out_dir: &PathBuf,
+    route: &str,
     components: &BTreeMap<String, CompilerComponentScript>,
     runtime_import_spec: &str,
+    validate_js: bool,
+    emitted: &mut BTreeSet<String>,
+    written: &mut Vec<WrittenFile>,
 ) -> Result<BTreeMap<String, String>, String> {
     let mut out = BTreeMap::new();
     for (hoist_id, component) in components {
@@ -661,8 +939,20 @@ fn emit_component_assets(
         module_source.push_str(&component.code);
         module_source.push('\n');
 
-        let module_hash = stable_hash_8(&module_source);
-        let rel = format!("assets/component.{}.{}.js", sanitize_asset_token(hoist_id), module_hash);
+        if validate_js {
+            zenith_bundler::utils::validate_js_syntax(&module_source, &format!("component {hoist_id}"))
+                .map_err(|e| e.to_string())?;
+        }
+
+        let rel = asset_filename(
+            &sanitize_asset_token(hoist_id),
+            &module_source,
+            AssetKind::Component,
+        );
+        if !emitted.insert(rel.clone()) {
+            out.insert(hoist_id.clone(), rel);
+            continue;
+        }
         let path = out_dir.join(&rel);
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).map_err(|e| {
@@ -672,18 +962,34 @@ fn emit_component_assets(
                 )
             })?;
         }
-        fs::write(&path, module_source).map_err(|e| {
+        fs::write(&path, &module_source).map_err(|e| {
             format!(
                 "failed to write component asset '{}': {e}",
                 path.display()
             )
         })?;
+        written.push(WrittenFile::hashed(
+            route,
+            WrittenKind::Component,
+            path,
+            &module_source,
+        ));
 
         out.insert(hoist_id.clone(), rel);
     }
     Ok(out)
 }
 
+/// Filename-safe token naming a route's entry asset: the route without its
+/// slashes, sanitized (`/blog/:slug` → `blog__slug`), or `index` for `/`.
+/// Unlike the source file's path, it is the same on every machine.
+fn route_asset_token(route: &str) -> String {
+    match route.trim_matches('/') {
+        "" => "index".to_string(),
+        path => sanitize_asset_token(path),
+    }
+}
+
 fn sanitize_asset_token(input: &str) -> String {
     input
         .chars()
@@ -691,12 +997,23 @@ fn sanitize_asset_token(input: &str) -> String {
         .collect()
 }
 
+/// Output-shaping switches for [`generate_entry_js`].
+#[derive(Debug, Clone, Copy, Default)]
+struct EntryJsOptions {
+    /// Emit the `__zenith_state_keys` debug map.
+    emit_state_keys: bool,
+    /// Emit expression bindings as parallel typed-array columns instead of
+    /// an array of objects (smaller to parse for very large pages).
+    columnar_bindings: bool,
+}
+
 fn generate_entry_js(
     ir: &CompilerIr,
     runtime_import_spec: &str,
     markers: &[MarkerBinding],
     events: &[EventBinding],
     component_assets: &BTreeMap<String, String>,
+    options: EntryJsOptions,
 ) -> Result<String, String> {
     let compiler_output = CompilerOutput {
         ir_version: ir.ir_version,
@@ -716,7 +1033,10 @@ fn generate_entry_js(
     let events_json = serde_json::to_string(events)
         .map_err(|e| format!("failed to serialize event table: {e}"))?;
 
-    let mut js = zenith_bundler::utils::generate_virtual_entry(&compiler_output);
+    let mut js = zenith_bundler::utils::generate_virtual_entry(
+        &compiler_output,
+        zenith_bundler::utils::DEFAULT_CONTRACT_VERSION,
+    );
     for block in &ir.hoisted.code {
         let trimmed = block.trim();
         if !trimmed.is_empty() {
@@ -735,14 +1055,21 @@ fn generate_entry_js(
     ));
     let signals_json = serde_json::to_string(&ir.signals)
         .map_err(|e| format!("failed to serialize signal table: {e}"))?;
-    let expression_bindings_json = if ir.expression_bindings.is_empty() {
-        fallback_expression_bindings(ir)?
+    let fallback_bindings;
+    let expression_bindings = if ir.expression_bindings.is_empty() {
+        fallback_bindings = fallback_expression_bindings(ir);
+        &fallback_bindings
+    } else {
+        &ir.expression_bindings
+    };
+    let expression_bindings_js = if options.columnar_bindings {
+        generate_columnar_bindings_js(expression_bindings)?
     } else {
-        serde_json::to_string(&ir.expression_bindings)
+        serde_json::to_string(expression_bindings)
             .map_err(|e| format!("failed to serialize expression table: {e}"))?
     };
 
-    js.push_str(&generate_state_table_js(&ir.hoisted.state)?);
+    js.push_str(&generate_state_table_js(&ir.hoisted.state, options.emit_state_keys)?);
     js.push_str(&format!(
         "const __zenith_ir_version = {};\n",
         ir.ir_version
@@ -753,7 +1080,7 @@ fn generate_entry_js(
     ));
     js.push_str(&format!(
         "const __zenith_expression_bindings = Object.freeze({});\n",
-        expression_bindings_json
+        expression_bindings_js
     ));
     let (component_imports, components_table) =
         generate_component_bootstrap_js(ir, component_assets)?;
@@ -779,24 +1106,43 @@ fn generate_entry_js(
     Ok(js)
 }
 
-fn generate_state_table_js(bindings: &[CompilerStateBinding]) -> Result<String, String> {
-    if bindings.is_empty() {
-        return Ok("const __zenith_state_values = Object.freeze([]);\n".to_string());
-    }
+fn generate_state_table_js(
+    bindings: &[CompilerStateBinding],
+    emit_state_keys: bool,
+) -> Result<String, String> {
+    let mut out = if bindings.is_empty() {
+        "const __zenith_state_values = Object.freeze([]);\n".to_string()
+    } else {
+        let mut values = String::from("const __zenith_state_values = Object.freeze([\n");
+        for binding in bindings {
+            values.push_str("  ");
+            values.push_str(binding.value.trim());
+            values.push_str(",\n");
+        }
+        values.push_str("]);\n");
+        values
+    };
 
-    let mut out = String::from("const __zenith_state_values = Object.freeze([\n");
-    for binding in bindings {
-        out.push_str("  ");
-        out.push_str(binding.value.trim());
-        out.push_str(",\n");
+    if emit_state_keys {
+        // Key → index, in state table order (debugging aid only)
+        let mut keys = String::from("const __zenith_state_keys = Object.freeze({");
+        for (index, binding) in bindings.iter().enumerate() {
+            if index > 0 {
+                keys.push(',');
+            }
+            let key_json = serde_json::to_string(&binding.key)
+                .map_err(|e| format!("failed to serialize state key: {e}"))?;
+            keys.push_str(&format!("{key_json}:{index}"));
+        }
+        keys.push_str("});\n");
+        out.push_str(&keys);
     }
-    out.push_str("]);\n");
+
     Ok(out)
 }
 
-fn fallback_expression_bindings(ir: &CompilerIr) -> Result<String, String> {
-    let bindings: Vec<CompilerExpressionBinding> = ir
-        .expressions
+fn fallback_expression_bindings(ir: &CompilerIr) -> Vec<CompilerExpressionBinding> {
+    ir.expressions
         .iter()
         .enumerate()
         .map(|(index, value)| CompilerExpressionBinding {
@@ -806,10 +1152,36 @@ fn fallback_expression_bindings(ir: &CompilerIr) -> Result<String, String> {
             component_instance: None,
             component_binding: None,
             literal: Some(value.clone()),
+            state_key: None,
         })
-        .collect();
-    serde_json::to_string(&bindings)
-        .map_err(|e| format!("failed to serialize fallback expressions: {e}"))
+        .collect()
+}
+
+/// Encode expression bindings as parallel columns.
+///
+/// Index columns are typed arrays; `-1` marks an absent signal/state index.
+/// String columns stay plain arrays with `null` for absent values. The
+/// runtime reads both layouts through `__evaluateExpression`.
+fn generate_columnar_bindings_js(bindings: &[CompilerExpressionBinding]) -> Result<String, String> {
+    fn index_column(values: impl Iterator<Item = i64>) -> String {
+        values.map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+    }
+    let optional_index = |value: Option<usize>| value.map_or(-1, |v| v as i64);
+
+    let marker_index = index_column(bindings.iter().map(|b| b.marker_index as i64));
+    let signal_index = index_column(bindings.iter().map(|b| optional_index(b.signal_index)));
+    let state_index = index_column(bindings.iter().map(|b| optional_index(b.state_index)));
+    let string_column = |values: Vec<&Option<String>>| {
+        serde_json::to_string(&values).map_err(|e| format!("failed to serialize expression column: {e}"))
+    };
+    let component_instance = string_column(bindings.iter().map(|b| &b.component_instance).collect())?;
+    let component_binding = string_column(bindings.iter().map(|b| &b.component_binding).collect())?;
+    let literal = string_column(bindings.iter().map(|b| &b.literal).collect())?;
+
+    Ok(format!(
+        "{{layout:'columnar',length:{},marker_index:new Uint32Array([{marker_index}]),signal_index:new Int32Array([{signal_index}]),state_index:new Int32Array([{state_index}]),component_instance:{component_instance},component_binding:{component_binding},literal:{literal}}}",
+        bindings.len()
+    ))
 }
 
 fn generate_component_bootstrap_js(
@@ -860,24 +1232,36 @@ fn generate_component_bootstrap_js(
 }
 
 fn generate_runtime_module_js() -> String {
-    r#"const BOOLEAN_ATTRIBUTES = new Set(['disabled', 'checked', 'readonly', 'required', 'selected', 'open', 'hidden']);
+    let supported_ir_versions =
+        serde_json::to_string(SUPPORTED_IR_VERSIONS).expect("IR versions serialize");
+    r#"const SUPPORTED_IR_VERSIONS = __ZENITH_SUPPORTED_IR_VERSIONS__;
+const BOOLEAN_ATTRIBUTES = new Set(['disabled', 'checked', 'readonly', 'required', 'selected', 'open', 'hidden']);
 const __listeners = [];
 const __components = [];
+const __subscriptions = [];
 
+// Each registry is detached before it is torn down, so anything registered
+// while tearing down belongs to the next hydrate. Subscriptions go first so
+// no marker re-renders into a component being destroyed.
 function cleanup() {
-  for (let i = 0; i < __components.length; i++) {
-    const instance = __components[i];
+  const subscriptions = __subscriptions.splice(0);
+  for (let i = 0; i < subscriptions.length; i++) {
+    subscriptions[i]();
+  }
+
+  const components = __components.splice(0);
+  for (let i = 0; i < components.length; i++) {
+    const instance = components[i];
     if (instance && typeof instance.destroy === 'function') {
       instance.destroy();
     }
   }
-  __components.length = 0;
 
-  for (let i = 0; i < __listeners.length; i++) {
-    const item = __listeners[i];
+  const listeners = __listeners.splice(0);
+  for (let i = 0; i < listeners.length; i++) {
+    const item = listeners[i];
     item.node.removeEventListener(item.event, item.handler);
   }
-  __listeners.length = 0;
 }
 
 function __coerceText(value) {
@@ -885,9 +1269,15 @@ function __coerceText(value) {
   return String(value);
 }
 
+// Values reach setAttribute as-is, except for characters browsers handle
+// inconsistently: null bytes are dropped and CR / CRLF become LF
+function __sanitizeAttributeValue(value) {
+  return String(value).replace(/\u0000/g, '').replace(/\r\n?/g, '\n');
+}
+
 function __applyAttribute(node, attrName, value) {
   if (attrName === 'class' || attrName === 'className') {
-    node.className = value === null || value === undefined || value === false ? '' : String(value);
+    node.className = value === null || value === undefined || value === false ? '' : __sanitizeAttributeValue(value);
     return;
   }
 
@@ -896,20 +1286,16 @@ function __applyAttribute(node, attrName, value) {
       node.removeAttribute('style');
       return;
     }
-    if (typeof value === 'string') {
-      node.setAttribute('style', value);
-      return;
-    }
     if (typeof value === 'object') {
       const entries = Object.entries(value);
       let styleText = '';
       for (let i = 0; i < entries.length; i++) {
         styleText += entries[i][0] + ': ' + entries[i][1] + ';';
       }
-      node.setAttribute('style', styleText);
+      node.setAttribute('style', __sanitizeAttributeValue(styleText));
       return;
     }
-    node.setAttribute('style', String(value));
+    node.setAttribute('style', __sanitizeAttributeValue(value));
     return;
   }
 
@@ -927,7 +1313,160 @@ function __applyAttribute(node, attrName, value) {
     return;
   }
 
-  node.setAttribute(attrName, String(value));
+  node.setAttribute(attrName, __sanitizeAttributeValue(value));
+}
+
+// Hidden exactly when __coerceText would render nothing for a non-string
+function __applyShow(node, value) {
+  if (value === null || value === undefined || value === false) {
+    node.setAttribute('hidden', '');
+  } else {
+    node.removeAttribute('hidden');
+  }
+}
+
+function __listField(item, field) {
+  if (field === '' || field === '.') return item;
+  return item === null || item === undefined ? undefined : item[field];
+}
+
+function __listKey(item) {
+  if (item === null || typeof item !== 'object') return undefined;
+  return item.key === null ? undefined : item.key;
+}
+
+function __mountList(container, index) {
+  const template = typeof container.querySelector === 'function' ? container.querySelector('template') : null;
+  const prototype = template && template.content ? template.content.firstElementChild : null;
+  if (!prototype) {
+    throw new Error('[Zenith Runtime] list marker index ' + index + ' requires a <template> with one root element');
+  }
+  const list = { container, prototype, rows: [] };
+  __components.push({
+    destroy() {
+      for (let i = 0; i < list.rows.length; i++) __removeListRow(list, list.rows[i]);
+      list.rows = [];
+    }
+  });
+  return list;
+}
+
+function __releaseListRow(row) {
+  for (let i = 0; i < row.listeners.length; i++) {
+    const item = row.listeners[i];
+    item.node.removeEventListener(item.event, item.handler);
+  }
+  row.listeners.length = 0;
+}
+
+function __removeListRow(list, row) {
+  __releaseListRow(row);
+  if (row.node.parentNode === list.container) {
+    list.container.removeChild(row.node);
+  }
+}
+
+// `data-zx-item="field"` renders a field of the item (`.` the item itself);
+// `data-zx-item-on-<event>="field"` binds a function field as a listener
+function __fillListRow(row, item, index) {
+  __releaseListRow(row);
+  for (let i = 0; i < row.nodes.length; i++) {
+    const node = row.nodes[i];
+    const names = node.getAttributeNames();
+    for (let j = 0; j < names.length; j++) {
+      const name = names[j];
+      const field = node.getAttribute(name);
+      if (name === 'data-zx-item') {
+        node.textContent = __coerceText(__listField(item, field));
+      } else if (name.startsWith('data-zx-item-on-')) {
+        const handler = __listField(item, field);
+        if (typeof handler !== 'function') {
+          throw new Error('[Zenith Runtime] list marker index ' + index + ' item field ' + field + ' must be a function');
+        }
+        const event = name.slice('data-zx-item-on-'.length);
+        node.addEventListener(event, handler);
+        row.listeners.push({ node, event, handler });
+      }
+    }
+  }
+}
+
+function __renderList(list, value, index) {
+  const items = value === null || value === undefined || value === false ? [] : value;
+  if (!Array.isArray(items)) {
+    throw new Error('[Zenith Runtime] list marker index ' + index + ' must resolve to an array');
+  }
+
+  // Keyed rows are reused across renders; unkeyed rows are always rebuilt
+  const reusable = new Map();
+  for (let i = 0; i < list.rows.length; i++) {
+    const row = list.rows[i];
+    if (row.key === undefined) {
+      __removeListRow(list, row);
+    } else {
+      reusable.set(row.key, row);
+    }
+  }
+
+  const rows = [];
+  const seen = new Set();
+  for (let i = 0; i < items.length; i++) {
+    const key = __listKey(items[i]);
+    if (key !== undefined) {
+      if (seen.has(key)) {
+        throw new Error('[Zenith Runtime] list marker index ' + index + ' has duplicate key ' + String(key));
+      }
+      seen.add(key);
+    }
+    let row = key === undefined ? undefined : reusable.get(key);
+    if (row) {
+      reusable.delete(key);
+    } else {
+      const node = list.prototype.cloneNode(true);
+      const nodes = [node];
+      const descendants = node.querySelectorAll('*');
+      for (let j = 0; j < descendants.length; j++) nodes.push(descendants[j]);
+      row = { key, node, nodes, listeners: [] };
+    }
+    __fillListRow(row, items[i], index);
+    rows.push(row);
+  }
+
+  for (const row of reusable.values()) __removeListRow(list, row);
+  // appendChild moves reused rows, so the container ends up in item order
+  for (let i = 0; i < rows.length; i++) list.container.appendChild(rows[i].node);
+  list.rows = rows;
+}
+
+function __modelProperty(node) {
+  const type = typeof node.type === 'string' ? node.type.toLowerCase() : '';
+  return type === 'checkbox' || type === 'radio' ? 'checked' : 'value';
+}
+
+function __writeModel(node, value) {
+  const property = __modelProperty(node);
+  const next = property === 'checked' ? !!value : __coerceText(value);
+  // Skip no-op writes so a DOM-originated update does not echo back
+  if (Object.is(node[property], next)) return;
+  node[property] = next;
+}
+
+function __bindModel(node, target, index) {
+  if (!target || typeof target.get !== 'function' || typeof target.set !== 'function') {
+    throw new Error('[Zenith Runtime] model marker index ' + index + ' must bind a signal');
+  }
+  __writeModel(node, target.get());
+  const property = __modelProperty(node);
+  const tagName = typeof node.tagName === 'string' ? node.tagName.toLowerCase() : '';
+  const event = property === 'checked' || tagName === 'select' ? 'change' : 'input';
+  const handler = function () {
+    const next = node[property];
+    if (!Object.is(target.get(), next)) {
+      target.set(next);
+    }
+  };
+  node.addEventListener(event, handler);
+  __listeners.push({ node, event, handler });
 }
 
 function __getComponentBinding(bindingsByInstance, instance, binding) {
@@ -937,47 +1476,75 @@ function __getComponentBinding(bindingsByInstance, instance, binding) {
   return instanceBindings[binding];
 }
 
-function __evaluateExpression(binding, stateValues, signalMap, componentBindings, mode) {
-  if (!binding || typeof binding !== 'object') {
-    throw new Error('[Zenith Runtime] expression binding must be an object');
-  }
+function __isColumnar(expressions) {
+  return !!expressions && !Array.isArray(expressions) && expressions.layout === 'columnar';
+}
 
-  if (!Number.isInteger(binding.marker_index) || binding.marker_index < 0) {
-    throw new Error('[Zenith Runtime] expression binding requires marker_index');
+function __columnIndex(column, index) {
+  const value = column[index];
+  return value < 0 ? null : value;
+}
+
+function __evaluateExpression(expressions, index, stateValues, signalMap, componentBindings, mode) {
+  let signalIndex;
+  let stateIndex;
+  let componentInstance;
+  let componentBinding;
+  let literal;
+
+  if (__isColumnar(expressions)) {
+    signalIndex = __columnIndex(expressions.signal_index, index);
+    stateIndex = __columnIndex(expressions.state_index, index);
+    componentInstance = expressions.component_instance[index];
+    componentBinding = expressions.component_binding[index];
+    literal = expressions.literal[index];
+  } else {
+    const binding = expressions[index];
+    if (!binding || typeof binding !== 'object') {
+      throw new Error('[Zenith Runtime] expression binding must be an object');
+    }
+    if (!Number.isInteger(binding.marker_index) || binding.marker_index < 0) {
+      throw new Error('[Zenith Runtime] expression binding requires marker_index');
+    }
+    signalIndex = binding.signal_index;
+    stateIndex = binding.state_index;
+    componentInstance = binding.component_instance;
+    componentBinding = binding.component_binding;
+    literal = binding.literal;
   }
 
-  if (binding.signal_index !== null && binding.signal_index !== undefined) {
-    if (!Number.isInteger(binding.signal_index)) {
+  if (signalIndex !== null && signalIndex !== undefined) {
+    if (!Number.isInteger(signalIndex)) {
       throw new Error('[Zenith Runtime] expression.signal_index must be an integer');
     }
-    const signalValue = signalMap.get(binding.signal_index);
+    const signalValue = signalMap.get(signalIndex);
     if (!signalValue || typeof signalValue.get !== 'function') {
       throw new Error('[Zenith Runtime] expression.signal_index did not resolve to a signal');
     }
     return mode === 'event' ? signalValue : signalValue.get();
   }
 
-  if (binding.state_index !== null && binding.state_index !== undefined) {
-    if (!Number.isInteger(binding.state_index) || binding.state_index < 0 || binding.state_index >= stateValues.length) {
+  if (stateIndex !== null && stateIndex !== undefined) {
+    if (!Number.isInteger(stateIndex) || stateIndex < 0 || stateIndex >= stateValues.length) {
       throw new Error('[Zenith Runtime] expression.state_index out of bounds');
     }
-    const resolved = stateValues[binding.state_index];
+    const resolved = stateValues[stateIndex];
     if (mode !== 'event' && typeof resolved === 'function') {
       return resolved();
     }
     return resolved;
   }
 
-  if (typeof binding.component_instance === 'string' && typeof binding.component_binding === 'string') {
-    const resolved = __getComponentBinding(componentBindings, binding.component_instance, binding.component_binding);
+  if (typeof componentInstance === 'string' && typeof componentBinding === 'string') {
+    const resolved = __getComponentBinding(componentBindings, componentInstance, componentBinding);
     if (mode !== 'event' && typeof resolved === 'function') {
       return resolved();
     }
     return resolved;
   }
 
-  if (binding.literal !== null && binding.literal !== undefined) {
-    return binding.literal;
+  if (literal !== null && literal !== undefined) {
+    return literal;
   }
 
   return '';
@@ -997,15 +1564,29 @@ export function hydrate(payload) {
   if (!payload || typeof payload !== 'object') {
     throw new Error('[Zenith Runtime] hydrate(payload) requires an object payload');
   }
-  if (payload.ir_version !== 1) {
-    throw new Error('[Zenith Runtime] unsupported ir_version (expected 1)');
+  if (!SUPPORTED_IR_VERSIONS.includes(payload.ir_version)) {
+    throw new Error('[Zenith Runtime] unsupported ir_version ' + payload.ir_version + ' (supported: ' + SUPPORTED_IR_VERSIONS.join(', ') + ')');
   }
   if (!payload.root || typeof payload.root.querySelectorAll !== 'function') {
     throw new Error('[Zenith Runtime] hydrate(payload) requires payload.root with querySelectorAll');
   }
-  if (!Array.isArray(payload.expressions)) {
+  const columnar = __isColumnar(payload.expressions);
+  if (!columnar && !Array.isArray(payload.expressions)) {
     throw new Error('[Zenith Runtime] hydrate(payload) requires expressions[]');
   }
+  if (columnar) {
+    const table = payload.expressions;
+    const columns = ['marker_index', 'signal_index', 'state_index', 'component_instance', 'component_binding', 'literal'];
+    if (!Number.isInteger(table.length) || table.length < 0) {
+      throw new Error('[Zenith Runtime] columnar expressions require a length');
+    }
+    for (let i = 0; i < columns.length; i++) {
+      const column = table[columns[i]];
+      if (!column || column.length !== table.length) {
+        throw new Error('[Zenith Runtime] columnar expressions column ' + columns[i] + ' must have length ' + table.length);
+      }
+    }
+  }
   if (!Array.isArray(payload.markers)) {
     throw new Error('[Zenith Runtime] hydrate(payload) requires markers[]');
   }
@@ -1093,11 +1674,17 @@ export function hydrate(payload) {
     if (!candidate || typeof candidate !== 'object' || typeof candidate.get !== 'function' || typeof candidate.subscribe !== 'function') {
       throw new Error('[Zenith Runtime] signal descriptor id ' + entry.id + ' did not resolve to a signal object');
     }
-    signalMap.set(entry.id, candidate);
+    // Keyed by table position: expression `signal_index` values index signals[]
+    signalMap.set(i, candidate);
   }
 
   const expressionMarkerIndices = new Set();
-  for (let i = 0; i < expressions.length; i++) {
+  for (let i = 0; columnar && i < expressions.length; i++) {
+    if (expressions.marker_index[i] !== i) {
+      throw new Error('[Zenith Runtime] expression table out of order at position ' + i + ': marker_index=' + expressions.marker_index[i]);
+    }
+  }
+  for (let i = 0; !columnar && i < expressions.length; i++) {
     const expression = expressions[i];
     if (!expression || typeof expression !== 'object') {
       throw new Error('[Zenith Runtime] expression at position ' + i + ' must be an object');
@@ -1117,6 +1704,7 @@ export function hydrate(payload) {
   const markerIndices = new Set();
   const markerByIndex = new Map();
   const markerNodesByIndex = new Map();
+  const listsByIndex = new Map();
   for (let i = 0; i < markers.length; i++) {
     const marker = markers[i];
     if (!marker || typeof marker !== 'object') {
@@ -1144,7 +1732,7 @@ export function hydrate(payload) {
 
     const nodes = __resolveNodes(root, marker.selector, marker.index, marker.kind);
     markerNodesByIndex.set(marker.index, nodes);
-    const value = __evaluateExpression(expressions[marker.index], stateValues, signalMap, componentBindings, marker.kind);
+    const value = __evaluateExpression(expressions, marker.index, stateValues, signalMap, componentBindings, marker.kind);
 
     for (let j = 0; j < nodes.length; j++) {
       if (marker.kind === 'text') {
@@ -1154,6 +1742,18 @@ export function hydrate(payload) {
           throw new Error('[Zenith Runtime] attr marker at position ' + i + ' requires attr');
         }
         __applyAttribute(nodes[j], marker.attr, value);
+      } else if (marker.kind === 'model') {
+        const target = __evaluateExpression(expressions, marker.index, stateValues, signalMap, componentBindings, 'event');
+        __bindModel(nodes[j], target, marker.index);
+      } else if (marker.kind === 'show') {
+        __applyShow(nodes[j], value);
+      } else if (marker.kind === 'list') {
+        const list = __mountList(nodes[j], marker.index);
+        if (!listsByIndex.has(marker.index)) {
+          listsByIndex.set(marker.index, []);
+        }
+        listsByIndex.get(marker.index).push(list);
+        __renderList(list, value, marker.index);
       } else {
         throw new Error('[Zenith Runtime] marker at position ' + i + ' has invalid kind');
       }
@@ -1172,25 +1772,40 @@ export function hydrate(payload) {
     const nodes = markerNodesByIndex.get(index) || __resolveNodes(root, marker.selector, marker.index, marker.kind);
     markerNodesByIndex.set(index, nodes);
 
-    const value = __evaluateExpression(expressions[index], stateValues, signalMap, componentBindings, marker.kind);
+    const value = __evaluateExpression(expressions, index, stateValues, signalMap, componentBindings, marker.kind);
     for (let j = 0; j < nodes.length; j++) {
       if (marker.kind === 'text') {
         nodes[j].textContent = __coerceText(value);
       } else if (marker.kind === 'attr') {
         __applyAttribute(nodes[j], marker.attr, value);
+      } else if (marker.kind === 'model') {
+        __writeModel(nodes[j], value);
+      } else if (marker.kind === 'show') {
+        __applyShow(nodes[j], value);
+      } else if (marker.kind === 'list') {
+        __renderList(listsByIndex.get(index)[j], value, index);
       }
     }
   }
 
   const dependentMarkersBySignal = new Map();
   for (let i = 0; i < expressions.length; i++) {
-    const binding = expressions[i];
-    if (!binding || typeof binding !== 'object') continue;
-    if (!Number.isInteger(binding.signal_index)) continue;
-    if (!dependentMarkersBySignal.has(binding.signal_index)) {
-      dependentMarkersBySignal.set(binding.signal_index, []);
+    let signalIndex;
+    let markerIndex;
+    if (columnar) {
+      signalIndex = __columnIndex(expressions.signal_index, i);
+      markerIndex = expressions.marker_index[i];
+    } else {
+      const binding = expressions[i];
+      if (!binding || typeof binding !== 'object') continue;
+      signalIndex = binding.signal_index;
+      markerIndex = binding.marker_index;
+    }
+    if (!Number.isInteger(signalIndex)) continue;
+    if (!dependentMarkersBySignal.has(signalIndex)) {
+      dependentMarkersBySignal.set(signalIndex, []);
     }
-    dependentMarkersBySignal.get(binding.signal_index).push(binding.marker_index);
+    dependentMarkersBySignal.get(signalIndex).push(markerIndex);
   }
 
   for (const [signalId, markerIndicesForSignal] of dependentMarkersBySignal.entries()) {
@@ -1204,7 +1819,7 @@ export function hydrate(payload) {
       }
     });
     if (typeof unsubscribe === 'function') {
-      __components.push({ destroy: unsubscribe });
+      __subscriptions.push(unsubscribe);
     }
   }
 
@@ -1229,7 +1844,7 @@ export function hydrate(payload) {
     }
 
     const nodes = __resolveNodes(root, binding.selector, binding.index, 'event');
-    const handler = __evaluateExpression(expressions[binding.index], stateValues, signalMap, componentBindings, 'event');
+    const handler = __evaluateExpression(expressions, binding.index, stateValues, signalMap, componentBindings, 'event');
     if (typeof handler !== 'function') {
       throw new Error('[Zenith Runtime] event binding at index ' + binding.index + ' did not resolve to a function');
     }
@@ -1316,11 +1931,11 @@ export function zeneffect(dependencies, fn) {
   };
 }
 "#
-    .to_string()
+    .replace("__ZENITH_SUPPORTED_IR_VERSIONS__", &supported_ir_versions)
 }
 
 fn upsert_router_manifest(out_dir: &PathBuf, entry: RouterRouteEntry) -> Result<(), String> {
-    let manifest_path = out_dir.join("assets").join("router-manifest.json");
+    let manifest_path = out_dir.join(ROUTER_MANIFEST_FILE);
     if let Some(parent) = manifest_path.parent() {
         fs::create_dir_all(parent).map_err(|e| {
             format!(
@@ -1343,17 +1958,7 @@ fn upsert_router_manifest(out_dir: &PathBuf, entry: RouterRouteEntry) -> Result<
         RouterManifest::default()
     };
 
-    if let Some(existing) = manifest
-        .routes
-        .iter_mut()
-        .find(|route| route.path == entry.path)
-    {
-        *existing = entry;
-    } else {
-        manifest.routes.push(entry);
-    }
-
-    manifest.routes.sort_by(|a, b| a.path.cmp(&b.path));
+    manifest.upsert(entry).map_err(|e| e.to_string())?;
 
     let json = serde_json::to_string(&manifest)
         .map_err(|e| format!("failed to serialize router manifest: {e}"))?;
@@ -1367,10 +1972,37 @@ fn upsert_router_manifest(out_dir: &PathBuf, entry: RouterRouteEntry) -> Result<
     Ok(())
 }
 
-fn generate_router_runtime_js() -> String {
+/// The client router. `base_path` (normalized, e.g. `/app/`) prefixes the
+/// manifest URL, matching where `upsert_router_manifest` writes it, and is
+/// stripped from location paths before they are matched against routes.
+fn generate_router_runtime_js(base_path: &str) -> String {
+    let manifest_url =
+        serde_json::Value::String(format!("{base_path}{ROUTER_MANIFEST_FILE}")).to_string();
     r#"(function() {
-  const MANIFEST_URL = '/assets/router-manifest.json';
+  const MANIFEST_URL = __ZENITH_MANIFEST_URL__;
+  const BASE_PATH = __ZENITH_BASE_PATH__;
+  const CONTENT_HASH_LEN = __ZENITH_CONTENT_HASH_LEN__;
   let manifestPromise = null;
+  const verifiedRoutes = new Set();
+
+  // Scroll positions by history entry. Entries the router pushes carry a
+  // key in their state; the page's first entry has none.
+  const scrollPositions = new Map();
+  let currentKey = entryKey(history.state);
+  if ('scrollRestoration' in history) history.scrollRestoration = 'manual';
+
+  function entryKey(state) {
+    return state && typeof state.zenith_key === 'string' ? state.zenith_key : 'initial';
+  }
+
+  function rememberScroll() {
+    scrollPositions.set(currentKey, [window.scrollX || 0, window.scrollY || 0]);
+  }
+
+  // Lets apps run analytics or move focus once the new route is rendered
+  function dispatchNavigate(path, params) {
+    window.dispatchEvent(new CustomEvent('zenith:navigate', { detail: { path, params } }));
+  }
 
   function loadManifest() {
     if (!manifestPromise) {
@@ -1385,40 +2017,96 @@ fn generate_router_runtime_js() -> String {
     return path.split('/').filter(Boolean);
   }
 
+  // Routes are declared without the base path; paths outside it are not ours
+  function routePath(pathname) {
+    if (pathname === BASE_PATH.slice(0, -1)) return '/';
+    return pathname.startsWith(BASE_PATH) ? pathname.slice(BASE_PATH.length - 1) : null;
+  }
+
+  // Manifests written before segments were recorded only carry `path`
+  function routeSegments(route) {
+    if (Array.isArray(route.segments)) return route.segments;
+    return splitPath(route.path).map((seg) =>
+      seg.startsWith(':') ? { kind: 'param', name: seg.slice(1) } : { kind: 'static', value: seg }
+    );
+  }
+
+  function isFlexible(routeSegs) {
+    return routeSegs.some((seg) => seg.kind === 'optional' || seg.kind === 'rest');
+  }
+
+  // Fixed-length routes win; otherwise the first optional/catch-all match
   function matchRoute(pathname, routes) {
-    const segments = splitPath(pathname);
+    const path = routePath(pathname);
+    if (path === null) return null;
+    const segments = splitPath(path);
+    let fallback = null;
     for (let i = 0; i < routes.length; i++) {
       const route = routes[i];
-      const routeSegs = splitPath(route.path);
-      if (routeSegs.length !== segments.length) continue;
-
+      const routeSegs = routeSegments(route);
       const params = {};
       let matched = true;
-      for (let j = 0; j < routeSegs.length; j++) {
+      let consumed = 0;
+      for (let j = 0; j < routeSegs.length && matched; j++) {
         const routeSeg = routeSegs[j];
         const seg = segments[j];
-        if (routeSeg.startsWith(':')) {
-          params[routeSeg.slice(1)] = seg;
-          continue;
-        }
-        if (routeSeg !== seg) {
+        if (routeSeg.kind === 'rest') {
+          params[routeSeg.name] = segments.slice(j);
+          consumed = segments.length;
+        } else if (seg === undefined) {
+          matched = routeSeg.kind === 'optional';
+        } else if (routeSeg.kind === 'param' || routeSeg.kind === 'optional') {
+          params[routeSeg.name] = seg;
+          consumed = j + 1;
+        } else if (routeSeg.value === seg) {
+          consumed = j + 1;
+        } else {
           matched = false;
-          break;
         }
       }
-      if (matched) return { route, params };
+      if (!matched || consumed !== segments.length) continue;
+      if (!isFlexible(routeSegs)) return { route, params };
+      if (!fallback) fallback = { route, params };
     }
-    return null;
+    return fallback;
   }
 
   function resolveExpression(expr, params) {
     const match = /^params\.([A-Za-z_$][\w$]*)$/.exec(expr);
     if (!match) return '';
     const value = params[match[1]];
+    if (Array.isArray(value)) return value.join('/');
     return value == null ? '' : String(value);
   }
 
+  async function routeContentHash(route) {
+    const source = route.html + '\n' + route.expressions.join('\n');
+    const digest = await crypto.subtle.digest('SHA-256', new TextEncoder().encode(source));
+    return Array.from(new Uint8Array(digest), (byte) => byte.toString(16).padStart(2, '0'))
+      .join('')
+      .slice(0, CONTENT_HASH_LEN);
+  }
+
+  // Best effort: entries without a hash, or pages without Web Crypto
+  // (insecure contexts), are not checked. A mismatch only warns.
+  function verifyRoute(route) {
+    if (!route.content_hash || verifiedRoutes.has(route.path)) return;
+    if (typeof crypto === 'undefined' || !crypto.subtle) return;
+    verifiedRoutes.add(route.path);
+    routeContentHash(route)
+      .then((hash) => {
+        if (hash !== route.content_hash) {
+          console.warn(
+            `[zenith] router manifest entry for ${route.path} is out of sync ` +
+              `(content_hash ${route.content_hash}, computed ${hash}); rebuild the route`
+          );
+        }
+      })
+      .catch(() => {});
+  }
+
   function renderRoute(match) {
+    verifyRoute(match.route);
     const template = document.createElement('template');
     template.innerHTML = match.route.html;
 
@@ -1465,9 +2153,9 @@ fn generate_router_runtime_js() -> String {
     const manifest = await loadManifest();
     const routes = Array.isArray(manifest.routes) ? manifest.routes : [];
     const matched = matchRoute(pathname, routes);
-    if (!matched) return false;
+    if (!matched) return null;
     renderRoute(matched);
-    return true;
+    return matched;
   }
 
   function isInternalLink(anchor) {
@@ -1481,12 +2169,16 @@ fn generate_router_runtime_js() -> String {
   }
 
   async function navigate(pathname) {
-    const ok = await resolvePath(pathname);
-    if (!ok) {
+    rememberScroll();
+    const matched = await resolvePath(pathname);
+    if (!matched) {
       window.location.assign(pathname);
       return;
     }
-    history.pushState({}, '', pathname);
+    currentKey = Date.now().toString(36) + Math.random().toString(36).slice(2);
+    history.pushState({ zenith_key: currentKey }, '', pathname);
+    window.scrollTo(0, 0);
+    dispatchNavigate(pathname, matched.params);
   }
 
   document.addEventListener('click', function(event) {
@@ -1501,17 +2193,1617 @@ fn generate_router_runtime_js() -> String {
     navigate(nextPath);
   });
 
-  window.addEventListener('popstate', function() {
-    resolvePath(window.location.pathname);
+  window.addEventListener('popstate', async function(event) {
+    rememberScroll();
+    currentKey = entryKey(event.state);
+    const pathname = window.location.pathname;
+    const matched = await resolvePath(pathname);
+    if (!matched) return;
+    const [x, y] = scrollPositions.get(currentKey) || [0, 0];
+    window.scrollTo(x, y);
+    dispatchNavigate(pathname, matched.params);
   });
 
   loadManifest().then((manifest) => {
     const routes = Array.isArray(manifest.routes) ? manifest.routes : [];
     const initial = matchRoute(window.location.pathname, routes);
-    if (initial && initial.route && typeof initial.route.path === 'string' && initial.route.path.includes(':')) {
+    if (initial && routeSegments(initial.route).some((seg) => seg.kind !== 'static')) {
       renderRoute(initial);
     }
   });
 })();"#
-        .to_string()
+        .replace("__ZENITH_MANIFEST_URL__", &manifest_url)
+        .replace(
+            "__ZENITH_BASE_PATH__",
+            &serde_json::Value::String(base_path.to_string()).to_string(),
+        )
+        .replace("__ZENITH_CONTENT_HASH_LEN__", &CONTENT_HASH_LEN.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zenith_bundler::cli_input::marker_attribute;
+    use zenith_bundler::utils::parse_route;
+
+    fn ir_from_json(value: serde_json::Value) -> CompilerIr {
+        serde_json::from_value(value).expect("valid IR JSON")
+    }
+
+    #[test]
+    fn validate_js_catches_broken_hoisted_block() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<h1 data-zx-e=\"0\"></h1>",
+            "expressions": ["title"],
+            "hoisted": { "code": ["const title = ;"] }
+        }));
+        let (markers, events) = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap();
+        let js = generate_entry_js(
+            &ir,
+            "./runtime.js",
+            &markers,
+            &events,
+            &BTreeMap::new(),
+            EntryJsOptions::default(),
+        )
+        .unwrap();
+
+        let err = zenith_bundler::utils::validate_js_syntax(&js, "entry").unwrap_err();
+        assert!(err.to_string().contains("`const title = ;`"), "{}", err);
+    }
+
+    fn payload_from_json(value: serde_json::Value) -> BundlerInput {
+        serde_json::from_value(value).expect("valid payload JSON")
+    }
+
+    #[test]
+    fn state_table_emits_key_map_in_index_order() {
+        let state = vec![
+            CompilerStateBinding {
+                key: "total".into(),
+                value: "0".into(),
+            },
+            CompilerStateBinding {
+                key: "label".into(),
+                value: "'x'".into(),
+            },
+        ];
+
+        let js = generate_state_table_js(&state, true).unwrap();
+        assert!(js.contains("const __zenith_state_keys = Object.freeze({\"total\":0,\"label\":1});"));
+        assert!(!generate_state_table_js(&state, false)
+            .unwrap()
+            .contains("__zenith_state_keys"));
+    }
+
+    #[test]
+    fn expression_schema_rejects_kind_mismatch() {
+        let payload = payload_from_json(serde_json::json!({
+            "route": "/",
+            "file": "index.zen",
+            "ir": {
+                "ir_version": 1,
+                "html": "<h1 data-zx-e=\"0\"></h1><p data-zx-e=\"1\"></p>",
+                "expressions": ["title", "count"]
+            },
+            "expression_schema": ["value", "event"]
+        }));
+        let (markers, _) =
+            derive_binding_tables(&payload.ir.html, payload.ir.expressions.len()).unwrap();
+
+        let err = validate_expression_schema(
+            payload.expression_schema.as_deref().unwrap(),
+            &payload.ir,
+            &markers,
+        )
+        .unwrap_err();
+        assert!(err.contains("expression_schema[1] expects Event"), "{}", err);
+        assert!(err.contains("bound as Value"), "{}", err);
+    }
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn cli_flags_override_config_file() {
+        let mut config = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut config,
+            br#"{ "base_path": "/docs", "validate_js": true, "future_option": 1 }"#,
+        )
+        .unwrap();
+        let config_path = config.path().to_string_lossy().to_string();
+
+        let from_file =
+            parse_args_from(args(&["--out-dir", "dist", "--config", &config_path])).unwrap();
+        assert_eq!(from_file.base_path, "/docs/");
+        assert!(from_file.validate_js);
+
+        let overridden = parse_args_from(args(&[
+            "--config",
+            &config_path,
+            "--out-dir",
+            "dist",
+            "--base",
+            "/app/",
+        ]))
+        .unwrap();
+        assert_eq!(overridden.base_path, "/app/");
+        assert!(overridden.validate_js);
+
+        // A `--no-*` flag turns off what the config enables; the last of
+        // a flag and its negation wins
+        let disabled = parse_args_from(args(&[
+            "--config",
+            &config_path,
+            "--out-dir",
+            "dist",
+            "--no-validate-js",
+        ]))
+        .unwrap();
+        assert!(!disabled.validate_js);
+        let last_wins = parse_args_from(args(&[
+            "--out-dir",
+            "dist",
+            "--strict",
+            "--no-strict",
+            "--legacy",
+        ]))
+        .unwrap();
+        assert!(!last_wins.strict);
+        assert!(last_wins.legacy);
+    }
+
+    #[test]
+    fn input_flag_reads_payload_from_file() {
+        let parsed = parse_args_from(args(&["--out-dir", "dist"])).unwrap();
+        assert_eq!(parsed.input, None);
+        assert!(parse_args_from(args(&["--out-dir", "dist", "--input"])).is_err());
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, br#"{ "route": "/" }"#).unwrap();
+        let path = file.path().to_string_lossy().to_string();
+        let parsed = parse_args_from(args(&["--input", &path, "--out-dir", "dist"])).unwrap();
+        assert_eq!(parsed.input.as_deref(), Some(file.path()));
+        assert_eq!(
+            read_payload(parsed.input.as_deref()).unwrap(),
+            r#"{ "route": "/" }"#
+        );
+
+        // Missing and empty files name the file, unlike the empty-stdin error
+        let empty = tempfile::NamedTempFile::new().unwrap();
+        let err = read_payload(Some(empty.path())).unwrap_err();
+        assert!(err.starts_with("--input file"), "{}", err);
+        assert!(err.ends_with("is empty"), "{}", err);
+        let missing = empty.path().with_extension("missing");
+        let err = read_payload(Some(&missing)).unwrap_err();
+        assert!(err.starts_with("failed to read --input file"), "{}", err);
+    }
+
+    #[test]
+    fn format_flag_selects_json_diagnostics() {
+        let human = parse_args_from(args(&["--out-dir", "dist"])).unwrap();
+        assert_eq!(human.format, OutputFormat::Human);
+        let json = parse_args_from(args(&["--out-dir", "dist", "--format", "json"])).unwrap();
+        assert_eq!(json.format, OutputFormat::Json);
+        assert!(parse_args_from(args(&["--out-dir", "dist", "--format", "xml"])).is_err());
+
+        let diagnostic = Diagnostic {
+            level: DiagnosticLevel::Error,
+            message: "boom".into(),
+            context: None,
+            code: None,
+        };
+        let line = serde_json::to_string(&JsonDiagnostic::from(&diagnostic)).unwrap();
+        assert_eq!(line, r#"{"level":"error","message":"boom","context":null}"#);
+        // The library type keeps its own representation
+        let library = serde_json::to_value(&diagnostic).unwrap();
+        assert_eq!(library["level"], "Error");
+    }
+
+    #[test]
+    fn duplicate_marker_index_names_both_attributes() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<p data-zx-e=\"0\"></p><button data-zx-on-click=\"0\"></button>",
+            "expressions": ["count"]
+        }));
+        let err = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap_err();
+        assert!(err.contains("duplicate marker index 0"), "{}", err);
+        assert!(
+            err.contains(r#"data-zx-e (selector [data-zx-e~="0"])"#),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains(r#"data-zx-on-click (selector [data-zx-on-click="0"])"#),
+            "{}",
+            err
+        );
+    }
+
+    /// Fake-DOM harness: import the entry, click every bound element, and
+    /// print the resulting node state as JSON.
+    const HYDRATE_HARNESS_JS: &str = r#"
+const nodes = new Map();
+globalThis.document = {
+  querySelectorAll(selector) {
+    if (!nodes.has(selector)) {
+      nodes.set(selector, [{ textContent: '', attrs: {}, listeners: {},
+        setAttribute(name, value) { this.attrs[name] = value; },
+        removeAttribute(name) { delete this.attrs[name]; },
+        addEventListener(event, fn) { this.listeners[event] = fn; },
+        removeEventListener(event) { delete this.listeners[event]; } }]);
+    }
+    return nodes.get(selector);
+  }
+};
+await import(process.argv[2]);
+for (const [, list] of nodes) for (const node of list) for (const fn of Object.values(node.listeners)) fn({});
+const out = {};
+for (const [selector, list] of [...nodes].sort()) out[selector] = list.map((n) => ({ text: n.textContent, attrs: n.attrs }));
+console.log(JSON.stringify(out));
+"#;
+
+    /// Run `node` with `args` and return its output, failing the test when
+    /// node cannot be started or exits unsuccessfully. The harness tests
+    /// need node on `PATH`; a missing binary must not pass as a skip.
+    fn run_node(args: &[&dyn AsRef<std::ffi::OsStr>]) -> std::process::Output {
+        let output = std::process::Command::new("node")
+            .args(args.iter().map(|arg| arg.as_ref()))
+            .output()
+            .unwrap_or_else(|e| panic!("node is required for this test but failed to start: {e}"));
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    fn hydrate_with_node(entry_js: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("runtime.mjs"), generate_runtime_module_js()).unwrap();
+        std::fs::write(dir.path().join("entry.mjs"), entry_js).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), HYDRATE_HARNESS_JS).unwrap();
+
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("entry.mjs"),
+        ]);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn columnar_bindings_hydrate_like_object_bindings() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<p data-zx-e=\"0\"></p><a data-zx-href=\"1\"></a><span data-zx-e=\"2\"></span><button data-zx-on-click=\"3\"></button>",
+            "expressions": ["'hi'", "href", "count", "inc"],
+            "hoisted": {
+                "code": [
+                    "const count = signal(1);",
+                    "const href = '/docs';",
+                    "function inc() { count.set(count.get() + 1); }"
+                ],
+                "state": [
+                    { "key": "count", "value": "count" },
+                    { "key": "href", "value": "href" },
+                    { "key": "inc", "value": "inc" }
+                ]
+            },
+            "signals": [{ "id": 0, "kind": "signal", "state_index": 0 }],
+            "expression_bindings": [
+                { "marker_index": 0, "literal": "hi" },
+                { "marker_index": 1, "state_index": 1 },
+                { "marker_index": 2, "signal_index": 0 },
+                { "marker_index": 3, "state_index": 2 }
+            ]
+        }));
+        let (markers, events) = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap();
+        let entry = |columnar_bindings| {
+            generate_entry_js(
+                &ir,
+                "./runtime.mjs",
+                &markers,
+                &events,
+                &BTreeMap::new(),
+                EntryJsOptions {
+                    columnar_bindings,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let columnar_js = entry(true);
+        assert!(columnar_js.contains("signal_index:new Int32Array([-1,-1,0,-1])"));
+
+        let objects = hydrate_with_node(&entry(false));
+        let columnar = hydrate_with_node(&columnar_js);
+        assert!(objects.contains(r#""text":"2""#), "{}", objects);
+        assert_eq!(objects, columnar);
+    }
+
+    #[test]
+    fn show_marker_toggles_hidden_on_signal_change() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<button data-zx-on-click=\"0\"></button><p data-zx-show=\"1\"></p>",
+            "expressions": ["toggle", "open"],
+            "hoisted": {
+                "code": [
+                    "const open = signal(true);",
+                    "function toggle() { open.set(!open.get()); }"
+                ],
+                "state": [
+                    { "key": "open", "value": "open" },
+                    { "key": "toggle", "value": "toggle" }
+                ]
+            },
+            "signals": [{ "id": 0, "kind": "signal", "state_index": 0 }],
+            "expression_bindings": [
+                { "marker_index": 0, "state_index": 1 },
+                { "marker_index": 1, "signal_index": 0 }
+            ]
+        }));
+        let (markers, events) = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap();
+        assert!(matches!(markers[1].kind, MarkerKind::Show));
+        assert_eq!(marker_attribute(&markers[1]), "data-zx-show");
+        let entry = generate_entry_js(
+            &ir,
+            "./runtime.mjs",
+            &markers,
+            &events,
+            &BTreeMap::new(),
+            EntryJsOptions::default(),
+        )
+        .unwrap();
+
+        // Rendered visible; the click flips the signal and the subscription
+        // re-renders the marker hidden
+        let output = hydrate_with_node(&entry);
+        assert!(
+            output.contains(r#""[data-zx-show=\"1\"]":[{"text":"","attrs":{"hidden":""}}]"#),
+            "{}",
+            output
+        );
+    }
+
+    /// Fake-DOM harness for re-hydration: evaluate the entry twice (the
+    /// second import re-runs `hydrate` against the same runtime), then set
+    /// the first run's signal and the second's, printing the marker text
+    /// after each.
+    const REHYDRATE_HARNESS_JS: &str = r#"
+import { pathToFileURL } from 'node:url';
+const nodes = new Map();
+globalThis.document = {
+  querySelectorAll(selector) {
+    if (!nodes.has(selector)) {
+      nodes.set(selector, [{ textContent: '', attrs: {},
+        setAttribute(name, value) { this.attrs[name] = value; },
+        removeAttribute(name) { delete this.attrs[name]; } }]);
+    }
+    return nodes.get(selector);
+  }
+};
+const texts = () => [...nodes].sort().map(([, list]) => list[0].textContent);
+const entry = pathToFileURL(process.argv[2]).href;
+await import(entry);
+await import(entry + '?rehydrate');
+const [stale, live] = globalThis.__counts;
+stale.set(5);
+const afterStale = texts();
+live.set(7);
+console.log(JSON.stringify({ afterStale, afterLive: texts() }));
+"#;
+
+    #[test]
+    fn rehydrate_tears_down_previous_signal_subscriptions() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<p data-zx-e=\"0\"></p><span data-zx-e=\"1\"></span>",
+            "expressions": ["count", "count"],
+            "hoisted": {
+                "code": [
+                    "const count = signal(0);",
+                    "(globalThis.__counts ||= []).push(count);"
+                ],
+                "state": [{ "key": "count", "value": "count" }]
+            },
+            "signals": [{ "id": 0, "kind": "signal", "state_index": 0 }],
+            "expression_bindings": [
+                { "marker_index": 0, "signal_index": 0 },
+                { "marker_index": 1, "signal_index": 0 }
+            ]
+        }));
+        let (markers, events) = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap();
+        let entry = generate_entry_js(
+            &ir,
+            "./runtime.mjs",
+            &markers,
+            &events,
+            &BTreeMap::new(),
+            EntryJsOptions::default(),
+        )
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("runtime.mjs"), generate_runtime_module_js()).unwrap();
+        std::fs::write(dir.path().join("entry.mjs"), entry).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), REHYDRATE_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("entry.mjs"),
+        ]);
+        // The first hydrate's signal no longer drives either marker
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            r#"{"afterStale":["0","0"],"afterLive":["7","7"]}"#
+        );
+    }
+
+    #[test]
+    fn runtime_sanitizes_attribute_values() {
+        let runtime = generate_runtime_module_js();
+        assert!(runtime.contains("node.setAttribute(attrName, __sanitizeAttributeValue(value));"));
+        assert!(runtime.contains("node.setAttribute('style', __sanitizeAttributeValue(value));"));
+        assert!(runtime.contains(": __sanitizeAttributeValue(value);"));
+
+        let start = runtime.find("function __sanitizeAttributeValue").unwrap();
+        let end = start + runtime[start..].find("\n}\n").unwrap() + 2;
+        let script = format!(
+            "{}\nconsole.log(JSON.stringify(__sanitizeAttributeValue('a\\u0000b\\r\\nc\\rd\\ne')));",
+            &runtime[start..end]
+        );
+        let output = run_node(&[&"-e", &script]);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            r#""ab\nc\nd\ne""#
+        );
+    }
+
+    /// Fake-DOM harness for list markers: hydrate, reverse the list, remove
+    /// the first row through its own listener, and print the rows after
+    /// each step (node ids show which rows were reused).
+    const LIST_HARNESS_JS: &str = r#"
+let nextId = 0;
+function element(tagName, attrs = {}, children = []) {
+  const node = {
+    tagName, attrs: { ...attrs }, children: [], parentNode: null, textContent: '', listeners: {}, id: nextId++,
+    getAttribute(name) { return name in this.attrs ? this.attrs[name] : null; },
+    getAttributeNames() { return Object.keys(this.attrs); },
+    setAttribute(name, value) { this.attrs[name] = String(value); },
+    removeAttribute(name) { delete this.attrs[name]; },
+    addEventListener(event, fn) { this.listeners[event] = fn; },
+    removeEventListener(event) { delete this.listeners[event]; },
+    appendChild(child) {
+      if (child.parentNode) child.parentNode.removeChild(child);
+      this.children.push(child);
+      child.parentNode = this;
+      return child;
+    },
+    removeChild(child) {
+      this.children.splice(this.children.indexOf(child), 1);
+      child.parentNode = null;
+      return child;
+    },
+    querySelectorAll() {
+      const out = [];
+      const walk = (n) => { for (const c of n.children) { out.push(c); walk(c); } };
+      walk(this);
+      return out;
+    },
+    querySelector(tag) { return this.querySelectorAll().find((n) => n.tagName === tag) || null; },
+    cloneNode() { return element(tagName, this.attrs, this.children.map((c) => c.cloneNode())); },
+  };
+  for (const child of children) node.appendChild(child);
+  return node;
+}
+const template = element('template');
+template.content = { firstElementChild: element('li', {}, [
+  element('span', { 'data-zx-item': 'label' }),
+  element('button', { 'data-zx-item-on-click': 'remove' }),
+]) };
+const list = element('ul', {}, [template]);
+const reverse = element('button');
+globalThis.document = {
+  querySelectorAll(selector) { return selector.includes('data-zx-list') ? [list] : [reverse]; }
+};
+const rows = () => list.children.filter((n) => n.tagName === 'li').map((li) => ({
+  id: li.id, label: li.children[0].textContent, bound: typeof li.children[1].listeners.click === 'function',
+}));
+await import(process.argv[2]);
+const steps = [rows()];
+reverse.listeners.click({});
+steps.push(rows());
+const removed = list.children.find((n) => n.tagName === 'li');
+removed.children[1].listeners.click({});
+steps.push(rows());
+console.log(JSON.stringify({ steps, removed: { attached: removed.parentNode !== null, listeners: Object.keys(removed.children[1].listeners) } }));
+"#;
+
+    #[test]
+    fn list_marker_reuses_keyed_rows() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<ul data-zx-list=\"0\"><template><li><span data-zx-item=\"label\"></span><button data-zx-item-on-click=\"remove\"></button></li></template></ul><button data-zx-on-click=\"1\"></button>",
+            "expressions": ["todos", "reverse"],
+            "hoisted": {
+                "code": [
+                    "const todos = signal([]);",
+                    "function todo(key, label) { return { key, label, remove() { todos.set(todos.get().filter((t) => t.key !== key)); } }; }",
+                    "todos.set([todo('a', 'A'), todo('b', 'B'), todo('c', 'C')]);",
+                    "function reverse() { todos.set([...todos.get()].reverse()); }"
+                ],
+                "state": [
+                    { "key": "todos", "value": "todos" },
+                    { "key": "reverse", "value": "reverse" }
+                ]
+            },
+            "signals": [{ "id": 0, "kind": "signal", "state_index": 0 }],
+            "expression_bindings": [
+                { "marker_index": 0, "signal_index": 0 },
+                { "marker_index": 1, "state_index": 1 }
+            ]
+        }));
+        let (markers, events) = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap();
+        assert_eq!(markers.len(), 2);
+        assert!(matches!(markers[0].kind, MarkerKind::List));
+        assert_eq!(marker_attribute(&markers[0]), "data-zx-list");
+        let entry = generate_entry_js(
+            &ir,
+            "./runtime.mjs",
+            &markers,
+            &events,
+            &BTreeMap::new(),
+            EntryJsOptions::default(),
+        )
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("runtime.mjs"), generate_runtime_module_js()).unwrap();
+        std::fs::write(dir.path().join("entry.mjs"), entry).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), LIST_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("entry.mjs"),
+        ]);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let steps = report["steps"].as_array().unwrap();
+        let labels = |step: &serde_json::Value| -> Vec<String> {
+            step.as_array()
+                .unwrap()
+                .iter()
+                .map(|row| row["label"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let ids = |step: &serde_json::Value| -> Vec<u64> {
+            step.as_array()
+                .unwrap()
+                .iter()
+                .map(|row| row["id"].as_u64().unwrap())
+                .collect()
+        };
+
+        assert_eq!(labels(&steps[0]), ["A", "B", "C"]);
+        assert_eq!(labels(&steps[1]), ["C", "B", "A"]);
+        assert_eq!(labels(&steps[2]), ["B", "A"]);
+        // Reordering and removal move existing rows instead of recreating them
+        let mut reversed = ids(&steps[0]);
+        reversed.reverse();
+        assert_eq!(ids(&steps[1]), reversed);
+        assert_eq!(ids(&steps[2]), reversed[1..]);
+        assert!(steps
+            .iter()
+            .flat_map(|step| step.as_array().unwrap())
+            .all(|row| row["bound"] == true));
+        // The removed row is detached with its listener released
+        assert_eq!(report["removed"]["attached"], false);
+        assert_eq!(report["removed"]["listeners"], serde_json::json!([]));
+    }
+
+    /// Fake-DOM harness for model bindings: hydrate, type into the input,
+    /// and print the DOM state after each step.
+    const MODEL_HARNESS_JS: &str = r#"
+const input = { type: 'text', value: '', listeners: {},
+  addEventListener(event, fn) { this.listeners[event] = fn; },
+  removeEventListener(event) { delete this.listeners[event]; } };
+const text = { textContent: '' };
+globalThis.document = {
+  querySelectorAll(selector) { return selector.includes('model') ? [input] : [text]; }
+};
+await import(process.argv[2]);
+const steps = [{ value: input.value, text: text.textContent }];
+input.value = 'Grace';
+input.listeners.input({});
+steps.push({ value: input.value, text: text.textContent });
+console.log(JSON.stringify({ steps, events: Object.keys(input.listeners) }));
+"#;
+
+    #[test]
+    fn model_marker_binds_input_both_ways() {
+        let ir = ir_from_json(serde_json::json!({
+            "ir_version": 1,
+            "html": "<input data-zx-model=\"0\"><p data-zx-e=\"1\"></p>",
+            "expressions": ["name", "name"],
+            "hoisted": {
+                "code": ["const name = signal('Ada');"],
+                "state": [{ "key": "name", "value": "name" }]
+            },
+            "signals": [{ "id": 0, "kind": "signal", "state_index": 0 }],
+            "expression_bindings": [
+                { "marker_index": 0, "signal_index": 0 },
+                { "marker_index": 1, "signal_index": 0 }
+            ]
+        }));
+        let (markers, events) = derive_binding_tables(&ir.html, ir.expressions.len()).unwrap();
+        assert!(matches!(markers[0].kind, MarkerKind::Model));
+        assert!(events.is_empty());
+        let entry = generate_entry_js(
+            &ir,
+            "./runtime.mjs",
+            &markers,
+            &events,
+            &BTreeMap::new(),
+            EntryJsOptions::default(),
+        )
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("runtime.mjs"), generate_runtime_module_js()).unwrap();
+        std::fs::write(dir.path().join("entry.mjs"), entry).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), MODEL_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("entry.mjs"),
+        ]);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            r#"{"steps":[{"value":"Ada","text":"Ada"},{"value":"Grace","text":"Grace"}],"events":["input"]}"#
+        );
+    }
+
+    #[test]
+    fn strict_rejects_non_module_injected_script() {
+        let html = ensure_document_html("<h1>hi</h1>");
+        let html = inject_script_once(&html, "/assets/runtime.js", "data-zx-runtime");
+        assert!(validate_injected_scripts(&html).is_ok());
+
+        // Author scripts are not the bundler's concern
+        let authored = html.replace("</body>", "<script src=\"/analytics.js\"></script></body>");
+        assert!(validate_injected_scripts(&authored).is_ok());
+
+        let classic = html.replace(
+            "</body>",
+            "<script src=\"/assets/page.js\" data-zx-page></script></body>",
+        );
+        let err = validate_injected_scripts(&classic).unwrap_err();
+        assert!(err.contains("data-zx-page"), "{}", err);
+    }
+
+    #[test]
+    fn legacy_flag_pairs_page_script_with_nomodule_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+            "route": "/",
+            "file": "index.zen",
+            "ir": {
+                "ir_version": 1,
+                "html": "<h1 data-zx-e=\"0\"></h1>",
+                "expressions": ["title"]
+            }
+        }))
+        .unwrap();
+        let build = |extra: &[&str]| {
+            let out_dir = dir.path().join(if extra.is_empty() { "modern" } else { "legacy" });
+            let out = out_dir.to_string_lossy().to_string();
+            let mut argv = vec!["--out-dir", out.as_str()];
+            argv.extend_from_slice(extra);
+            let cli = parse_args_from(args(&argv)).unwrap();
+            bundle_route(&cli, &payload, &mut SharedAssets::default()).unwrap();
+            fs::read_to_string(out_dir.join("index.html")).unwrap()
+        };
+
+        let modern = build(&[]);
+        assert!(!modern.contains("nomodule"), "{}", modern);
+
+        let legacy = build(&["--legacy", "--strict"]);
+        let page_src = Regex::new(r#"<script type="module" src="([^"]+)" data-zx-page>"#)
+            .unwrap()
+            .captures(&legacy)
+            .unwrap()[1]
+            .to_string();
+        let fallback = format!("<script nomodule src=\"{page_src}\" data-zx-nomodule></script>");
+        assert!(legacy.contains(&fallback), "{}", legacy);
+
+        // The marker dedups, even though the src is already on the page
+        assert_eq!(inject_nomodule_script_once(&legacy, &page_src), legacy);
+    }
+
+    #[test]
+    fn entry_assets_are_named_after_the_route() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_string_lossy().to_string();
+        let cli = parse_args_from(args(&["--out-dir", &out_dir])).unwrap();
+        let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+            "route": "/blog/:slug",
+            "file": "/home/dev/my site/pages/blog/[slug] #1?.zen",
+            "ir": {
+                "ir_version": 1,
+                "html": "<h1 data-zx-e=\"0\"></h1>",
+                "expressions": ["title"]
+            }
+        }))
+        .unwrap();
+
+        bundle_route(&cli, &payload, &mut SharedAssets::default()).unwrap();
+        let html =
+            fs::read_to_string(dir.path().join(route_to_output_path(&payload.route))).unwrap();
+        let page_src = Regex::new(r#"src="([^"]+)" data-zx-page>"#)
+            .unwrap()
+            .captures(&html)
+            .unwrap()[1]
+            .to_string();
+        assert!(page_src.starts_with("/assets/blog__slug."), "{}", page_src);
+        assert!(!page_src.contains("home"), "{}", page_src);
+        assert_eq!(route_asset_token("/"), "index");
+    }
+
+    #[test]
+    fn document_wrap_flags_override_the_fragment_heuristic() {
+        let dir = tempfile::tempdir().unwrap();
+        let build = |name: &str, html: &str, extra: &[&str]| {
+            let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+                "route": "/",
+                "file": "index.zen",
+                "ir": {
+                    "ir_version": 1,
+                    "html": html,
+                    "expressions": ["title"]
+                }
+            }))
+            .unwrap();
+            let out_dir = dir.path().join(name);
+            let out = out_dir.to_string_lossy().to_string();
+            let mut argv = vec!["--out-dir", out.as_str()];
+            argv.extend_from_slice(extra);
+            let cli = parse_args_from(args(&argv)).unwrap();
+            bundle_route(&cli, &payload, &mut SharedAssets::default()).unwrap();
+            fs::read_to_string(out_dir.join("index.html")).unwrap()
+        };
+        let fragment = "<h1 data-zx-e=\"0\"></h1>";
+
+        let auto = build("auto", fragment, &[]);
+        assert!(auto.starts_with("<!DOCTYPE html><html>"), "{}", auto);
+
+        // The fragment is written as-is, with the scripts appended after it
+        let raw = build("raw", fragment, &["--no-document-wrap"]);
+        assert!(raw.starts_with(fragment), "{}", raw);
+        assert!(!raw.contains("<html"), "{}", raw);
+        assert!(
+            raw.trim_end().ends_with("data-zx-page></script>"),
+            "{}",
+            raw
+        );
+
+        let document = "<html><body><h1 data-zx-e=\"0\"></h1></body></html>";
+        let forced = build("forced", document, &["--force-document-wrap"]);
+        assert!(
+            forced.starts_with("<!DOCTYPE html><html><head></head><body><html>"),
+            "{}",
+            forced
+        );
+
+        let err = parse_args_from(args(&[
+            "--out-dir",
+            "dist",
+            "--no-document-wrap",
+            "--force-document-wrap",
+        ]))
+        .err()
+        .unwrap();
+        assert!(err.contains("mutually exclusive"), "{}", err);
+    }
+
+    #[test]
+    fn routes_get_their_own_head_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_string_lossy().to_string();
+        let cli = parse_args_from(args(&["--out-dir", &out_dir])).unwrap();
+        let mut shared = SharedAssets::default();
+        for (route, title) in [("/", "Home"), ("/about", "About")] {
+            let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+                "route": route,
+                "file": "page.zen",
+                "head": [
+                    format!("<title>{title}</title>"),
+                    format!("<link rel=\"canonical\" href=\"https://example.com{route}\">"),
+                    format!("<title>{title}</title>")
+                ],
+                "ir": { "ir_version": 1, "html": "<h1>hi</h1>", "expressions": [] }
+            }))
+            .unwrap();
+            bundle_payload(&cli, &payload, &mut shared).unwrap();
+        }
+
+        let home = fs::read_to_string(dir.path().join("index.html")).unwrap();
+        let about = fs::read_to_string(dir.path().join("about").join("index.html")).unwrap();
+        assert_eq!(home.matches("<title>").count(), 1, "{}", home);
+        assert!(home.contains("<title>Home</title>"), "{}", home);
+        assert!(about.contains("<title>About</title>"), "{}", about);
+        assert!(
+            about.contains(r#"href="https://example.com/about""#),
+            "{}",
+            about
+        );
+        assert!(about.find("<title>").unwrap() < about.find("</head>").unwrap());
+    }
+
+    #[test]
+    fn router_manifest_keeps_sibling_dynamic_routes_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_path_buf();
+        for route in ["/users/:id", "/users/:slug/detail", "/users"] {
+            let output = route_to_output_path(route).to_string_lossy().replace('\\', "/");
+            upsert_router_manifest(
+                &out_dir,
+                RouterRouteEntry {
+                    path: route.to_string(),
+                    segments: parse_route(route).unwrap(),
+                    output,
+                    html: String::new(),
+                    expressions: Vec::new(),
+                    content_hash: None,
+                },
+            )
+            .unwrap();
+        }
+
+        let manifest: RouterManifest = serde_json::from_str(
+            &fs::read_to_string(out_dir.join("assets").join("router-manifest.json")).unwrap(),
+        )
+        .unwrap();
+        let outputs: Vec<&str> = manifest.routes.iter().map(|r| r.output.as_str()).collect();
+        assert_eq!(
+            outputs,
+            vec![
+                "users/index.html",
+                "users/_id/index.html",
+                "users/_slug/detail/index.html"
+            ]
+        );
+    }
+
+    /// Fake-DOM harness for the router: serve the manifest from argv, visit
+    /// each path via `popstate`, and print the text each render produced.
+    const ROUTER_HARNESS_JS: &str = r#"
+const manifest = JSON.parse(process.argv[3]);
+const listeners = {};
+const rendered = [];
+globalThis.window = {
+  location: { pathname: '/', href: 'http://localhost/' },
+  addEventListener(event, fn) { listeners[event] = fn; },
+  scrollTo() {},
+  dispatchEvent() { return true; },
+};
+globalThis.history = { state: null, pushState() {} };
+globalThis.fetch = async () => ({ ok: true, json: async () => manifest });
+globalThis.document = {
+  addEventListener() {},
+  createElement() {
+    const node = { textContent: '', getAttribute: () => '0' };
+    return {
+      set innerHTML(_) {},
+      content: {
+        querySelectorAll: (selector) => (selector === '[data-zx-e]' ? [node] : []),
+        cloneNode: () => node,
+      },
+    };
+  },
+  getElementById() {
+    return { set innerHTML(_) {}, appendChild(node) { rendered.push(node.textContent); } };
+  },
+};
+await import(process.argv[2]);
+for (const path of process.argv.slice(4)) {
+  window.location.pathname = path;
+  rendered.push(path);
+  listeners.popstate({ state: null });
+  await new Promise((resolve) => setTimeout(resolve, 0));
+}
+console.log(JSON.stringify(rendered));
+"#;
+
+    /// Fake-browser harness for navigation: scroll, follow a link, go back
+    /// and forward, logging every scroll and `zenith:navigate` event.
+    const NAVIGATE_HARNESS_JS: &str = r#"
+const manifest = JSON.parse(process.argv[3]);
+const listeners = {};
+const documentListeners = {};
+const log = [];
+const entries = [{ state: null, path: '/' }];
+let index = 0;
+globalThis.window = {
+  location: { pathname: '/', href: 'http://localhost/', origin: 'http://localhost' },
+  scrollX: 0,
+  scrollY: 0,
+  addEventListener(event, fn) { listeners[event] = fn; },
+  scrollTo(x, y) { this.scrollX = x; this.scrollY = y; log.push(['scroll', x, y]); },
+  dispatchEvent(event) { log.push([event.type, event.detail]); return true; },
+};
+globalThis.history = {
+  get state() { return entries[index].state; },
+  pushState(state, _, path) {
+    entries.splice(index + 1, entries.length, { state, path });
+    index += 1;
+    window.location.pathname = path;
+  },
+};
+globalThis.fetch = async () => ({ ok: true, json: async () => manifest });
+globalThis.document = {
+  addEventListener(event, fn) { documentListeners[event] = fn; },
+  createElement() {
+    return {
+      set innerHTML(_) {},
+      content: { querySelectorAll: () => [], cloneNode: () => ({}) },
+    };
+  },
+  getElementById() { return { set innerHTML(_) {}, appendChild() {} }; },
+};
+const tick = () => new Promise((resolve) => setTimeout(resolve, 0));
+const go = async (delta) => {
+  index += delta;
+  window.location.pathname = entries[index].path;
+  listeners.popstate({ state: entries[index].state });
+  await tick();
+};
+await import(process.argv[2]);
+window.scrollY = 300;
+const anchor = { href: 'http://localhost/users/1', target: '', getAttribute: () => '/users/1', hasAttribute: () => false };
+documentListeners.click({ target: { closest: () => anchor }, preventDefault() {} });
+await tick();
+window.scrollY = 50;
+await go(-1);
+await go(1);
+console.log(JSON.stringify(log));
+"#;
+
+    #[test]
+    fn router_navigation_restores_scroll_and_dispatches_event() {
+        let manifest = RouterManifest {
+            routes: vec![
+                RouterRouteEntry::new("/", "<p></p>".into(), Vec::new()).unwrap(),
+                RouterRouteEntry::new(
+                    "/users/:id",
+                    "<p data-zx-e=\"0\"></p>".into(),
+                    vec!["params.id".into()],
+                )
+                .unwrap(),
+            ],
+        };
+        let router_js = generate_router_runtime_js("/");
+        assert!(router_js.contains("new CustomEvent('zenith:navigate'"));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("router.js"), router_js).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), NAVIGATE_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("router.js"),
+            &serde_json::to_string(&manifest).unwrap(),
+        ]);
+        // A link scrolls to top; back and forward restore each entry's
+        // position. Every render is followed by the event
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            concat!(
+                r#"[["scroll",0,0],["zenith:navigate",{"path":"/users/1","params":{"id":"1"}}],"#,
+                r#"["scroll",0,300],["zenith:navigate",{"path":"/","params":{}}],"#,
+                r#"["scroll",0,50],["zenith:navigate",{"path":"/users/1","params":{"id":"1"}}]]"#
+            )
+        );
+    }
+
+    #[test]
+    fn router_matches_catch_all_and_optional_segments() {
+        let route = |path: &str, expression: &str| RouterRouteEntry {
+            path: path.to_string(),
+            segments: parse_route(path).unwrap(),
+            output: route_to_output_path(path).to_string_lossy().replace('\\', "/"),
+            html: "<p data-zx-e=\"0\"></p>".into(),
+            expressions: vec![expression.into()],
+            content_hash: None,
+        };
+        let manifest = RouterManifest {
+            routes: vec![
+                route("/files/*", "params.rest"),
+                route("/files/:id", "params.id"),
+                route("/blog/:slug?", "params.slug"),
+            ],
+        };
+        assert_eq!(manifest.routes[0].output, "files/__rest/index.html");
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("router.js"), generate_router_runtime_js("/")).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), ROUTER_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("router.js"),
+            &serde_json::to_string(&manifest).unwrap(),
+            &"/files/a/b/c",
+            &"/files/x",
+            &"/files",
+            &"/blog",
+            &"/blog/hello",
+            &"/blog/a/b",
+        ]);
+        // Each visited path is followed by the text its render produced
+        // (none when no route matched)
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            r#"["/files/a/b/c","a/b/c","/files/x","x","/files","","/blog","","/blog/hello","hello","/blog/a/b"]"#
+        );
+    }
+
+    #[test]
+    fn router_matches_paths_under_the_base_path() {
+        let route = |path: &str| RouterRouteEntry {
+            path: path.to_string(),
+            segments: parse_route(path).unwrap(),
+            output: route_to_output_path(path).to_string_lossy().replace('\\', "/"),
+            html: "<p data-zx-e=\"0\"></p>".into(),
+            expressions: vec!["params.id".into()],
+            content_hash: None,
+        };
+        let manifest = RouterManifest {
+            routes: vec![route("/"), route("/users/:id")],
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let router_js = generate_router_runtime_js("/app/");
+        assert!(router_js.contains(r#"const BASE_PATH = "/app/";"#));
+        std::fs::write(dir.path().join("router.js"), router_js).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), ROUTER_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("router.js"),
+            &serde_json::to_string(&manifest).unwrap(),
+            &"/app/users/7",
+            &"/app",
+            &"/app/",
+            &"/users/7",
+            &"/application/users/7",
+        ]);
+        // Only paths under the base render; the base itself is `/`
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            r#"["/app/users/7","7","/app","","/app/","","/users/7","/application/users/7"]"#
+        );
+    }
+
+    #[test]
+    fn router_warns_when_manifest_entry_is_out_of_sync() {
+        let route = |path: &str| {
+            RouterRouteEntry::new(
+                path,
+                "<p data-zx-e=\"0\"></p>".into(),
+                vec!["params.id".into()],
+            )
+            .unwrap()
+        };
+        let mut stale = route("/stale/:id");
+        stale.html = "<h1 data-zx-e=\"0\"></h1>".into();
+        let manifest = RouterManifest {
+            routes: vec![route("/fresh/:id"), stale],
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("router.js"), generate_router_runtime_js("/")).unwrap();
+        std::fs::write(dir.path().join("harness.mjs"), ROUTER_HARNESS_JS).unwrap();
+        let output = run_node(&[
+            &dir.path().join("harness.mjs"),
+            &dir.path().join("router.js"),
+            &serde_json::to_string(&manifest).unwrap(),
+            &"/fresh/a",
+            &"/stale/b",
+        ]);
+        // Both routes still render; only the stale one is reported
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            r#"["/fresh/a","a","/stale/b","b"]"#
+        );
+        let warnings = String::from_utf8(output.stderr).unwrap();
+        assert!(warnings.contains("/stale/:id is out of sync"), "{}", warnings);
+        assert!(!warnings.contains("/fresh/:id"), "{}", warnings);
+    }
+
+    #[test]
+    fn colliding_routes_are_rejected_before_anything_is_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("dist");
+        let input = dir.path().join("input.json");
+        let page = |route: &str| {
+            serde_json::json!({
+                "route": route,
+                "file": "page.zen",
+                "router": true,
+                "ir": {
+                    "ir_version": 1,
+                    "html": "<h1 data-zx-e=\"0\"></h1>",
+                    "expressions": ["params.id"]
+                }
+            })
+        };
+        let cli = || {
+            parse_args_from(args(&[
+                "--out-dir",
+                &out_dir.to_string_lossy(),
+                "--input",
+                &input.to_string_lossy(),
+            ]))
+            .unwrap()
+        };
+
+        // Within a batch
+        fs::write(
+            &input,
+            serde_json::json!([page("/blog/:slug?"), page("/blog/:slug")]).to_string(),
+        )
+        .unwrap();
+        let err = run(cli()).unwrap_err();
+        assert!(
+            err.message.contains("both write `blog/_slug/index.html`"),
+            "{}",
+            err.message
+        );
+        assert!(!out_dir.exists());
+
+        // Against a route an earlier run put in the router manifest
+        fs::write(&input, page("/users/:id").to_string()).unwrap();
+        run(cli()).unwrap();
+        let shell = out_dir.join("users").join("_id").join("index.html");
+        let before = fs::read_to_string(&shell).unwrap();
+        fs::write(&input, page("/users/_id").to_string()).unwrap();
+        let err = run(cli()).unwrap_err();
+        assert!(err.message.contains("Route collision"), "{}", err.message);
+        assert_eq!(fs::read_to_string(&shell).unwrap(), before);
+    }
+
+    #[test]
+    fn report_lists_only_files_written_by_this_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.json");
+        let page = |route: &str| {
+            serde_json::json!({
+                "route": route,
+                "file": "page.zen",
+                "router": true,
+                "ir": {
+                    "ir_version": 1,
+                    "html": "<h1 data-zx-e=\"0\"></h1>",
+                    "expressions": ["params.id"]
+                }
+            })
+        };
+        fs::write(
+            &input,
+            serde_json::json!([page("/users/:id"), page("/posts/:id")]).to_string(),
+        )
+        .unwrap();
+        let out_dir = dir.path().join("dist");
+        let cli = || {
+            parse_args_from(args(&[
+                "--out-dir",
+                &out_dir.to_string_lossy(),
+                "--input",
+                &input.to_string_lossy(),
+            ]))
+            .unwrap()
+        };
+        let count = |written: &[WrittenFile], kind: WrittenKind| {
+            written.iter().filter(|file| file.kind == kind).count()
+        };
+
+        // The manifest is rewritten per route but reported once
+        let written = run(cli()).unwrap();
+        assert_eq!(count(&written, WrittenKind::Runtime), 1);
+        assert_eq!(count(&written, WrittenKind::RouterManifest), 1);
+
+        // The runtime asset is already on disk, so the next run skips it
+        let written = run(cli()).unwrap();
+        assert_eq!(count(&written, WrittenKind::Runtime), 0);
+        assert_eq!(count(&written, WrittenKind::Entry), 2);
+    }
+
+    #[test]
+    fn report_lists_every_written_file_with_asset_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.json");
+        let report = dir.path().join("report.json");
+        fs::write(
+            &input,
+            serde_json::json!({
+                "route": "/users/:id",
+                "file": "user.zen",
+                "router": true,
+                "ir": {
+                    "ir_version": 1,
+                    "html": "<h1 data-zx-e=\"0\"></h1>",
+                    "expressions": ["params.id"]
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let out_dir = dir.path().join("dist");
+        let cli = parse_args_from(args(&[
+            "--out-dir",
+            &out_dir.to_string_lossy(),
+            "--input",
+            &input.to_string_lossy(),
+            "--report",
+            &report.to_string_lossy(),
+        ]))
+        .unwrap();
+        assert_eq!(cli.report.as_deref(), Some(report.as_path()));
+
+        let written = run(cli).unwrap();
+        write_report(&report, &written).unwrap();
+
+        let summary: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+        let files = summary["files"].as_array().unwrap();
+        let kinds: Vec<&str> = files.iter().map(|file| file["kind"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["runtime", "entry", "router_manifest", "router", "html"]);
+        for file in files {
+            assert_eq!(file["route"], "/users/:id");
+            let path = Path::new(file["path"].as_str().unwrap());
+            assert!(path.is_file(), "{}", path.display());
+            // Hashed assets report the hash their filename carries
+            match file["hash"].as_str() {
+                Some(hash) => {
+                    assert!(path.to_string_lossy().contains(hash));
+                    assert_eq!(hash, content_hash(&fs::read_to_string(path).unwrap()));
+                }
+                None => {
+                    let kind = file["kind"].as_str().unwrap();
+                    assert!(kind == "html" || kind == "router_manifest", "{}", kind);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn batch_payload_shares_runtime_and_names_failing_element() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = parse_args_from(args(&["--out-dir", &dir.path().to_string_lossy()])).unwrap();
+        let page = |route: &str| {
+            serde_json::json!({
+                "route": route,
+                "file": "page.zen",
+                "ir": {
+                    "ir_version": 1,
+                    "html": "<h1 data-zx-e=\"0\"></h1>",
+                    "expressions": ["title"]
+                }
+            })
+        };
+        let raw = format!("\n  {}", serde_json::json!([page("/"), page("/about")]));
+        assert!(is_batch_payload(&raw));
+        assert!(!is_batch_payload(" {}"));
+
+        let mut shared = SharedAssets::default();
+        let mut written = Vec::new();
+        for payload in parse_batch_payload(&raw).unwrap() {
+            written.extend(bundle_route(&cli, &payload, &mut shared).unwrap());
+        }
+        assert!(written
+            .iter()
+            .any(|file| file.path == dir.path().join("about").join("index.html")));
+        assert!(dir.path().join("index.html").is_file());
+        assert!(dir.path().join("about").join("index.html").is_file());
+        let runtimes = fs::read_dir(dir.path().join("assets"))
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("runtime.")
+            })
+            .count();
+        assert_eq!(runtimes, 1);
+        let runtime_import = format!(
+            "from '{}'",
+            runtime_import_specifier(&runtime_asset().rel).unwrap()
+        );
+        let entries: Vec<_> = written
+            .iter()
+            .filter(|file| file.kind == WrittenKind::Entry)
+            .collect();
+        assert_eq!(entries.len(), 2, "{:?}", written);
+        for entry in entries {
+            let entry_js = fs::read_to_string(&entry.path).unwrap();
+            assert!(entry_js.contains(&runtime_import), "{}", entry_js);
+        }
+
+        let bad = serde_json::json!([page("/"), { "route": "/oops" }]).to_string();
+        let err = parse_batch_payload(&bad).unwrap_err();
+        assert!(err.contains("input[1]"), "{}", err);
+    }
+
+    #[test]
+    fn batch_payload_writes_shared_component_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = parse_args_from(args(&["--out-dir", &dir.path().to_string_lossy()])).unwrap();
+        let page = |route: &str| {
+            serde_json::json!({
+                "route": route,
+                "file": "page.zen",
+                "ir": {
+                    "ir_version": 1,
+                    "html": "<div data-zx-c=\"c0\"></div>",
+                    "expressions": [],
+                    "components_scripts": {
+                        "card": {
+                            "hoist_id": "card",
+                            "factory": "createCard",
+                            "code": "export default function createCard() { return {}; }"
+                        }
+                    },
+                    "component_instances": [
+                        { "instance": "c0", "hoist_id": "card", "selector": "[data-zx-c=\"c0\"]" }
+                    ]
+                }
+            })
+        };
+        let raw = serde_json::json!([page("/"), page("/about")]).to_string();
+
+        let mut shared = SharedAssets::default();
+        let mut written = Vec::new();
+        for payload in parse_batch_payload(&raw).unwrap() {
+            written.extend(bundle_route(&cli, &payload, &mut shared).unwrap());
+        }
+
+        let components: Vec<_> = fs::read_dir(dir.path().join("assets"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("component."))
+            .collect();
+        assert_eq!(components.len(), 1, "{:?}", components);
+        let reported: Vec<_> = written
+            .iter()
+            .filter(|file| file.kind == WrittenKind::Component)
+            .collect();
+        assert_eq!(reported.len(), 1, "{:?}", written);
+
+        // Both entries import the one file
+        let import = format!("from './{}'", components[0]);
+        for entry in written.iter().filter(|file| file.kind == WrittenKind::Entry) {
+            let entry_js = fs::read_to_string(&entry.path).unwrap();
+            assert!(entry_js.contains(&import), "{}", entry_js);
+        }
+    }
+
+    #[test]
+    fn runtime_specifier_imports_external_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_string_lossy().to_string();
+        let cli = parse_args_from(args(&[
+            "--out-dir",
+            &out_dir,
+            "--runtime-specifier",
+            "@zenith/runtime",
+        ]))
+        .unwrap();
+        let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+            "route": "/",
+            "file": "page.zen",
+            "ir": {
+                "ir_version": 1,
+                "html": "<h1 data-zx-e=\"0\"></h1>",
+                "expressions": ["title"]
+            }
+        }))
+        .unwrap();
+
+        let mut shared = SharedAssets::default();
+        let written = bundle_route(&cli, &payload, &mut shared).unwrap();
+
+        assert!(shared.runtime_rel.is_none());
+        assert!(!written
+            .iter()
+            .any(|file| file.kind == WrittenKind::Runtime));
+        let entry = written
+            .iter()
+            .find(|file| file.kind == WrittenKind::Entry)
+            .unwrap();
+        let entry_js = fs::read_to_string(&entry.path).unwrap();
+        assert!(entry_js.contains("from '@zenith/runtime'"), "{}", entry_js);
+        let html = fs::read_to_string(dir.path().join("index.html")).unwrap();
+        assert!(!html.contains("data-zx-runtime"), "{}", html);
+    }
+
+    #[test]
+    fn runtime_specifier_rejects_quotes_and_control_characters() {
+        for specifier in ["x';alert(1);//", "x\"y", "a\\b", "line\nbreak", ""] {
+            let err = parse_args_from(args(&[
+                "--out-dir",
+                "dist",
+                "--runtime-specifier",
+                specifier,
+            ]))
+            .unwrap_err();
+            assert!(err.contains("--runtime-specifier"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn base_path_prefixes_injected_scripts_and_manifest_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_string_lossy().to_string();
+        let cli = parse_args_from(args(&["--out-dir", &out_dir, "--base", "app"])).unwrap();
+        let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+            "route": "/users/:id",
+            "file": "user.zen",
+            "router": true,
+            "ir": {
+                "ir_version": 1,
+                "html": "<h1 data-zx-e=\"0\"></h1>",
+                "expressions": ["params.id"]
+            }
+        }))
+        .unwrap();
+
+        let written = bundle_route(&cli, &payload, &mut SharedAssets::default()).unwrap();
+
+        let html = fs::read_to_string(dir.path().join("users/_id/index.html")).unwrap();
+        let srcs: Vec<&str> = html
+            .split("src=\"")
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect();
+        assert_eq!(srcs.len(), 3, "{}", html);
+        assert!(srcs.iter().all(|src| src.starts_with("/app/assets/")), "{:?}", srcs);
+
+        let router = written
+            .iter()
+            .find(|file| file.kind == WrittenKind::Router)
+            .unwrap();
+        let router_js = fs::read_to_string(&router.path).unwrap();
+        assert!(
+            router_js.contains(r#"const MANIFEST_URL = "/app/assets/router-manifest.json";"#),
+            "{}",
+            &router_js[..200]
+        );
+    }
+
+    #[test]
+    fn bundle_errors_keep_their_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_string_lossy().to_string();
+        let cli = parse_args_from(args(&["--out-dir", &out_dir])).unwrap();
+        let payload: BundlerInput = serde_json::from_value(serde_json::json!({
+            "route": "/",
+            "file": "index.zen",
+            "ir": {
+                "ir_version": 1,
+                "html": "<p data-zx-e=\"0\"></p>",
+                "expressions": ["count"],
+                "hoisted": { "state": [{ "key": "total", "value": "0" }] },
+                "expression_bindings": [{ "marker_index": 0, "state_key": "count" }]
+            }
+        }))
+        .unwrap();
+
+        let err = bundle_payload(&cli, &payload, &mut SharedAssets::default())
+            .unwrap_err()
+            .context("input[0]");
+        assert_eq!(err.code, Some("E_VALIDATION"));
+        assert!(
+            err.message.starts_with("input[0]: Validation failed: "),
+            "{}",
+            err
+        );
+
+        // CLI-only failures have no library code
+        let err: CliError = unsupported_ir_version(99).into();
+        assert_eq!(err.code, None);
+    }
+
+    #[test]
+    fn ir_version_dispatch_lists_supported_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_string_lossy().to_string();
+        let cli = parse_args_from(args(&["--out-dir", &out_dir])).unwrap();
+        let payload = |ir_version: u32| -> BundlerInput {
+            serde_json::from_value(serde_json::json!({
+                "route": "/",
+                "file": "index.zen",
+                "ir": {
+                    "ir_version": ir_version,
+                    "html": "<h1 data-zx-e=\"0\"></h1>",
+                    "expressions": ["title"]
+                }
+            }))
+            .unwrap()
+        };
+
+        assert!(SUPPORTED_IR_VERSIONS.contains(&1));
+        assert!(!bundle_payload(&cli, &payload(1), &mut SharedAssets::default())
+            .unwrap()
+            .is_empty());
+
+        let err = bundle_payload(&cli, &payload(99), &mut SharedAssets::default()).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported input.ir.ir_version 99 (supported: 1)");
+        assert_eq!(
+            validate_payload(&payload(99)).unwrap_err().to_string(),
+            format!("Validation failed: {err}")
+        );
+
+        // The runtime accepts exactly the same versions
+        assert!(generate_runtime_module_js()
+            .starts_with("const SUPPORTED_IR_VERSIONS = [1];\n"));
+    }
+
+    #[test]
+    fn component_bootstrap_imports_sorted_instances_in_source_order() {
+        let instances = [
+            ("c0", "card", "[data-zx-c=\"c0\"]"),
+            ("c1", "alert", "[data-zx-c=\"c1\"]"),
+            ("c2", "card", "[data-zx-c=\"c2\"]"),
+        ];
+        let ir = |order: &[usize]| {
+            let component_instances: Vec<serde_json::Value> = order
+                .iter()
+                .map(|&i| {
+                    let (instance, hoist_id, selector) = instances[i];
+                    serde_json::json!({ "instance": instance, "hoist_id": hoist_id, "selector": selector })
+                })
+                .collect();
+            ir_from_json(serde_json::json!({
+                "ir_version": 1,
+                "html": "<div></div>",
+                "expressions": [],
+                "component_instances": component_instances
+            }))
+        };
+        let assets = BTreeMap::from([
+            ("card".to_string(), "assets/card.1111.js".to_string()),
+            ("alert".to_string(), "assets/alert.2222.js".to_string()),
+        ]);
+
+        let (imports, forward) = generate_component_bootstrap_js(&ir(&[0, 1, 2]), &assets).unwrap();
+        let (shuffled_imports, shuffled) =
+            generate_component_bootstrap_js(&ir(&[2, 0, 1]), &assets).unwrap();
+
+        // Imports follow the (sorted) asset map whatever the instance order
+        assert_eq!(imports, shuffled_imports);
+        assert_eq!(
+            imports,
+            "import __zenith_component_alert from './alert.2222.js';\n\
+             import __zenith_component_card from './card.1111.js';\n"
+        );
+
+        // The instance array keeps the IR's instance order
+        let order = |components: &str| -> Vec<usize> {
+            ["\"c0\"", "\"c1\"", "\"c2\""]
+                .iter()
+                .map(|id| components.find(&format!("instance:{id}")).unwrap())
+                .collect()
+        };
+        let forward_positions = order(&forward);
+        let shuffled_positions = order(&shuffled);
+        assert!(forward_positions.windows(2).all(|w| w[0] < w[1]), "{}", forward);
+        // Shuffled as c2, c0, c1
+        assert!(
+            shuffled_positions[2] < shuffled_positions[0]
+                && shuffled_positions[0] < shuffled_positions[1],
+            "{}",
+            shuffled
+        );
+        assert!(forward.contains(
+            r#"{instance:"c1",selector:"[data-zx-c=\"c1\"]",hoist_id:"alert",create:__zenith_component_alert}"#
+        ));
+    }
 }
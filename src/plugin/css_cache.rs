@@ -8,9 +8,12 @@ use std::sync::{Arc, RwLock};
 
 /// Thread-safe CSS cache keyed by page ID.
 /// Includes dirty tracking for HMR live reload.
+///
+/// CSS is stored as `Arc<str>`, so [`CssCache::get_arc`] hands out the
+/// cached text without copying it.
 #[derive(Debug, Clone)]
 pub struct CssCache {
-    inner: Arc<RwLock<HashMap<String, String>>>,
+    inner: Arc<RwLock<HashMap<String, Arc<str>>>>,
     /// Pages that have been modified since last check.
     dirty: Arc<RwLock<HashSet<String>>>,
 }
@@ -29,11 +32,18 @@ impl CssCache {
         let mut map = self.inner.write().expect("CSS cache poisoned");
         let mut dirty = self.dirty.write().expect("CSS dirty set poisoned");
         dirty.insert(page_id.to_string());
-        map.insert(page_id.to_string(), css)
+        map.insert(page_id.to_string(), css.into())
+            .map(|old| old.to_string())
     }
 
-    /// Get the cached CSS for a page.
+    /// Get a copy of the cached CSS for a page.
     pub fn get(&self, page_id: &str) -> Option<String> {
+        self.get_arc(page_id).map(|css| css.to_string())
+    }
+
+    /// Get the cached CSS for a page as a shared reference — a pointer
+    /// clone, for handing the same CSS to many subscribers.
+    pub fn get_arc(&self, page_id: &str) -> Option<Arc<str>> {
         let map = self.inner.read().expect("CSS cache poisoned");
         map.get(page_id).cloned()
     }
@@ -41,7 +51,7 @@ impl CssCache {
     /// Remove CSS for a page (used during HMR invalidation).
     pub fn remove(&self, page_id: &str) -> Option<String> {
         let mut map = self.inner.write().expect("CSS cache poisoned");
-        map.remove(page_id)
+        map.remove(page_id).map(|css| css.to_string())
     }
 
     /// Clear all cached CSS. Used between builds to prevent stale data.
@@ -82,6 +92,26 @@ impl CssCache {
         let mut dirty = self.dirty.write().expect("CSS dirty set poisoned");
         dirty.remove(page_id)
     }
+
+    /// Take every dirty page paired with its current CSS (`None` if the page
+    /// was invalidated), clearing the dirty set.
+    ///
+    /// Both locks are held for the whole snapshot, so a concurrent `insert`
+    /// lands either in this drain or in the next one — never in neither.
+    /// Results are sorted by page ID.
+    pub fn drain_changed(&self) -> Vec<(String, Option<String>)> {
+        let map = self.inner.read().expect("CSS cache poisoned");
+        let mut dirty = self.dirty.write().expect("CSS dirty set poisoned");
+        let mut changed: Vec<(String, Option<String>)> = dirty
+            .drain()
+            .map(|page_id| {
+                let css = map.get(&page_id).map(|css| css.to_string());
+                (page_id, css)
+            })
+            .collect();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        changed
+    }
 }
 
 impl Default for CssCache {
@@ -134,6 +164,22 @@ mod tests {
         assert!(cache.is_empty());
     }
 
+    #[test]
+    fn get_arc_shares_cached_css() {
+        let cache = CssCache::new();
+        cache.insert("home", ".app { color: red }".into());
+        let first = cache.get_arc("home").unwrap();
+        let second = cache.get_arc("home").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(&*first, ".app { color: red }");
+
+        // An overwrite replaces the entry; handed-out references keep theirs
+        cache.insert("home", "new".into());
+        assert_eq!(&*first, ".app { color: red }");
+        assert_eq!(cache.get_arc("home").as_deref(), Some("new"));
+        assert_eq!(cache.get_arc("missing"), None);
+    }
+
     #[test]
     fn thread_safety() {
         use std::thread;
@@ -147,5 +193,10 @@ mod tests {
 
         handle.join().unwrap();
         assert_eq!(cache.get("thread"), Some("data".into()));
+
+        // Shared CSS can be read from other threads
+        let css = cache.get_arc("thread").unwrap();
+        let reader = thread::spawn(move || css.len());
+        assert_eq!(reader.join().unwrap(), 4);
     }
 }
@@ -15,7 +15,9 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use arcstr::ArcStr;
 use dashmap::DashMap;
@@ -24,6 +26,8 @@ use rolldown_plugin::{
     HookLoadArgs, HookLoadOutput, HookResolveIdArgs, HookResolveIdOutput, HookTransformArgs,
     HookTransformOutput, HookUsage, Plugin, SharedLoadPluginContext, SharedTransformPluginContext,
 };
+use rolldown_sourcemap::SourceMap;
+use tokio::sync::Semaphore;
 
 use zenith_compiler::compiler::{compile_structured, CompilerOutput};
 
@@ -38,10 +42,77 @@ pub struct ZenithLoaderConfig {
     pub components: Option<HashMap<String, ComponentDef>>,
     /// Optional pre-compiled metadata for strict validation.
     pub metadata: Option<CompilerOutput>,
+    /// Per-module metadata, keyed by module path (see
+    /// `BundleOptions::module_metadata`). Validated during load in strict mode.
+    pub module_metadata: Option<HashMap<String, CompilerOutput>>,
     /// Whether to fail on invariant violations.
     pub strict: bool,
     /// Dev mode — enables HMR footer injection.
     pub is_dev: bool,
+    /// Emit the `__zenith_page` default export (named exports are always emitted).
+    pub default_export: bool,
+    /// Emit a `.zen` → JS sourcemap for each compiled module.
+    pub sourcemap: bool,
+    /// Embed the normalized `.zen` source as `sourcesContent`.
+    pub sourcemap_sources_content: bool,
+    /// Optional host-supplied source lookup for `.zen` ids.
+    /// Consulted in `load` before falling back to the filesystem.
+    pub content_resolver: Option<ContentResolver>,
+    /// Optional compile cache shared across builds (see [`CompileCache`]).
+    pub compile_cache: Option<CompileCache>,
+    /// Value emitted as `__zenith_contract`; must be one of
+    /// [`utils::SUPPORTED_CONTRACT_VERSIONS`].
+    pub contract_version: String,
+    /// Runtime module specifier. When set, entries are generated with a
+    /// `hydrate({...})` call importing it, and the specifier is kept external.
+    pub runtime_import: Option<String>,
+    /// Bare specifier patterns kept external (see `BundleOptions::external`).
+    pub external: Vec<String>,
+    /// Collapse expression whitespace before emission (see
+    /// `BundleOptions::normalize_expression_whitespace`).
+    pub normalize_expression_whitespace: bool,
+    /// Project root page ids are derived relative to (see
+    /// `BundleOptions::project_root`).
+    pub project_root: Option<PathBuf>,
+    /// Fetch the binding tables from a JSON sidecar instead of inlining
+    /// them (see `BundleOptions::externalize_bindings`).
+    pub externalize_bindings: bool,
+    /// Permits bounding how many `.zen` modules are read and compiled at
+    /// once (see `BundleOptions::concurrency`). `None` leaves them unbounded.
+    pub compile_permits: Option<Arc<Semaphore>>,
+}
+
+/// Compiled `.zen` outputs keyed by module id, each stored with the hash of
+/// the (normalized) source it was compiled from.
+///
+/// A long-lived process (dev server) threads one cache through successive
+/// builds; unchanged sources skip `compile_structured` entirely.
+pub type CompileCache = Arc<DashMap<String, (u64, CompilerOutput)>>;
+
+/// Resolves `.zen` source for a module id from a custom source
+/// (VFS, CMS, network, ...).
+///
+/// Returning `None` falls back to reading the id from disk.
+#[derive(Clone)]
+pub struct ContentResolver(Arc<ResolveFn>);
+
+type ResolveFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+impl ContentResolver {
+    pub fn new(resolve: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(resolve))
+    }
+
+    /// Look up the source for `id`.
+    pub fn resolve(&self, id: &str) -> Option<String> {
+        (self.0)(id)
+    }
+}
+
+impl fmt::Debug for ContentResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ContentResolver(..)")
+    }
 }
 
 /// HMR footer injected in dev mode.
@@ -62,6 +133,9 @@ pub struct ZenithLoader {
     css_cache: Arc<CssCache>,
     /// Compiled outputs keyed by module ID — used for post-build validation.
     compiled_outputs: Arc<DashMap<String, CompilerOutput>>,
+    /// First `BundleError` raised while compiling a module. Rolldown only
+    /// carries the message through, so the build reports this one instead.
+    load_error: Arc<Mutex<Option<BundleError>>>,
 }
 
 impl fmt::Debug for ZenithLoader {
@@ -79,9 +153,17 @@ impl ZenithLoader {
             config,
             css_cache: Arc::new(CssCache::new()),
             compiled_outputs: Arc::new(DashMap::new()),
+            load_error: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Collect CSS into `css_cache` instead of a fresh cache, so a long-lived
+    /// process can keep one cache across builds.
+    pub fn with_css_cache(mut self, css_cache: Arc<CssCache>) -> Self {
+        self.css_cache = css_cache;
+        self
+    }
+
     /// Get the CSS cache (for reading collected CSS after build).
     pub fn css_cache(&self) -> Arc<CssCache> {
         Arc::clone(&self.css_cache)
@@ -91,6 +173,12 @@ impl ZenithLoader {
     pub fn compiled_outputs(&self) -> Arc<DashMap<String, CompilerOutput>> {
         Arc::clone(&self.compiled_outputs)
     }
+
+    /// Get the slot holding the first error raised by `load` (for surfacing
+    /// it when Rolldown fails the build).
+    pub fn load_error(&self) -> Arc<Mutex<Option<BundleError>>> {
+        Arc::clone(&self.load_error)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -117,8 +205,24 @@ impl Plugin for ZenithLoader {
         args: &HookResolveIdArgs<'_>,
     ) -> impl std::future::Future<Output = rolldown_plugin::HookResolveIdReturn> + Send {
         let specifier = args.specifier.to_string();
+        let importer = args.importer.map(|importer| importer.to_string());
+        let runtime_import = self.config.runtime_import.clone();
+        let external = utils::is_external(&specifier, &self.config.external);
 
         async move {
+            // User code must never reach into the virtual namespace
+            check_import_namespace(&specifier, importer.as_deref())?;
+
+            // The runtime is served alongside the bundle, never inlined;
+            // configured packages are left for the host to provide
+            if external || runtime_import.as_deref() == Some(specifier.as_str()) {
+                return Ok(Some(HookResolveIdOutput {
+                    id: ArcStr::from(specifier),
+                    external: Some(ResolvedExternal::Bool(true)),
+                    ..Default::default()
+                }));
+            }
+
             // Handle .zen files
             if specifier.ends_with(".zen") {
                 return Ok(Some(HookResolveIdOutput {
@@ -151,6 +255,7 @@ impl Plugin for ZenithLoader {
         let config = self.config.clone();
         let css_cache = Arc::clone(&self.css_cache);
         let compiled_outputs = Arc::clone(&self.compiled_outputs);
+        let load_error = Arc::clone(&self.load_error);
 
         async move {
             // Handle virtual CSS module
@@ -166,7 +271,8 @@ impl Plugin for ZenithLoader {
             // Handle virtual entry module
             if id.starts_with("\0zenith:entry:") {
                 if let Some(ref metadata) = config.metadata {
-                    let entry_code = utils::generate_virtual_entry(metadata);
+                    let page_id = utils::extract_page_id(&id).unwrap_or("unknown");
+                    let entry_code = generate_entry(metadata, page_id, &config)?;
                     return Ok(Some(HookLoadOutput {
                         code: ArcStr::from(entry_code),
                         ..Default::default()
@@ -174,21 +280,89 @@ impl Plugin for ZenithLoader {
                 }
             }
 
-            // Handle .zen files — compile via sealed compiler API
+            // Handle .zen files — compile via sealed compiler API. Source
+            // comes from the content resolver, then a pre-loaded component
+            // definition, then disk
             if id.ends_with(".zen") {
-                let source = std::fs::read_to_string(&id)
-                    .map_err(|e| anyhow::anyhow!("Failed to read .zen file '{}': {}", id, e))?;
-
-                // Call the sealed compiler API
-                // Delegate to shared compilation function (handles normalization etc.)
-                let (js_code, compiled) = compile_zen_source(&source, &id, &config)?;
+                // Held until the module is compiled
+                let _permit = match config.compile_permits {
+                    Some(ref permits) => Some(
+                        Arc::clone(permits)
+                            .acquire_owned()
+                            .await
+                            .map_err(|e| anyhow::anyhow!("Semaphore closed: {}", e))?,
+                    ),
+                    None => None,
+                };
+                let resolved = config
+                    .content_resolver
+                    .as_ref()
+                    .and_then(|resolver| resolver.resolve(&id))
+                    .or_else(|| {
+                        preloaded_source(config.components.as_ref(), &id).map(str::to_string)
+                    });
+                let source = match resolved {
+                    Some(source) => source,
+                    None => std::fs::read_to_string(&id)
+                        .map_err(|e| anyhow::anyhow!("Failed to read .zen file '{}': {}", id, e))?,
+                };
+
+                // Normalize up front so the sourcemap embeds exactly what is compiled
+                let source = utils::normalize_source(&source);
+
+                // Compilation is CPU-bound, so it runs on the blocking pool and
+                // Rolldown keeps loading sibling modules meanwhile. Output order
+                // is unaffected: Rolldown orders modules by the import graph,
+                // not by load completion
+                let (js_code, compiled) = {
+                    let (task_source, task_id, task_config) =
+                        (source.clone(), id.clone(), config.clone());
+                    tokio::task::spawn_blocking(move || {
+                        compile_zen_source(&task_source, &task_id, &task_config)
+                    })
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Compile task for '{}' failed: {}", id, e))?
+                    .map_err(|err| {
+                        let message = format!("Failed to compile '{}': {}", id, err);
+                        load_error
+                            .lock()
+                            .expect("load error slot poisoned")
+                            .get_or_insert(err);
+                        anyhow::anyhow!(message)
+                    })?
+                };
+                let map = config
+                    .sourcemap
+                    .then(|| zen_source_map(&id, &source, &js_code, &config));
+
+                // Collect the styles of every component the module instantiates
+                // under a component-scoped key, then stitch them (deduplicated)
+                // ahead of the page's own styles
+                let component_css = collect_component_css(&source, &config, &css_cache)?;
+
+                // Page styles go under the same page id `execute_bundle_pages`
+                // reads them back with. A page that lost its styles drops the
+                // entry a previous build left in a shared cache. Either way the
+                // page is marked dirty for CSS live reload, except in dev when
+                // a recompile left its styles untouched
+                let page_id = utils::canonicalize_page_id(&id, config.project_root.as_deref());
+                match utils::stitch_css(&component_css, utils::extract_style_blocks(&source)) {
+                    Some(css)
+                        if config.is_dev
+                            && css_cache.get_arc(&page_id).as_deref() == Some(css.as_str()) => {}
+                    Some(css) => {
+                        css_cache.insert(&page_id, css);
+                    }
+                    None if css_cache.contains(&page_id) => css_cache.invalidate(&page_id),
+                    None => {}
+                }
 
                 // Store compiled output for post-build validation
-                // CSS extraction (if any) would happen here or in transform
                 compiled_outputs.insert(id.clone(), compiled);
 
                 return Ok(Some(HookLoadOutput {
                     code: ArcStr::from(js_code),
+                    map: map.transpose()?,
                     ..Default::default()
                 }));
             }
@@ -238,21 +412,319 @@ impl Plugin for ZenithLoader {
 // Standalone functions (used by both Plugin and non-Plugin codepaths)
 // ---------------------------------------------------------------------------
 
+/// Source pre-loaded in `components` for the `.zen` module `id`: the
+/// `source` of the definition whose `path` is `id`, if it has one. Both
+/// paths are compared in [`normalize_module_path`] form.
+pub(crate) fn preloaded_source<'a>(
+    components: Option<&'a HashMap<String, ComponentDef>>,
+    id: &str,
+) -> Option<&'a str> {
+    let id = normalize_module_path(Path::new(id));
+    components?
+        .values()
+        .find(|def| normalize_module_path(&def.path) == id)
+        .and_then(|def| def.source.as_deref())
+}
+
+/// Metadata in `module_metadata` for the `.zen` module `id`, with paths
+/// compared in [`normalize_module_path`] form.
+fn module_metadata<'a>(
+    module_metadata: Option<&'a HashMap<String, CompilerOutput>>,
+    id: &str,
+) -> Option<&'a CompilerOutput> {
+    let id = normalize_module_path(Path::new(id));
+    module_metadata?
+        .iter()
+        .find(|(path, _)| normalize_module_path(Path::new(path)) == id)
+        .map(|(_, metadata)| metadata)
+}
+
+/// `path` made absolute against the working directory, with `\` read as a
+/// separator and `.` and `..` segments resolved lexically (no filesystem
+/// access, so pre-loaded paths need not exist).
+fn normalize_module_path(path: &Path) -> PathBuf {
+    let path = PathBuf::from(path.to_string_lossy().replace('\\', "/"));
+    let path = std::path::absolute(&path).unwrap_or(path);
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Cache the styles of each component `source` instantiates under its
+/// [`utils::component_css_key`] and return them in tag order.
+///
+/// A component used several times is read once; components without a
+/// `<style>` block contribute nothing.
+fn collect_component_css(
+    source: &str,
+    config: &ZenithLoaderConfig,
+    css_cache: &CssCache,
+) -> Result<Vec<String>, BundleError> {
+    let Some(ref components) = config.components else {
+        return Ok(Vec::new());
+    };
+
+    let mut tags: Vec<&String> = components
+        .keys()
+        .filter(|tag| utils::uses_component(source, tag))
+        .collect();
+    tags.sort();
+
+    let mut collected = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let def = &components[tag];
+        let component_source = match def.source {
+            Some(ref source) => source.clone(),
+            None => std::fs::read_to_string(&def.path).map_err(|e| {
+                BundleError::CompilerError(format!(
+                    "Failed to read component <{}> at '{}': {}",
+                    tag,
+                    def.path.display(),
+                    e
+                ))
+            })?,
+        };
+
+        if let Some(css) = utils::extract_style_blocks(&utils::normalize_source(&component_source))
+        {
+            css_cache.insert(
+                &utils::component_css_key(
+                    &def.path.to_string_lossy(),
+                    config.project_root.as_deref(),
+                ),
+                css.clone(),
+            );
+            collected.push(css);
+        }
+    }
+    Ok(collected)
+}
+
 /// Compile a .zen source string directly (no filesystem).
 /// Used by `bundle.rs` when reading files through tokio.
+///
+/// With a `compile_cache` in `config`, a cached output for `id` is reused
+/// when its source hash matches, and fresh compiles are stored back.
+///
+/// In strict mode, a module with an entry in `module_metadata` is validated
+/// against it here. That covers imported components, which the post-build
+/// check of the page's `metadata` never sees.
 pub fn compile_zen_source(
     source: &str,
-    _id: &str,
-    _config: &ZenithLoaderConfig,
+    id: &str,
+    config: &ZenithLoaderConfig,
 ) -> Result<(String, CompilerOutput), BundleError> {
-    // Normalize newlines to LF for determinism (CRLF -> LF)
-    let source = source.replace("\r\n", "\n");
-    let compiled = compile_structured(&source);
+    // Strip a BOM and normalize newlines to LF for determinism
+    let source = utils::normalize_source(source);
+
+    let compiled = match config.compile_cache {
+        Some(ref cache) => {
+            let hash = source_hash(&source);
+            let cached = cache
+                .get(id)
+                .filter(|entry| entry.0 == hash)
+                .map(|entry| entry.1.clone());
+            match cached {
+                Some(compiled) => compiled,
+                None => {
+                    let compiled = compile_structured(&source);
+                    cache.insert(id.to_string(), (hash, compiled.clone()));
+                    compiled
+                }
+            }
+        }
+        None => compile_structured(&source),
+    };
+    // Applied after the cache, which keeps the compiler's own output
+    let compiled = if config.normalize_expression_whitespace {
+        CompilerOutput {
+            expressions: compiled
+                .expressions
+                .iter()
+                .map(|expr| utils::normalize_expression_whitespace(expr))
+                .collect(),
+            ..compiled
+        }
+    } else {
+        compiled
+    };
+
+    if config.strict {
+        if let Some(metadata) = module_metadata(config.module_metadata.as_ref(), id) {
+            utils::validate_expressions(
+                &compiled.expressions,
+                &metadata.expressions,
+                &compiled.html,
+            )?;
+        }
+    }
 
-    let js_code = utils::generate_virtual_entry(&compiled);
+    let page_id = utils::canonicalize_page_id(id, config.project_root.as_deref());
+    let js_code = generate_entry(&compiled, &page_id, config)?;
     Ok((js_code, compiled))
 }
 
+/// Generate the entry module for a compiled page in the configured shape.
+///
+/// With `externalize_bindings`, the entry references the sidecar named
+/// after `page_id` and the tables' content hash.
+fn generate_entry(
+    compiled: &CompilerOutput,
+    page_id: &str,
+    config: &ZenithLoaderConfig,
+) -> Result<String, BundleError> {
+    let version = config.contract_version.as_str();
+    utils::validate_contract_version(version)?;
+    match config.runtime_import {
+        Some(ref runtime_import) => {
+            let bindings_file = if config.externalize_bindings {
+                let json = utils::generate_bindings_json(compiled)?;
+                Some(utils::bindings_filename(page_id, &json))
+            } else {
+                None
+            };
+            utils::generate_hydration_entry(
+                compiled,
+                runtime_import,
+                version,
+                config.default_export,
+                bindings_file.as_deref(),
+            )
+        }
+        None if config.default_export => Ok(utils::generate_virtual_entry(compiled, version)),
+        None => Ok(utils::generate_named_entry(compiled, version)),
+    }
+}
+
+/// Hash of a normalized `.zen` source, for [`CompileCache`] lookups.
+fn source_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Reject `\0zenith:` specifiers imported from user modules.
+///
+/// Virtual modules synthesized by the plugin may import each other (and
+/// entry points have no importer); anything else importing into the
+/// namespace could pull another page's internals.
+fn check_import_namespace(specifier: &str, importer: Option<&str>) -> Result<(), BundleError> {
+    match importer {
+        Some(importer) if !utils::is_virtual(importer) => {
+            utils::reject_external_zenith_import(specifier)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Build a line-granular sourcemap from a compiled module back to its `.zen` source.
+///
+/// The source is named relative to the project root (see
+/// [`utils::project_relative_path`]). When `sourcemap_sources_content` is
+/// set, `source` (already normalized) is embedded verbatim as `sourcesContent`.
+fn zen_source_map(
+    id: &str,
+    source: &str,
+    generated: &str,
+    config: &ZenithLoaderConfig,
+) -> anyhow::Result<SourceMap> {
+    let mut map = serde_json::json!({
+        "version": 3,
+        "sources": [utils::project_relative_path(id, config.project_root.as_deref())],
+        "names": [],
+        "mappings": line_mappings(source, generated),
+    });
+    if config.sourcemap_sources_content {
+        map["sourcesContent"] = serde_json::json!([source]);
+    }
+
+    SourceMap::from_json_string(&map.to_string())
+        .map_err(|e| anyhow::anyhow!("Failed to build sourcemap for '{}': {:?}", id, e))
+}
+
+/// Encode `mappings` that map each generated line to the source line it was
+/// copied from.
+///
+/// The compiler does not report spans, so lines are matched by content: a
+/// generated line whose trimmed text equals a source line's maps to it, else
+/// one that contains a source line's text (with at least one alphanumeric
+/// character, so lone braces don't match) maps to where it occurs. The search
+/// starts after the previous match, so repeated lines map in order. Lines
+/// the compiler synthesized (contract exports, tables) stay unmapped.
+fn line_mappings(source: &str, generated: &str) -> String {
+    let source_lines: Vec<&str> = source.lines().collect();
+    let mut cursor = 0;
+    let mut previous = (0i64, 0i64);
+    let mut groups = Vec::new();
+
+    for line in generated.lines() {
+        let text = line.trim();
+        let order = (cursor..source_lines.len()).chain(0..cursor.min(source_lines.len()));
+        let candidates: Vec<(usize, &str)> = order
+            .map(|i| (i, source_lines[i].trim()))
+            .filter(|(_, src)| !src.is_empty())
+            .collect();
+        let matched = candidates
+            .iter()
+            .find(|(_, src)| *src == text)
+            .map(|&(i, src)| (i, src, line.len() - line.trim_start().len()))
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .filter(|(_, src)| src.chars().any(char::is_alphanumeric))
+                    .find_map(|&(i, src)| line.find(src).map(|column| (i, src, column)))
+            });
+
+        let mut group = String::new();
+        if let Some((index, src, column)) = matched {
+            let source_column = source_lines[index].find(src).unwrap_or(0) as i64;
+            let segment = [
+                column as i64,
+                0,
+                index as i64 - previous.0,
+                source_column - previous.1,
+            ];
+            for value in segment {
+                encode_vlq(value, &mut group);
+            }
+            previous = (index as i64, source_column);
+            cursor = index + 1;
+        }
+        groups.push(group);
+    }
+
+    groups.join(";")
+}
+
+/// Append `value` to `out` as a base64 VLQ, per the sourcemap v3 spec.
+fn encode_vlq(value: i64, out: &mut String) {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut vlq = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = (vlq & 0b11111) as usize;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -265,28 +737,34 @@ mod tests {
         ZenithLoaderConfig {
             components: None,
             metadata: None,
+            module_metadata: None,
             strict: false,
             is_dev: false,
+            default_export: true,
+            sourcemap: false,
+            sourcemap_sources_content: false,
+            content_resolver: None,
+            compile_cache: None,
+            contract_version: utils::DEFAULT_CONTRACT_VERSION.to_string(),
+            runtime_import: None,
+            external: Vec::new(),
+            normalize_expression_whitespace: false,
+            project_root: None,
+            externalize_bindings: false,
+            compile_permits: None,
         }
     }
 
-    fn loader_config_with_metadata(expressions: Vec<String>) -> ZenithLoaderConfig {
+    /// Strict config with module metadata for `path` only.
+    fn loader_config_with_metadata(path: &str, expressions: Vec<String>) -> ZenithLoaderConfig {
+        let metadata = CompilerOutput {
+            expressions,
+            ..Default::default()
+        };
         ZenithLoaderConfig {
-            components: None,
-            metadata: Some(CompilerOutput {
-                ir_version: 1,
-                html: String::new(),
-                expressions,
-                hoisted: Default::default(),
-                components_scripts: Default::default(),
-                component_instances: Default::default(),
-                signals: Default::default(),
-                expression_bindings: Default::default(),
-                marker_bindings: Default::default(),
-                event_bindings: Default::default(),
-            }),
+            module_metadata: Some(HashMap::from([(path.to_string(), metadata)])),
             strict: true,
-            is_dev: false,
+            ..loader_config_no_metadata()
         }
     }
 
@@ -309,11 +787,48 @@ mod tests {
 
     #[test]
     fn compile_zen_source_strict_match() {
-        let config = loader_config_with_metadata(vec!["title".into()]);
+        let config = loader_config_with_metadata("page.zen", vec!["title".into()]);
         let result = compile_zen_source("<h1>{title}</h1>", "page.zen", &config);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn compile_zen_source_strict_rejects_diverging_import() {
+        // An imported component whose expressions diverge from its own
+        // metadata, under an equivalent spelling of its path
+        let config = loader_config_with_metadata("./components/card.zen", vec!["title".into()]);
+
+        match compile_zen_source("<span>{label}</span>", "components/card.zen", &config) {
+            Err(BundleError::ExpressionContentMismatch {
+                index,
+                expected,
+                got,
+                ..
+            }) => {
+                assert_eq!(index, 0);
+                assert_eq!(expected, "title");
+                assert_eq!(got, "label");
+            }
+            other => panic!("Expected ExpressionContentMismatch, got {:?}", other),
+        }
+
+        // Without strict mode the module compiles as before
+        let relaxed = ZenithLoaderConfig {
+            strict: false,
+            ..config
+        };
+        assert!(
+            compile_zen_source("<span>{label}</span>", "components/card.zen", &relaxed).is_ok()
+        );
+    }
+
+    #[test]
+    fn compile_zen_source_strict_skips_modules_without_metadata() {
+        let config = loader_config_with_metadata("components/card.zen", vec!["title".into()]);
+        let (_, compiled) = compile_zen_source("<h1>{heading}</h1>", "page.zen", &config).unwrap();
+        assert_eq!(compiled.expressions, vec!["heading"]);
+    }
+
     #[test]
     fn compile_zen_source_multiple_expressions() {
         let config = loader_config_no_metadata();
@@ -336,6 +851,98 @@ mod tests {
         assert!(js.contains("\"handler\""));
     }
 
+    #[test]
+    fn user_module_cannot_import_virtual_namespace() {
+        let other_entry = "\0zenith:entry:other";
+        assert!(matches!(
+            check_import_namespace(other_entry, Some("/src/pages/home.zen")),
+            Err(BundleError::ValidationError(_))
+        ));
+        assert!(check_import_namespace(other_entry, Some("/src/lib/util.js")).is_err());
+
+        // Plugin-synthesized modules and entry points are trusted
+        assert!(check_import_namespace("\0zenith:css:home", Some("\0zenith:entry:home")).is_ok());
+        assert!(check_import_namespace("\0zenith:entry:home", None).is_ok());
+        assert!(check_import_namespace("./header.zen", Some("/src/pages/home.zen")).is_ok());
+    }
+
+    #[test]
+    fn compile_cache_reuses_output_for_unchanged_source() {
+        let cache = CompileCache::default();
+        let config = ZenithLoaderConfig {
+            compile_cache: Some(Arc::clone(&cache)),
+            ..loader_config_no_metadata()
+        };
+        let source = "<h1>{title}</h1>";
+
+        // Seed the cache with a sentinel output for the current source hash
+        let sentinel = CompilerOutput {
+            expressions: vec!["from_cache".into()],
+            ..Default::default()
+        };
+        cache.insert("page.zen".into(), (source_hash(source), sentinel));
+
+        let (_, compiled) = compile_zen_source(source, "page.zen", &config).unwrap();
+        assert_eq!(compiled.expressions, vec!["from_cache"]);
+
+        // A changed source misses the cache and replaces the entry
+        let (_, compiled) = compile_zen_source("<h1>{other}</h1>", "page.zen", &config).unwrap();
+        assert_ne!(compiled.expressions, vec!["from_cache"]);
+        assert_eq!(
+            cache.get("page.zen").unwrap().0,
+            source_hash("<h1>{other}</h1>")
+        );
+    }
+
+    #[test]
+    fn source_map_lines_follow_copied_source_lines() {
+        let source = "<main>\n  <h1>{title}</h1>\n</main>\n";
+        let generated = "export const __zenith_html = `<main>\n  <h1 data-zx-e=\"0\"></h1>\n</main>`;\nexport const __zenith_expr = [\"title\"];";
+
+        // Line 1 maps to `<main>` at column 29, line 3 to `</main>` two
+        // source lines further down; the rewritten and synthesized lines
+        // stay unmapped
+        assert_eq!(line_mappings(source, generated), "6BAAA;;AAEA;");
+
+        let mut out = String::new();
+        for value in [0, 1, -1, 16, -17] {
+            encode_vlq(value, &mut out);
+        }
+        assert_eq!(out, "ACDgBjB");
+    }
+
+    #[test]
+    fn preloaded_source_matches_equivalent_paths() {
+        let def = ComponentDef {
+            path: "./virtual/pages/../components/card.zen".into(),
+            source: Some("<div></div>".into()),
+        };
+        let config = ZenithLoaderConfig {
+            components: Some(HashMap::from([("Card".to_string(), def)])),
+            ..loader_config_no_metadata()
+        };
+        let absolute = std::env::current_dir()
+            .unwrap()
+            .join("virtual/components/card.zen");
+
+        for id in [
+            "virtual/components/card.zen",
+            "virtual\\components\\card.zen",
+            absolute.to_str().unwrap(),
+        ] {
+            assert_eq!(
+                preloaded_source(config.components.as_ref(), id),
+                Some("<div></div>"),
+                "{}",
+                id
+            );
+        }
+        assert_eq!(
+            preloaded_source(config.components.as_ref(), "virtual/card.zen"),
+            None
+        );
+    }
+
     #[test]
     fn plugin_name() {
         let loader = ZenithLoader::new(loader_config_no_metadata());
@@ -0,0 +1,123 @@
+//! Router manifest read by the client router.
+//!
+//! The CLI keeps `assets/router-manifest.json` up to date route by route;
+//! [`build_router_manifest`] assembles the same JSON from in-memory
+//! [`BundleResult`]s so a dev server can serve it without touching disk.
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    check_route_collision, content_hash, parse_route, route_to_output_path, RouteSegment,
+};
+use crate::{BundleError, BundleResult};
+
+/// Path of the router manifest, relative to the output directory.
+pub const ROUTER_MANIFEST_FILE: &str = "assets/router-manifest.json";
+
+/// Every route the client router can render, sorted by `path`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouterManifest {
+    pub routes: Vec<RouterRouteEntry>,
+}
+
+impl RouterManifest {
+    /// Insert `entry`, replacing any route with the same `path`, and keep
+    /// the routes sorted. Fails if `entry` collides with another route
+    /// (see [`check_route_collision`]).
+    pub fn upsert(&mut self, entry: RouterRouteEntry) -> Result<(), BundleError> {
+        for route in &self.routes {
+            check_route_collision(&route.path, &entry.path)?;
+        }
+        if let Some(existing) = self
+            .routes
+            .iter_mut()
+            .find(|route| route.path == entry.path)
+        {
+            *existing = entry;
+        } else {
+            self.routes.push(entry);
+        }
+        self.routes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouterRouteEntry {
+    pub path: String,
+    /// `path` split into segments, so the client matcher handles optional
+    /// and catch-all segments exactly as the bundler parsed them.
+    #[serde(default)]
+    pub segments: Vec<RouteSegment>,
+    pub output: String,
+    pub html: String,
+    pub expressions: Vec<String>,
+    /// [`route_content_hash`] of `html` and `expressions` at write time; the
+    /// client router warns when an entry no longer matches it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+impl RouterRouteEntry {
+    /// Entry for `route`, with its segments, output path and content hash
+    /// derived from the arguments.
+    pub fn new(route: &str, html: String, expressions: Vec<String>) -> Result<Self, BundleError> {
+        Ok(Self {
+            path: route.to_string(),
+            segments: parse_route(route)?,
+            output: route_to_output_path(route)
+                .to_string_lossy()
+                .replace('\\', "/"),
+            content_hash: Some(route_content_hash(&html, &expressions)),
+            html,
+            expressions,
+        })
+    }
+}
+
+/// Integrity hash of a router manifest entry: [`content_hash`] of `html`, a
+/// newline, and the expressions joined by newlines. The client router
+/// recomputes it the same way.
+pub fn route_content_hash(html: &str, expressions: &[String]) -> String {
+    content_hash(&format!("{html}\n{}", expressions.join("\n")))
+}
+
+/// Serialize a router manifest for `routes` (`(page_path, route)` pairs)
+/// from in-memory build results, byte-identical to the file the CLI writes.
+///
+/// Each result must carry its compiled output
+/// (`BundleOptions.include_compiled`): the manifest stores the page markup,
+/// not the rendered document.
+pub fn build_router_manifest(
+    results: &[BundleResult],
+    routes: &[(String, String)],
+) -> Result<String, BundleError> {
+    let mut manifest = RouterManifest::default();
+    for (page_path, route) in routes {
+        let result = results
+            .iter()
+            .find(|result| result.page_path == *page_path)
+            .ok_or_else(|| {
+                BundleError::ValidationError(format!(
+                    "Route '{}' names '{}', which has no bundle result",
+                    route, page_path
+                ))
+            })?;
+        let compiled = result.compiled.as_ref().ok_or_else(|| {
+            BundleError::ValidationError(format!(
+                "No compiled output for '{}'; bundle with include_compiled",
+                page_path
+            ))
+        })?;
+        manifest.upsert(RouterRouteEntry::new(
+            route,
+            compiled.html.clone(),
+            result.expressions.clone(),
+        )?)?;
+    }
+    serde_json::to_string(&manifest).map_err(|e| {
+        BundleError::BuildError(format!("Router manifest serialization failed: {}", e))
+    })
+}
@@ -0,0 +1,81 @@
+//! Helpers for exercising the bundler from tests, here and in downstream
+//! crates.
+//!
+//! ```ignore
+//! use zenith_bundler::testing::{bundle_str, MockCompilerOutput};
+//! use zenith_bundler::{BuildMode, CompilerOutput};
+//!
+//! let result = bundle_str("<h1>{title}</h1>", BuildMode::Dev).await?;
+//! assert_eq!(result.expressions, CompilerOutput::mock("", &["title"]).expressions);
+//! ```
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{BuildMode, BundleError, BundleOptions, BundlePlan, BundleResult, CompilerOutput};
+
+/// Construct a [`CompilerOutput`] from just its HTML and expressions.
+pub trait MockCompilerOutput {
+    /// IR version 1 output with `html` and `expressions`; every other
+    /// table is empty.
+    fn mock(html: &str, expressions: &[&str]) -> Self;
+}
+
+impl MockCompilerOutput for CompilerOutput {
+    fn mock(html: &str, expressions: &[&str]) -> Self {
+        CompilerOutput {
+            ir_version: 1,
+            html: html.to_string(),
+            expressions: expressions.iter().map(|expr| expr.to_string()).collect(),
+            hoisted: Default::default(),
+            components_scripts: Default::default(),
+            component_instances: Default::default(),
+            signals: Default::default(),
+            expression_bindings: Default::default(),
+            marker_bindings: Default::default(),
+            event_bindings: Default::default(),
+        }
+    }
+}
+
+/// Bundle `content` as a standalone `.zen` page with default options.
+///
+/// The page is written to a temporary file that is removed once bundling
+/// finishes; nothing is written to disk otherwise.
+pub async fn bundle_str(content: &str, mode: BuildMode) -> Result<BundleResult, BundleError> {
+    let path = temp_page_path();
+    std::fs::write(&path, content)?;
+    let plan = BundlePlan {
+        page_path: path.to_string_lossy().to_string(),
+        out_dir: None,
+        mode,
+    };
+    let result = crate::bundle_page(plan, BundleOptions::default()).await;
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// A fresh `.zen` path in the system temp dir, unique within this process
+/// and across concurrently running ones.
+fn temp_page_path() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("zenith-bundler-{}-{n}.zen", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn bundle_str_bundles_a_page() {
+        let result = bundle_str("<h1>{title}</h1>", BuildMode::Dev)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.expressions,
+            CompilerOutput::mock("", &["title"]).expressions
+        );
+        assert!(result.entry_js.contains("__zenith_html"));
+    }
+}
@@ -4,9 +4,14 @@
 //! - JS string escaping (injection-safe)
 //! - Post-build validation helpers
 
+use std::borrow::Cow;
+use std::path::Path;
+
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::{BundleError, CompilerOutput, Diagnostic, DiagnosticLevel};
+use crate::{codes, BundleError, CompilerOutput, Diagnostic, DiagnosticLevel};
 
 // ---------------------------------------------------------------------------
 // Virtual Module IDs
@@ -84,6 +89,94 @@ pub fn reject_external_zenith_import(specifier: &str) -> Result<(), BundleError>
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// External Specifiers
+// ---------------------------------------------------------------------------
+
+/// Check if an import specifier is bare (a package name such as `gsap` or
+/// `@scope/pkg/sub`), i.e. not relative, absolute, virtual or a `.zen` file.
+pub fn is_bare_specifier(specifier: &str) -> bool {
+    !specifier.is_empty()
+        && !specifier.starts_with('.')
+        && !specifier.starts_with('/')
+        && !specifier.starts_with('\\')
+        && !is_virtual(specifier)
+        && !is_zen_file(specifier)
+        && !std::path::Path::new(specifier).is_absolute()
+}
+
+/// Check if a bare specifier matches one of the `BundleOptions.external`
+/// patterns.
+///
+/// A pattern without `*` names a package: it matches the specifier exactly
+/// and any subpath of it (`gsap` matches `gsap/ScrollTrigger`). Otherwise
+/// `*` matches any run of characters, `/` included.
+pub fn is_external(specifier: &str, patterns: &[String]) -> bool {
+    is_bare_specifier(specifier)
+        && patterns.iter().any(|pattern| {
+            if pattern.contains('*') {
+                glob_match(pattern.as_bytes(), specifier.as_bytes())
+            } else {
+                specifier == pattern
+                    || specifier
+                        .strip_prefix(pattern.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+        })
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of bytes.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen, and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, at)) = backtrack {
+            // Let the last `*` swallow one more byte and retry
+            p = star + 1;
+            t = at + 1;
+            backtrack = Some((star, at + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+// ---------------------------------------------------------------------------
+// Manual Chunks
+// ---------------------------------------------------------------------------
+
+/// Build the module-id regex for one `BundleOptions.manual_chunks` entry.
+///
+/// Each pattern names a package and matches any module under
+/// `node_modules/<name>/`. In a pattern with `*`, each `*` matches any run of
+/// characters within one path segment (`@motion/*` covers every package of
+/// the scope, `*-icons` every package whose name ends in `-icons`).
+pub fn manual_chunk_regex(patterns: &[String]) -> String {
+    let alternatives: Vec<String> = patterns
+        .iter()
+        .map(|pattern| {
+            let name = pattern
+                .split('/')
+                .map(|segment| {
+                    let parts: Vec<String> = segment.split('*').map(regex::escape).collect();
+                    parts.join(r"[^\\/]*")
+                })
+                .collect::<Vec<_>>()
+                .join(r"[\\/]");
+            format!(r"[\\/]node_modules[\\/]{}[\\/]", name)
+        })
+        .collect();
+    format!("(?:{})", alternatives.join("|"))
+}
+
 // ---------------------------------------------------------------------------
 // Rolldown Commit Pin
 // ---------------------------------------------------------------------------
@@ -97,30 +190,30 @@ pub const EXPECTED_ROLLDOWN_COMMIT: &str = "67a1f58";
 // ---------------------------------------------------------------------------
 
 /// Escape a string for safe embedding inside a JS template literal (backtick string).
-/// Prevents injection by escaping backticks, backslashes, and `${`.
+/// Prevents injection by escaping backticks, backslashes, and every `$` — not
+/// only `${`, so escaped fragments can be concatenated without a `$` and a
+/// `{` meeting at the seam. U+2028/U+2029 are written as `\u` escapes, which
+/// some engines reject raw in script source.
+///
+/// `</script` (any case) becomes `<\/script`: the same string at runtime,
+/// but it cannot end an inline `<script>` the bundle is later embedded in.
 pub fn escape_js_template_literal(s: &str) -> String {
     let mut out = String::with_capacity(s.len() + 16);
-    let chars: Vec<char> = s.chars().collect();
-    let len = chars.len();
-    let mut i = 0;
-
-    while i < len {
-        match chars[i] {
-            '\\' => {
-                out.push_str("\\\\");
-            }
-            '`' => {
-                out.push_str("\\`");
-            }
-            '$' if i + 1 < len && chars[i + 1] == '{' => {
-                out.push_str("\\${");
-                i += 1; // skip the '{'
-            }
-            c => {
-                out.push(c);
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' if s
+                .get(i + 1..i + 8)
+                .is_some_and(|tag| tag.eq_ignore_ascii_case("/script")) =>
+            {
+                out.push_str("<\\")
             }
+            '\\' => out.push_str("\\\\"),
+            '`' => out.push_str("\\`"),
+            '$' => out.push_str("\\$"),
+            '\u{2028}' => out.push_str("\\u2028"),
+            '\u{2029}' => out.push_str("\\u2029"),
+            c => out.push(c),
         }
-        i += 1;
     }
     out
 }
@@ -135,6 +228,8 @@ pub fn escape_js_string(s: &str) -> String {
             '\n' => out.push_str("\\n"),
             '\r' => out.push_str("\\r"),
             '\t' => out.push_str("\\t"),
+            '\u{2028}' => out.push_str("\\u2028"),
+            '\u{2029}' => out.push_str("\\u2029"),
             c => out.push(c),
         }
     }
@@ -145,13 +240,48 @@ pub fn escape_js_string(s: &str) -> String {
 // Virtual Entry Generation
 // ---------------------------------------------------------------------------
 
+/// Contract version emitted as `__zenith_contract` unless overridden.
+pub const DEFAULT_CONTRACT_VERSION: &str = "v0";
+
+/// Contract versions the bundler can emit. The runtime reads
+/// `__zenith_contract` to decide how to hydrate, so anything else is refused
+/// rather than risk a runtime mis-reading the payload.
+pub const SUPPORTED_CONTRACT_VERSIONS: &[&str] = &[DEFAULT_CONTRACT_VERSION];
+
+/// Reject contract versions outside [`SUPPORTED_CONTRACT_VERSIONS`].
+pub fn validate_contract_version(version: &str) -> Result<(), BundleError> {
+    if SUPPORTED_CONTRACT_VERSIONS.contains(&version) {
+        return Ok(());
+    }
+    Err(BundleError::ValidationError(format!(
+        "Unknown contract version '{}' (supported: {})",
+        version,
+        SUPPORTED_CONTRACT_VERSIONS.join(", ")
+    )))
+}
+
 /// Generate the virtual entry module JS source for a compiled page.
 ///
 /// The entry contains:
 /// - `__zenith_html` — the HTML template string
 /// - `__zenith_expr` — the expression table
+/// - `__zenith_contract` — `contract_version`
 /// - A default export function (hydration stub)
-pub fn generate_virtual_entry(output: &CompilerOutput) -> String {
+pub fn generate_virtual_entry(output: &CompilerOutput, contract_version: &str) -> String {
+    format!(
+        r#"{}
+export default function __zenith_page() {{
+  return {{ html: __zenith_html, expressions: __zenith_expr, contract: __zenith_contract }};
+}}"#,
+        generate_named_entry(output, contract_version)
+    )
+}
+
+/// Generate the entry with only the named contract exports.
+///
+/// Identical to [`generate_virtual_entry`] minus the `__zenith_page` default
+/// export, for library-style consumers and module systems that reject defaults.
+pub fn generate_named_entry(output: &CompilerOutput, contract_version: &str) -> String {
     let html_escaped = escape_js_template_literal(&output.html);
 
     let expr_items: Vec<String> = output
@@ -165,25 +295,920 @@ pub fn generate_virtual_entry(output: &CompilerOutput) -> String {
     format!(
         r#"export const __zenith_html = `{}`;
 export const __zenith_expr = [{}];
-export const __zenith_contract = "v0";
-export default function __zenith_page() {{
-  return {{ html: __zenith_html, expressions: __zenith_expr, contract: __zenith_contract }};
-}}"#,
-        html_escaped, expr_array
+export const __zenith_contract = "{}";"#,
+        html_escaped,
+        expr_array,
+        escape_js_string(contract_version)
     )
 }
 
+/// Generate a hydratable entry: the contract exports, then the page's
+/// hoisted code, its binding tables and a `hydrate({...})` call.
+///
+/// The contract symbols are unchanged (`default_export` selects between the
+/// [`generate_virtual_entry`] and [`generate_named_entry`] shapes). The
+/// runtime is imported from `runtime_import`. Without compiler-reported
+/// expression bindings, each expression is bound as a literal to its own
+/// marker; without marker bindings, the markers are derived from the HTML
+/// (see [`binding_tables`]). Components are not bootstrapped — that needs the component
+/// assets the CLI emits.
+///
+/// With `bindings_file`, the tables are not inlined: the entry fetches that
+/// sidecar (see [`generate_bindings_json`]), resolved next to the entry,
+/// and hydrates once it loads. A failed request (network error or non-2xx
+/// status) is logged and leaves the page unhydrated.
+pub fn generate_hydration_entry(
+    output: &CompilerOutput,
+    runtime_import: &str,
+    contract_version: &str,
+    default_export: bool,
+    bindings_file: Option<&str>,
+) -> Result<String, BundleError> {
+    let mut js = if default_export {
+        generate_virtual_entry(output, contract_version)
+    } else {
+        generate_named_entry(output, contract_version)
+    };
+    js.push('\n');
+
+    for block in &output.hoisted.code {
+        let trimmed = block.trim();
+        if !trimmed.is_empty() {
+            js.push('\n');
+            js.push_str(trimmed);
+            js.push('\n');
+        }
+    }
+
+    let mut state_values = String::from("const __zenith_state_values = Object.freeze([");
+    for binding in &output.hoisted.state {
+        state_values.push_str("\n  ");
+        state_values.push_str(binding.value.trim());
+        state_values.push(',');
+    }
+    if !output.hoisted.state.is_empty() {
+        state_values.push('\n');
+    }
+    state_values.push_str("]);\n");
+
+    js.push_str(&format!(
+        "\nimport {{ hydrate, signal, state, zeneffect }} from \"{}\";\n",
+        escape_js_string(runtime_import)
+    ));
+
+    if let Some(bindings_file) = bindings_file {
+        js.push_str(&state_values);
+        js.push_str(&format!(
+            r#"fetch(new URL("./{}", import.meta.url))
+  .then((response) => {{
+    if (!response.ok) {{
+      throw new Error(`failed to load binding tables from ${{response.url}} (HTTP ${{response.status}})`);
+    }}
+    return response.json();
+  }})
+  .then((tables) => hydrate({{
+    root: document,
+    ir_version: {},
+    expressions: Object.freeze(tables.expressions),
+    markers: tables.markers,
+    events: tables.events,
+    state_values: __zenith_state_values,
+    signals: Object.freeze(tables.signals),
+    components: []
+  }}))
+  .catch((error) => {{
+    console.error('[zenith] page was not hydrated:', error);
+  }});
+"#,
+            escape_js_string(bindings_file),
+            output.ir_version
+        ));
+        return Ok(js);
+    }
+
+    let (markers, events) = binding_tables(output)?;
+    let signals = table_json("signal", serde_json::to_string(&output.signals))?;
+    let bindings = if output.expression_bindings.is_empty() {
+        table_json(
+            "expression",
+            serde_json::to_string(&literal_expression_bindings(output)),
+        )?
+    } else {
+        table_json(
+            "expression",
+            serde_json::to_string(&output.expression_bindings),
+        )?
+    };
+
+    js.push_str(&format!(
+        "const __zenith_markers = {};\n",
+        table_json("marker", serde_json::to_string(&markers))?
+    ));
+    js.push_str(&format!(
+        "const __zenith_events = {};\n",
+        table_json("event", serde_json::to_string(&events))?
+    ));
+    js.push_str(&state_values);
+    js.push_str(&format!(
+        "const __zenith_signals = Object.freeze({});\n",
+        signals
+    ));
+    js.push_str(&format!(
+        "const __zenith_expression_bindings = Object.freeze({});\n",
+        bindings
+    ));
+    js.push_str(&format!(
+        r#"hydrate({{
+  root: document,
+  ir_version: {},
+  expressions: __zenith_expression_bindings,
+  markers: __zenith_markers,
+  events: __zenith_events,
+  state_values: __zenith_state_values,
+  signals: __zenith_signals,
+  components: []
+}});
+"#,
+        output.ir_version
+    ));
+
+    Ok(js)
+}
+
+/// The binding tables of a page as the JSON sidecar written for
+/// `BundleOptions::externalize_bindings`: `expressions`, `markers`,
+/// `events`, `signals` and `state`, with every object's keys sorted.
+///
+/// `state` lists the state keys only. Their initial values are JS
+/// expressions evaluated with the hoisted code, so they stay in the entry.
+pub fn generate_bindings_json(output: &CompilerOutput) -> Result<String, BundleError> {
+    let expressions = if output.expression_bindings.is_empty() {
+        serde_json::Value::Array(literal_expression_bindings(output))
+    } else {
+        table_json(
+            "expression",
+            serde_json::to_value(&output.expression_bindings),
+        )?
+    };
+    let (markers, events) = binding_tables(output)?;
+    let mut tables = serde_json::json!({
+        "events": events,
+        "expressions": expressions,
+        "markers": markers,
+        "signals": table_json("signal", serde_json::to_value(&output.signals))?,
+        "state": output
+            .hoisted
+            .state
+            .iter()
+            .map(|binding| binding.key.as_str())
+            .collect::<Vec<_>>(),
+    });
+    tables.sort_all_objects();
+    table_json("binding", serde_json::to_string(&tables))
+}
+
+/// Filename of a page's bindings sidecar: `{page_id}.bindings.{hash}.json`,
+/// written beside its entry.
+pub fn bindings_filename(page_id: &str, bindings_json: &str) -> String {
+    format!("{}.bindings.{}.json", page_id, content_hash(bindings_json))
+}
+
+/// One literal expression binding per expression, each to its own marker.
+fn literal_expression_bindings(output: &CompilerOutput) -> Vec<serde_json::Value> {
+    output
+        .expressions
+        .iter()
+        .enumerate()
+        .map(|(index, value)| serde_json::json!({ "marker_index": index, "literal": value }))
+        .collect()
+}
+
+/// The page's marker and event tables, as JSON.
+///
+/// The compiler's tables when it reported markers. Otherwise (or for a page
+/// without expressions) they are derived from the `data-zx-*` attributes in
+/// the HTML, one marker per expression, as the CLI does (see
+/// [`cli_input::derive_binding_tables`](crate::cli_input::derive_binding_tables));
+/// the runtime rejects a marker table that does not cover the expressions.
+fn binding_tables(
+    output: &CompilerOutput,
+) -> Result<(serde_json::Value, serde_json::Value), BundleError> {
+    if output.marker_bindings.is_empty() && !output.expressions.is_empty() {
+        let (markers, events) =
+            crate::cli_input::derive_binding_tables(&output.html, output.expressions.len())
+                .map_err(BundleError::ValidationError)?;
+        let events = if output.event_bindings.is_empty() {
+            table_json("event", serde_json::to_value(&events))?
+        } else {
+            table_json("event", serde_json::to_value(&output.event_bindings))?
+        };
+        return Ok((
+            table_json("marker", serde_json::to_value(&markers))?,
+            events,
+        ));
+    }
+    Ok((
+        table_json("marker", serde_json::to_value(&output.marker_bindings))?,
+        table_json("event", serde_json::to_value(&output.event_bindings))?,
+    ))
+}
+
+/// Map a table serialization failure to a [`BundleError::BuildError`].
+fn table_json<T>(table: &str, value: serde_json::Result<T>) -> Result<T, BundleError> {
+    value
+        .map_err(|e| BundleError::BuildError(format!("Failed to serialize {} table: {}", table, e)))
+}
+
+// ---------------------------------------------------------------------------
+// Content Hashing
+// ---------------------------------------------------------------------------
+
+/// Hex digits kept from the SHA-256 digest in asset filenames (64 bits).
+pub const CONTENT_HASH_LEN: usize = 16;
+
+/// Content hash for asset filenames: SHA-256, truncated to
+/// [`CONTENT_HASH_LEN`] lowercase hex digits.
+pub fn content_hash(content: &str) -> String {
+    let digest = Sha256::digest(content.as_bytes());
+    let mut hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    hex.truncate(CONTENT_HASH_LEN);
+    hex
+}
+
+/// What an emitted asset holds; picks its filename prefix and extension in
+/// [`asset_filename`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    /// A page's entry module.
+    Entry,
+    /// A page's stylesheet.
+    Css,
+    /// A hoisted component module, shared by every page that uses it.
+    Component,
+    /// The hydration runtime, shared by every page.
+    Runtime,
+    /// The client router, shared by every page.
+    Router,
+}
+
+impl AssetKind {
+    /// File extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            AssetKind::Css => "css",
+            _ => "js",
+        }
+    }
+}
+
+/// Content-hashed path of an asset, relative to the output directory.
+///
+/// Entries and stylesheets are named after their page
+/// (`assets/{page_id}.{hash}.{ext}`) and components after their hoist id
+/// (`assets/component.{page_id}.{hash}.js`); `page_id` must already be
+/// filename-safe. The runtime and router are shared by every page, so they
+/// ignore `page_id` (`assets/runtime.{hash}.js`, `assets/router.{hash}.js`).
+pub fn asset_filename(page_id: &str, content: &str, kind: AssetKind) -> String {
+    let hash = content_hash(content);
+    let ext = kind.extension();
+    match kind {
+        AssetKind::Entry | AssetKind::Css => format!("assets/{}.{}.{}", page_id, hash, ext),
+        AssetKind::Component => format!("assets/component.{}.{}.{}", page_id, hash, ext),
+        AssetKind::Runtime => format!("assets/runtime.{}.{}", hash, ext),
+        AssetKind::Router => format!("assets/router.{}.{}", hash, ext),
+    }
+}
+
+/// Normalize a `.zen` source before compiling: drop a leading UTF-8 BOM and
+/// convert CRLF to LF, so output does not depend on the editor that saved it.
+pub fn normalize_source(source: &str) -> String {
+    source
+        .strip_prefix('\u{FEFF}')
+        .unwrap_or(source)
+        .replace("\r\n", "\n")
+}
+
+/// Collapse the whitespace of an expression: trim it and turn every run of
+/// whitespace into one space, so `{ a  +  b }` and `{a + b}` emit the same
+/// expression. String, template and regex literals and comments are copied
+/// verbatim; the newline ending a `//` comment is kept so the comment
+/// cannot swallow the code after it.
+///
+/// Only applied with `BundleOptions::normalize_expression_whitespace`; by
+/// default expressions are emitted exactly as the compiler reports them.
+pub fn normalize_expression_whitespace(expr: &str) -> String {
+    let chars: Vec<char> = expr.trim().chars().collect();
+    let mut out = String::with_capacity(expr.len());
+    let mut pending_space = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            // Whitespace after a line comment's newline is layout too
+            pending_space = !out.ends_with('\n');
+            i += 1;
+            continue;
+        }
+        if pending_space {
+            out.push(' ');
+            pending_space = false;
+        }
+
+        let next = chars.get(i + 1).copied();
+        let end = match c {
+            '"' | '\'' | '`' => literal_end(&chars, i, |ch| ch == c),
+            '/' if next == Some('/') => chars[i..]
+                .iter()
+                .position(|&ch| ch == '\n')
+                .map_or(chars.len(), |n| i + n + 1),
+            '/' if next == Some('*') => chars[i + 2..]
+                .windows(2)
+                .position(|w| w == ['*', '/'])
+                .map_or(chars.len(), |n| i + 2 + n + 2),
+            '/' if starts_regex_literal(&out) => {
+                let mut in_class = false;
+                literal_end(&chars, i, |ch| {
+                    in_class = match ch {
+                        '[' => true,
+                        ']' => false,
+                        _ => in_class,
+                    };
+                    ch == '/' && !in_class
+                })
+            }
+            _ => i + 1,
+        };
+        out.extend(&chars[i..end]);
+        i = end;
+    }
+    out
+}
+
+/// Index just past the literal opening at `chars[start]`: the first
+/// unescaped character after it for which `closes` returns true, or the end
+/// of input when the literal is unterminated.
+fn literal_end(chars: &[char], start: usize, mut closes: impl FnMut(char) -> bool) -> usize {
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate().skip(start + 1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if closes(c) {
+            return i + 1;
+        }
+    }
+    chars.len()
+}
+
+/// Whether a `/` following `before` opens a regex literal rather than
+/// dividing: it does at the start, after an operator or opening bracket,
+/// and after a keyword that takes an expression.
+fn starts_regex_literal(before: &str) -> bool {
+    let before = before.trim_end();
+    let Some(last) = before.chars().last() else {
+        return true;
+    };
+    if "([{,;:=!&|?+-*%<>~^".contains(last) {
+        return true;
+    }
+    let word_start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .map_or(0, |i| i + 1);
+    REGEX_PRECEDING_KEYWORDS.contains(&&before[word_start..])
+}
+
+/// Keywords after which a `/` starts a regex literal.
+const REGEX_PRECEDING_KEYWORDS: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+];
+
 // ---------------------------------------------------------------------------
 // Canonicalize Page ID
 // ---------------------------------------------------------------------------
 
 /// Derive a deterministic page ID from a file path.
-/// Strips extensions, normalizes separators, and lowercases.
-pub fn canonicalize_page_id(page_path: &str) -> String {
-    let path = std::path::Path::new(page_path);
-    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
-    // Use the stem as the page ID, lowercased
-    stem.to_lowercase()
+///
+/// The path is made relative to `root` (the project root) when it lies
+/// under it, then its directory segments and stem are joined with `-`
+/// (`src/blog/index.zen` → `src-blog-index`). Within a segment, `_` is
+/// written `__`, `-` is written `_-` and an uppercase letter is written `_`
+/// and its lowercase form. A dash in a name never reads as a directory
+/// boundary (`src/blog-index.zen` → `src-blog_-index`), and ids stay
+/// lowercase yet distinct for `Home.zen` and `home.zen`. Separators are
+/// normalized first, so the id is the same on every OS. Root, drive and
+/// `.` segments are dropped; `..` cancels the segment before it and is kept
+/// otherwise.
+///
+/// Without a `root`, or for a path outside it, the path is used as given:
+/// an absolute path then yields a machine-dependent id.
+pub fn canonicalize_page_id(page_path: &str, root: Option<&Path>) -> String {
+    let normalized = page_path.replace('\\', "/");
+    let relative = root
+        .and_then(|root| strip_root(&normalized, &root.to_string_lossy()))
+        .unwrap_or(normalized);
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            _ if segment.ends_with(':') => {}
+            ".." if segments.last().is_some_and(|last| *last != "..") => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    if let Some(last) = segments.last_mut() {
+        *last = last.strip_suffix(".zen").unwrap_or(last);
+    }
+    segments
+        .iter()
+        .map(|segment| escape_id_segment(segment))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Escape one path segment for [`canonicalize_page_id`].
+fn escape_id_segment(segment: &str) -> String {
+    let mut escaped = String::with_capacity(segment.len());
+    for c in segment.chars() {
+        match c {
+            '_' => escaped.push_str("__"),
+            '-' => escaped.push_str("_-"),
+            c if c.is_uppercase() => {
+                escaped.push('_');
+                escaped.extend(c.to_lowercase());
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The part of a `/`-separated path under `root`, if any.
+fn strip_root(normalized: &str, root: &str) -> Option<String> {
+    let root = root.replace('\\', "/");
+    normalized
+        .strip_prefix(root.trim_end_matches('/'))
+        .and_then(|rest| rest.strip_prefix('/'))
+        .map(str::to_string)
+}
+
+/// The part of a `/`-separated path under the working directory, if any.
+fn strip_cwd(normalized: &str) -> Option<String> {
+    let cwd = std::env::current_dir().ok()?;
+    strip_root(normalized, &cwd.to_string_lossy())
+}
+
+/// Derive the URL route a page is served at.
+///
+/// Segments after the last `pages` directory form the route (just the file
+/// name if there is none); `.zen` and a trailing `index` are dropped.
+/// `src/pages/blog/index.zen` → `/blog`, `about.zen` → `/about`.
+pub fn page_route(page_path: &str) -> String {
+    let normalized = page_path.replace('\\', "/");
+    let segments: Vec<&str> = normalized
+        .split('/')
+        .filter(|segment| {
+            !segment.is_empty() && *segment != "." && *segment != ".." && !segment.ends_with(':')
+        })
+        .collect();
+    let start = segments
+        .iter()
+        .rposition(|segment| *segment == "pages")
+        .map_or(segments.len().saturating_sub(1), |i| i + 1);
+
+    let mut route: Vec<&str> = segments[start..].to_vec();
+    if let Some(last) = route.last_mut() {
+        *last = last.strip_suffix(".zen").unwrap_or(last);
+    }
+    if route.last() == Some(&"index") {
+        route.pop();
+    }
+    format!("/{}", route.join("/"))
+}
+
+/// One segment of a route pattern, as recorded in the router manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum RouteSegment {
+    /// Literal segment (`blog`).
+    Static { value: String },
+    /// `:name` — exactly one segment.
+    Param { name: String },
+    /// `:name?` — one segment, or none at the end of the path.
+    Optional { name: String },
+    /// `*` or `:name*` — all remaining segments (possibly none), captured
+    /// as an array. A bare `*` is named `rest`.
+    Rest { name: String },
+}
+
+impl RouteSegment {
+    fn parse(segment: &str) -> Self {
+        if segment == "*" {
+            return Self::Rest {
+                name: "rest".into(),
+            };
+        }
+        match segment.strip_prefix(':') {
+            Some(param) => {
+                if let Some(name) = param.strip_suffix('*') {
+                    Self::Rest { name: name.into() }
+                } else if let Some(name) = param.strip_suffix('?') {
+                    Self::Optional { name: name.into() }
+                } else {
+                    Self::Param { name: param.into() }
+                }
+            }
+            None => Self::Static {
+                value: segment.into(),
+            },
+        }
+    }
+}
+
+/// Split a route pattern into its segments.
+///
+/// Fails on an unnamed parameter, on a catch-all that is not the last
+/// segment, and on an optional segment followed by a required one (the
+/// path would be ambiguous).
+pub fn parse_route(route_path: &str) -> Result<Vec<RouteSegment>, BundleError> {
+    let segments: Vec<RouteSegment> = route_path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(RouteSegment::parse)
+        .collect();
+
+    for (i, segment) in segments.iter().enumerate() {
+        let rest = &segments[i + 1..];
+        let problem = match segment {
+            RouteSegment::Param { name }
+            | RouteSegment::Optional { name }
+            | RouteSegment::Rest { name }
+                if name.is_empty() =>
+            {
+                Some("parameter without a name")
+            }
+            RouteSegment::Rest { .. } if !rest.is_empty() => Some("catch-all segment must be last"),
+            RouteSegment::Optional { .. }
+                if rest.iter().any(|s| {
+                    matches!(s, RouteSegment::Static { .. } | RouteSegment::Param { .. })
+                }) =>
+            {
+                Some("optional segment followed by a required one")
+            }
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            return Err(BundleError::ValidationError(format!(
+                "Invalid route `{}`: {}",
+                route_path, problem
+            )));
+        }
+    }
+    Ok(segments)
+}
+
+/// Map a route to its static HTML file (`/` → `index.html`,
+/// `/blog/post` → `blog/post/index.html`).
+///
+/// Dynamic `:param` (and `:param?`) segments keep their place as `_param`
+/// directories (`/users/:id` → `users/_id/index.html`), so every parametric
+/// route gets its own shell and sibling routes never overwrite each other.
+/// Catch-alls become `__name` (`/files/*` → `files/__rest/index.html`),
+/// apart from a `:rest` parameter at the same position.
+pub fn route_to_output_path(route_path: &str) -> std::path::PathBuf {
+    let mut out = std::path::PathBuf::new();
+    for segment in route_path.split('/').filter(|s| !s.is_empty()) {
+        match RouteSegment::parse(segment) {
+            RouteSegment::Static { value } => out.push(value),
+            RouteSegment::Param { name } | RouteSegment::Optional { name } => {
+                out.push(format!("_{}", name))
+            }
+            RouteSegment::Rest { name } => out.push(format!("__{}", name)),
+        }
+    }
+    out.push("index.html");
+    out
+}
+
+/// A route's segments with parameter names erased: two routes with the same
+/// shape match exactly the same paths.
+fn route_shape(route_path: &str) -> Vec<RouteSegment> {
+    route_path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|segment| match RouteSegment::parse(segment) {
+            RouteSegment::Static { value } => RouteSegment::Static { value },
+            RouteSegment::Param { .. } => RouteSegment::Param {
+                name: String::new(),
+            },
+            RouteSegment::Optional { .. } => RouteSegment::Optional {
+                name: String::new(),
+            },
+            RouteSegment::Rest { .. } => RouteSegment::Rest {
+                name: String::new(),
+            },
+        })
+        .collect()
+}
+
+/// Fail if two distinct routes collide: they differ only in parameter
+/// names (`/users/:id` and `/users/:name`), so the router can only ever
+/// pick one, or they write the same HTML shell (`/users/:id` and
+/// `/users/_id`, `/blog/:slug` and `/blog/:slug?`).
+pub fn check_route_collision(existing: &str, route: &str) -> Result<(), BundleError> {
+    if existing == route {
+        return Ok(());
+    }
+    if route_shape(existing) == route_shape(route) {
+        return Err(BundleError::ValidationError(format!(
+            "Route collision: `{}` and `{}` match the same paths",
+            existing, route
+        )));
+    }
+    let output = route_to_output_path(route);
+    if route_to_output_path(existing) == output {
+        return Err(BundleError::ValidationError(format!(
+            "Route collision: `{}` and `{}` both write `{}`",
+            existing,
+            route,
+            output.to_string_lossy().replace('\\', "/")
+        )));
+    }
+    Ok(())
+}
+
+/// [`check_route_collision`] for every pair of `routes`. A route listed
+/// twice is not a collision.
+pub fn check_route_collisions<'a>(
+    routes: impl IntoIterator<Item = &'a str>,
+) -> Result<(), BundleError> {
+    let mut seen: Vec<&str> = Vec::new();
+    for route in routes {
+        for existing in &seen {
+            check_route_collision(existing, route)?;
+        }
+        seen.push(route);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Scope IDs
+// ---------------------------------------------------------------------------
+
+/// Derive the scope id for a canonical page id.
+///
+/// `zx-` followed by the 8-hex-digit FNV-1a hash of the id: stable across
+/// builds and machines, and safe as an unquoted HTML attribute value or CSS
+/// identifier. Collisions between pages are detected by the multi-page build.
+///
+/// Reserved for scoped CSS and not emitted yet: no `data-zx-scope` attribute
+/// appears in the bundled HTML.
+pub fn scope_id(page_id: &str) -> String {
+    const FNV_OFFSET: u32 = 0x811c_9dc5;
+    const FNV_PRIME: u32 = 0x0100_0193;
+
+    let hash = page_id.bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+    });
+    format!("zx-{:08x}", hash)
+}
+
+/// Fail if a page is listed twice (each page is one Rolldown entry, named
+/// by its id), or two distinct pages map to the same page id (which would
+/// overwrite each other's assets) or the same scope id.
+pub fn check_scope_collisions<'a>(
+    page_paths: impl IntoIterator<Item = &'a str>,
+    root: Option<&Path>,
+) -> Result<(), BundleError> {
+    let mut ids: std::collections::HashMap<String, &str> = std::collections::HashMap::new();
+    let mut seen: std::collections::HashMap<String, &str> = std::collections::HashMap::new();
+    for page_path in page_paths {
+        let page_id = canonicalize_page_id(page_path, root);
+        if let Some(existing) = ids.insert(page_id.clone(), page_path) {
+            if existing == page_path {
+                return Err(BundleError::ValidationError(format!(
+                    "Page `{}` is listed more than once",
+                    page_path
+                )));
+            }
+            return Err(BundleError::ValidationError(format!(
+                "Page id collision: `{}` and `{}` both map to `{}`",
+                existing, page_path, page_id
+            )));
+        }
+        let scope = scope_id(&page_id);
+        if let Some(existing) = seen.insert(scope.clone(), page_path) {
+            return Err(BundleError::ValidationError(format!(
+                "Scope id collision: `{}` and `{}` both map to `{}`",
+                existing, page_path, scope
+            )));
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Sourcemaps
+// ---------------------------------------------------------------------------
+
+/// Mark the `//#region` / `//#endregion` comments Rolldown wraps each module in.
+///
+/// Rolldown labels a region with its module id, a filesystem path (or a
+/// `\0zenith:` virtual id) that differs between machines. Only those regions
+/// are marked, along with the `//#endregion` that closes each of them, matched
+/// by nesting. A user's own `//#region helpers` and its end are kept.
+pub fn rolldown_region_lines(code: &str) -> Vec<bool> {
+    let mut open = Vec::new();
+    code.lines()
+        .map(|line| {
+            if let Some(label) = line.strip_prefix("//#region") {
+                let ours = is_module_region_label(label.trim());
+                open.push(ours);
+                ours
+            } else if line.trim_end() == "//#endregion" {
+                open.pop().unwrap_or(false)
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
+/// Relabel a Rolldown `//#region <module id>` comment with a
+/// machine-independent path: relative to the working directory when the
+/// module lies under it, else just the file name of an absolute path.
+/// Virtual module labels and other lines are returned unchanged.
+pub fn relativize_region_comment(line: &str) -> Cow<'_, str> {
+    let Some(label) = line.strip_prefix("//#region") else {
+        return Cow::Borrowed(line);
+    };
+    let label = label.trim();
+    if label.contains("zenith:") {
+        return Cow::Borrowed(line);
+    }
+
+    Cow::Owned(format!("//#region {}", project_relative_path(label, None)))
+}
+
+/// A machine-independent, `/`-separated form of a module path: relative to
+/// `root` (the working directory when `None`) when the path lies under it,
+/// else just the file name of an absolute path. Relative paths are kept.
+pub fn project_relative_path(path: &str, root: Option<&Path>) -> String {
+    let normalized = path.replace('\\', "/");
+    let relative = match root {
+        Some(root) => strip_root(&normalized, &root.to_string_lossy()),
+        None => strip_cwd(&normalized),
+    };
+    relative.unwrap_or_else(|| {
+        let absolute = normalized.starts_with('/') || normalized.get(1..2) == Some(":");
+        match normalized.rsplit_once('/') {
+            Some((_, file_name)) if absolute => file_name.to_string(),
+            _ => normalized.clone(),
+        }
+    })
+}
+
+/// Whether a `//#region` label is a module id: a path ending in a file name
+/// with an extension, or a Zenith virtual module.
+fn is_module_region_label(label: &str) -> bool {
+    if label.contains("zenith:") {
+        return true;
+    }
+    match label.rfind(['/', '\\']) {
+        Some(sep) => label[sep + 1..].contains('.'),
+        None => false,
+    }
+}
+
+/// Remove the mapping groups for generated lines that were stripped from a chunk.
+///
+/// `dropped[i]` is true when line `i` of the original chunk was removed.
+/// Only unmapped lines (Rolldown's `//#region` comments) may be dropped —
+/// removing a mapped line would corrupt the relative VLQ deltas that follow,
+/// so that is reported as an error.
+pub fn drop_sourcemap_lines(map_json: &str, dropped: &[bool]) -> Result<String, BundleError> {
+    let mut map: serde_json::Value = serde_json::from_str(map_json)
+        .map_err(|e| BundleError::BuildError(format!("Invalid sourcemap JSON: {}", e)))?;
+
+    if let Some(mappings) = map.get("mappings").and_then(|m| m.as_str()) {
+        let mut kept = Vec::new();
+        for (i, group) in mappings.split(';').enumerate() {
+            if !dropped.get(i).copied().unwrap_or(false) {
+                kept.push(group);
+            } else if !group.is_empty() {
+                return Err(BundleError::BuildError(format!(
+                    "Cannot drop generated line {} from the sourcemap: it is mapped",
+                    i + 1
+                )));
+            }
+        }
+        map["mappings"] = serde_json::Value::String(kept.join(";"));
+    }
+
+    Ok(map.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Chunk Graph
+// ---------------------------------------------------------------------------
+
+/// Collect every chunk reachable from `entry` through static imports.
+///
+/// `static_imports` maps a chunk filename to the filenames it statically
+/// imports. Dynamic `import()` edges must not be present in the map, so
+/// lazily-loaded chunks are never reached. The entry itself is excluded and
+/// the result is sorted for deterministic output.
+pub fn static_import_closure(
+    entry: &str,
+    static_imports: &std::collections::HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut seen = std::collections::BTreeSet::new();
+    let mut stack = vec![entry.to_string()];
+
+    while let Some(current) = stack.pop() {
+        for import in static_imports.get(&current).into_iter().flatten() {
+            if import != entry && seen.insert(import.clone()) {
+                stack.push(import.clone());
+            }
+        }
+    }
+
+    seen.into_iter().collect()
+}
+
+// ---------------------------------------------------------------------------
+// CSS Extraction
+// ---------------------------------------------------------------------------
+
+/// Collect the contents of every `<style>` block in a `.zen` source.
+///
+/// Blocks are trimmed and joined with `\n` in source order. Returns `None`
+/// when the source has no non-empty style block.
+pub fn extract_style_blocks(source: &str) -> Option<String> {
+    let re = Regex::new(r"(?is)<style\b[^>]*>(.*?)</style\s*>").unwrap();
+    let blocks: Vec<&str> = re
+        .captures_iter(source)
+        .filter_map(|caps| caps.get(1))
+        .map(|m| m.as_str().trim())
+        .filter(|css| !css.is_empty())
+        .collect();
+
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks.join("\n"))
+    }
+}
+
+/// `CssCache` key for a component's styles, derived from its `.zen` path
+/// (relative to `root`, as for [`canonicalize_page_id`]).
+///
+/// The `component:` prefix keeps these entries apart from page entries.
+pub fn component_css_key(component_path: &str, root: Option<&Path>) -> String {
+    format!("component:{}", canonicalize_page_id(component_path, root))
+}
+
+/// Check if `source` instantiates the component `tag` (`<Tag ...>` or `<Tag/>`).
+pub fn uses_component(source: &str, tag: &str) -> bool {
+    Regex::new(&format!(r"<{}[\s/>]", regex::escape(tag)))
+        .map(|re| re.is_match(source))
+        .unwrap_or(false)
+}
+
+/// Stitch a page's CSS: component styles first (each distinct stylesheet
+/// once, in order), then the page's own styles so they win on equal
+/// specificity. `None` when there is nothing to emit.
+pub fn stitch_css(component_css: &[String], page_css: Option<String>) -> Option<String> {
+    let mut blocks: Vec<&str> = Vec::new();
+    for css in component_css {
+        if !blocks.contains(&css.as_str()) {
+            blocks.push(css);
+        }
+    }
+    blocks.extend(page_css.as_deref());
+
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks.join("\n"))
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -196,7 +1221,7 @@ pub fn validate_placeholders(html: &str, expression_count: usize) -> Result<(),
 
     // Regex to find all data-zx-* attributes and capture their values (quoted or unquoted)
     // Matches: data-zx-something="value" OR data-zx-something='value' OR data-zx-something=value
-    let re = Regex::new(r#"data-zx-[a-z-]+=(?:"([^"]+)"|'([^']+)'|([^\s>"']+))"#).unwrap();
+    let re = Regex::new(r#"data-zx-[A-Za-z0-9_-]+=(?:"([^"]+)"|'([^']+)'|([^\s>"']+))"#).unwrap();
 
     for cap in re.captures_iter(html) {
         // Value is in group 1, 2, or 3
@@ -225,6 +1250,7 @@ pub fn validate_placeholders(html: &str, expression_count: usize) -> Result<(),
                     "Expected index {} in a data-zx-e or data-zx-on-* attribute",
                     i
                 )),
+                code: None,
             });
         }
     }
@@ -237,7 +1263,14 @@ pub fn validate_placeholders(html: &str, expression_count: usize) -> Result<(),
 }
 
 /// Validate that compiled expressions match metadata expressions exactly.
-pub fn validate_expressions(compiled: &[String], metadata: &[String]) -> Result<(), BundleError> {
+///
+/// A content mismatch names the element of `html` (the compiled page markup)
+/// that carries the expression's placeholder, via [`expression_context`].
+pub fn validate_expressions(
+    compiled: &[String],
+    metadata: &[String],
+    html: &str,
+) -> Result<(), BundleError> {
     if compiled.len() != metadata.len() {
         return Err(BundleError::ExpressionMismatch {
             expected: metadata.len(),
@@ -251,6 +1284,7 @@ pub fn validate_expressions(compiled: &[String], metadata: &[String]) -> Result<
                 index: i,
                 expected: expected.clone(),
                 got: got.clone(),
+                near: expression_context(html, i),
             });
         }
     }
@@ -258,6 +1292,180 @@ pub fn validate_expressions(compiled: &[String], metadata: &[String]) -> Result<
     Ok(())
 }
 
+/// Opening tag of the first element in `html` whose `data-zx-*` attribute
+/// references expression `index`, e.g. `<h1 class="title" data-zx-e="3">`.
+///
+/// Tags longer than 80 characters are cut short with `...`. Returns `None`
+/// when no placeholder references the index.
+pub fn expression_context(html: &str, index: usize) -> Option<String> {
+    const MAX_LEN: usize = 80;
+    let tag_re = Regex::new(r"<[A-Za-z][^<>]*>").unwrap();
+    let attr_re =
+        Regex::new(r#"data-zx-[A-Za-z0-9_-]+=(?:"([^"]+)"|'([^']+)'|([^\s>"']+))"#).unwrap();
+
+    let tag = tag_re.find_iter(html).map(|m| m.as_str()).find(|tag| {
+        attr_re.captures_iter(tag).any(|cap| {
+            let value = cap
+                .get(1)
+                .or(cap.get(2))
+                .or(cap.get(3))
+                .map_or("", |m| m.as_str());
+            value
+                .split_whitespace()
+                .any(|part| part.parse() == Ok(index))
+        })
+    })?;
+
+    Some(match tag.char_indices().nth(MAX_LEN) {
+        Some((cut, _)) => format!("{}...", &tag[..cut]),
+        None => tag.to_string(),
+    })
+}
+
+/// Validate the compiler's marker binding indices against the expression table.
+///
+/// An empty table means the compiler emitted no marker bindings and passes.
+/// Otherwise there must be exactly one marker per expression, so every index
+/// has to be in bounds and appear once.
+pub fn validate_marker_bindings(
+    indices: impl IntoIterator<Item = usize>,
+    expression_count: usize,
+) -> Result<(), BundleError> {
+    let indices: Vec<usize> = indices.into_iter().collect();
+    if indices.is_empty() {
+        return Ok(());
+    }
+    if indices.len() != expression_count {
+        return Err(BundleError::MarkerMismatch {
+            expected: expression_count,
+            got: indices.len(),
+        });
+    }
+
+    let mut seen = std::collections::BTreeSet::new();
+    for index in indices {
+        if index >= expression_count {
+            return Err(BundleError::ValidationError(format!(
+                "Marker binding index out of bounds: {} ({} expressions)",
+                index, expression_count
+            )));
+        }
+        if !seen.insert(index) {
+            return Err(BundleError::ValidationError(format!(
+                "Duplicate marker binding index {}",
+                index
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Flag event bindings whose expression is obviously not a function.
+///
+/// Heuristic, so findings are `Warning`s: a literal, state whose initial
+/// value is a literal (number, string, boolean or `null`), or a call such as
+/// `increment()` (the handler's result is bound, not the handler — the usual
+/// "forgot the arrow" mistake). Anything else, including identifiers, arrow
+/// functions and state holding either, passes. The runtime would
+/// otherwise only fail at hydration with "did not resolve to a function".
+pub fn check_event_handlers(output: &CompilerOutput) -> Vec<Diagnostic> {
+    let call_re = Regex::new(r"^[A-Za-z_$][\w$]*(?:\.[A-Za-z_$][\w$]*)*\s*\(.*\)$").unwrap();
+    let literal_state = |state_index: usize| {
+        output.hoisted.state.get(state_index).is_some_and(|state| {
+            let value = state.value.trim();
+            value.starts_with(['"', '\'', '`'])
+                || value.parse::<f64>().is_ok()
+                || matches!(value, "true" | "false" | "null")
+        })
+    };
+
+    output
+        .event_bindings
+        .iter()
+        .filter_map(|binding| {
+            let expression = output.expressions.get(binding.index)?.trim();
+            // The state the handler is bound through (directly or via its
+            // signal), or the state named like the expression
+            let reads_literal_state = output
+                .expression_bindings
+                .iter()
+                .filter(|b| b.marker_index == binding.index)
+                .filter_map(|b| {
+                    b.state_index.or_else(|| {
+                        b.signal_index
+                            .and_then(|index| output.signals.get(index))
+                            .map(|signal| signal.state_index)
+                    })
+                })
+                .chain(
+                    output
+                        .hoisted
+                        .state
+                        .iter()
+                        .position(|state| state.key == expression),
+                )
+                .any(literal_state);
+            let reason = if expression.starts_with(['"', '\'', '`'])
+                || expression.starts_with(['{', '['])
+                || expression.parse::<f64>().is_ok()
+                || matches!(expression, "true" | "false" | "null" | "undefined")
+            {
+                "it is a literal"
+            } else if reads_literal_state {
+                "it reads state rather than naming a handler"
+            } else if call_re.is_match(expression) && !expression.contains("=>") {
+                "it calls the handler instead of passing it (wrap it in `() => ...`)"
+            } else {
+                return None;
+            };
+            Some(Diagnostic {
+                level: DiagnosticLevel::Warning,
+                message: format!(
+                    "Event binding {} (on:{}) expression `{}` is not a function: {}",
+                    binding.index, binding.event, expression, reason
+                ),
+                context: Some(binding.selector.clone()),
+                code: Some(codes::EVENT_HANDLER_NOT_CALLABLE.into()),
+            })
+        })
+        .collect()
+}
+
+/// Parse a generated ES module and reject it if it is not valid JavaScript.
+///
+/// Opt-in (parsing every module costs build time). `module` names the
+/// module in the error, which also carries the line/column and source line
+/// of the first offending construct.
+pub fn validate_js_syntax(code: &str, module: &str) -> Result<(), BundleError> {
+    let allocator = oxc_allocator::Allocator::default();
+    let parsed = oxc_parser::Parser::new(&allocator, code, oxc_span::SourceType::mjs()).parse();
+
+    let Some(error) = parsed.errors.first() else {
+        return Ok(());
+    };
+
+    let offset = error
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.first())
+        .map_or(0, |label| label.offset())
+        .min(code.len());
+    let line_start = code[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = code[offset..].find('\n').map_or(code.len(), |i| offset + i);
+    let line = code[..offset].matches('\n').count() + 1;
+    let column = offset - line_start + 1;
+
+    Err(BundleError::ValidationError(format!(
+        "invalid JS in {}:{}:{}: {} — `{}`",
+        module,
+        line,
+        column,
+        error.message,
+        code[line_start..line_end].trim()
+    )))
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -296,45 +1504,605 @@ mod tests {
         assert_eq!(escape_js_template_literal("a`b"), "a\\`b");
         assert_eq!(escape_js_template_literal("${x}"), "\\${x}");
         assert_eq!(escape_js_template_literal("a\\b"), "a\\\\b");
+        assert_eq!(escape_js_template_literal("cost: $"), "cost: \\$");
+        assert_eq!(
+            escape_js_template_literal("a\u{2028}b\u{2029}c"),
+            "a\\u2028b\\u2029c"
+        );
+    }
+
+    #[test]
+    fn test_escape_js_template_literal_breaks_script_end_tags() {
+        assert_eq!(
+            escape_js_template_literal("<code><script>x()</script></code>"),
+            "<code><script>x()<\\/script></code>"
+        );
+        assert_eq!(escape_js_template_literal("</SCRIPT >"), "<\\/SCRIPT >");
+        assert_eq!(escape_js_template_literal("</scrip"), "</scrip");
+        assert_eq!(escape_js_template_literal("</style>"), "</style>");
+    }
+
+    #[test]
+    fn test_escape_js_template_literal_fragments_concatenate_safely() {
+        let joined = escape_js_template_literal("a$") + &escape_js_template_literal("{b}");
+        assert_eq!(joined, "a\\${b}");
+        assert!(!joined.replace("\\$", "").contains("${"));
     }
 
     #[test]
     fn test_escape_js_string() {
         assert_eq!(escape_js_string(r#"he said "hi""#), r#"he said \"hi\""#);
         assert_eq!(escape_js_string("line1\nline2"), "line1\\nline2");
+        assert_eq!(escape_js_string("a\u{2028}b\u{2029}"), "a\\u2028b\\u2029");
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let hash = content_hash("export const a = 1;");
+        assert_eq!(hash.len(), CONTENT_HASH_LEN);
+        assert!(hash
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        assert_eq!(hash, content_hash("export const a = 1;"));
+        assert_ne!(hash, content_hash("export const a = 2;"));
+        // Truncated SHA-256 of the empty string
+        assert_eq!(content_hash(""), "e3b0c44298fc1c14");
+    }
+
+    #[test]
+    fn test_asset_filename() {
+        let js = "export const a = 1;";
+        let hash = content_hash(js);
+        assert_eq!(
+            asset_filename("about", js, AssetKind::Entry),
+            format!("assets/about.{}.js", hash)
+        );
+        assert_eq!(
+            asset_filename("about", ".a{}", AssetKind::Css),
+            format!("assets/about.{}.css", content_hash(".a{}"))
+        );
+        assert_eq!(
+            asset_filename("card", js, AssetKind::Component),
+            format!("assets/component.card.{}.js", hash)
+        );
+        // Shared modules are named the same whichever page asks
+        assert_eq!(
+            asset_filename("about", js, AssetKind::Runtime),
+            asset_filename("home", js, AssetKind::Runtime)
+        );
+        assert_eq!(
+            asset_filename("", js, AssetKind::Router),
+            format!("assets/router.{}.js", hash)
+        );
+    }
+
+    #[test]
+    fn test_rolldown_region_lines() {
+        let code = "//#region /home/me/site/src/page.zen\n\
+                    const a = 1;\n\
+                    //#region helpers\n\
+                    const b = 2;\n\
+                    //#endregion\n\
+                    //#endregion\n\
+                    //#region C:\\site\\lib\\util.js\n\
+                    //#endregion\n\
+                    //#region \0zenith:entry:page\n\
+                    //#endregion";
+        assert_eq!(
+            rolldown_region_lines(code),
+            vec![true, false, false, false, false, true, true, true, true, true]
+        );
+        // A stray end tag is left alone
+        assert_eq!(rolldown_region_lines("//#endregion"), vec![false]);
+    }
+
+    #[test]
+    fn test_relativize_region_comment() {
+        let cwd = std::env::current_dir().unwrap();
+        let inside = cwd.join("src").join("page.zen");
+        assert_eq!(
+            relativize_region_comment(&format!("//#region {}", inside.to_string_lossy())),
+            "//#region src/page.zen"
+        );
+        assert_eq!(
+            relativize_region_comment("//#region /elsewhere/lib/util.js"),
+            "//#region util.js"
+        );
+        assert_eq!(
+            relativize_region_comment("//#region C:\\site\\lib\\util.js"),
+            "//#region util.js"
+        );
+        for unchanged in [
+            "//#region \0zenith:entry:page",
+            "//#endregion",
+            "const a = 1;",
+        ] {
+            assert_eq!(relativize_region_comment(unchanged), unchanged);
+        }
+    }
+
+    #[test]
+    fn test_project_relative_path_prefers_the_root() {
+        let root = Path::new("/home/dev/site");
+        assert_eq!(
+            project_relative_path("/home/dev/site/src/pages/index.zen", Some(root)),
+            "src/pages/index.zen"
+        );
+        assert_eq!(
+            project_relative_path("/opt/lib/util.js", Some(root)),
+            "util.js"
+        );
+        assert_eq!(
+            project_relative_path("pages/about.zen", Some(root)),
+            "pages/about.zen"
+        );
+    }
+
+    #[test]
+    fn test_drop_sourcemap_lines() {
+        let map = r#"{"version":3,"sources":["a.js"],"names":[],"mappings":";AAAA;;AACA"}"#;
+        let dropped = drop_sourcemap_lines(map, &[true, false, true, false]).unwrap();
+        let dropped: serde_json::Value = serde_json::from_str(&dropped).unwrap();
+        assert_eq!(dropped["mappings"], "AAAA;AACA");
+
+        let err = drop_sourcemap_lines(map, &[false, true]).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(normalize_source("\u{FEFF}<p>\r\nhi</p>"), "<p>\nhi</p>");
+        assert_eq!(normalize_source("<p>hi</p>"), "<p>hi</p>");
+        // Only a leading BOM is source encoding; anything later is content
+        assert_eq!(normalize_source("<p>\u{FEFF}</p>"), "<p>\u{FEFF}</p>");
+    }
+
+    #[test]
+    fn test_normalize_expression_whitespace() {
+        assert_eq!(normalize_expression_whitespace(" a "), "a");
+        assert_eq!(normalize_expression_whitespace("a  +\n\tb"), "a + b");
+        // Literal contents are meaning, not layout
+        assert_eq!(
+            normalize_expression_whitespace(r#"  greet( "a  b",  'c \'  d' ) "#),
+            r#"greet( "a  b", 'c \'  d' )"#
+        );
+        assert_eq!(
+            normalize_expression_whitespace("`x  ${ y }`"),
+            "`x  ${ y }`"
+        );
+
+        // Comments keep their text, and a line comment its newline
+        assert_eq!(
+            normalize_expression_whitespace("a  // sum  of\n   +  b  /* both  */"),
+            "a // sum  of\n+ b /* both  */"
+        );
+        // Regex literals are not division
+        assert_eq!(
+            normalize_expression_whitespace("/a  b/.test( s )  &&  x  /  2"),
+            "/a  b/.test( s ) && x / 2"
+        );
+        assert_eq!(
+            normalize_expression_whitespace("s.replace( /[ /]  +/g,  ' ' )"),
+            "s.replace( /[ /]  +/g, ' ' )"
+        );
+        assert_eq!(
+            normalize_expression_whitespace("(a)  /  b  /  c"),
+            "(a) / b / c"
+        );
+    }
+
+    #[test]
+    fn test_page_route() {
+        assert_eq!(page_route("src/pages/index.zen"), "/");
+        assert_eq!(page_route("/site/src/pages/blog/Post.zen"), "/blog/Post");
+        assert_eq!(page_route("src\\pages\\docs\\index.zen"), "/docs");
+        assert_eq!(page_route("/tmp/about.zen"), "/about");
+    }
+
+    #[test]
+    fn test_route_to_output_path() {
+        let path = |route| {
+            route_to_output_path(route)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        assert_eq!(path("/"), "index.html");
+        assert_eq!(path("/blog/post"), "blog/post/index.html");
+    }
+
+    #[test]
+    fn test_route_to_output_path_keeps_dynamic_segments() {
+        let path = |route| {
+            route_to_output_path(route)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        // Single param
+        assert_eq!(path("/users/:id"), "users/_id/index.html");
+        // Multiple params
+        assert_eq!(path("/:lang/:slug"), "_lang/_slug/index.html");
+        // Mixed static and dynamic segments
+        assert_eq!(path("/users/:slug/detail"), "users/_slug/detail/index.html");
+        assert_ne!(path("/users/:id"), path("/users/:slug/detail"));
+        assert_ne!(path("/users/:id"), path("/users"));
+    }
+
+    #[test]
+    fn test_route_to_output_path_catch_all_and_optional() {
+        let path = |route| {
+            route_to_output_path(route)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        assert_eq!(path("/files/*"), "files/__rest/index.html");
+        assert_eq!(path("/docs/:page*"), "docs/__page/index.html");
+        assert_eq!(path("/blog/:slug?"), "blog/_slug/index.html");
+        assert_ne!(path("/files/*"), path("/files/:rest"));
+    }
+
+    #[test]
+    fn test_check_route_collisions() {
+        assert!(check_route_collisions([
+            "/",
+            "/users",
+            "/users/:id",
+            "/users/:id/posts",
+            "/users/admin",
+            "/blog/:slug?",
+            "/files/*",
+            "/users/:id",
+        ])
+        .is_ok());
+
+        let err =
+            |routes: [&'static str; 2]| check_route_collisions(routes).unwrap_err().to_string();
+        assert!(err(["/users/:id", "/users/:name"]).contains("match the same paths"));
+        assert!(err(["/files/*", "/files/:path*"]).contains("match the same paths"));
+        assert!(err(["/users/:id", "/users/_id"]).contains("both write `users/_id/index.html`"));
+        assert!(err(["/blog/:slug?", "/blog/:slug"]).contains("both write `blog/_slug/index.html`"));
+        assert!(err(["/files/*", "/files/__rest"]).contains("both write"));
+    }
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(
+            parse_route("/files/:id/*").unwrap(),
+            vec![
+                RouteSegment::Static {
+                    value: "files".into()
+                },
+                RouteSegment::Param { name: "id".into() },
+                RouteSegment::Rest {
+                    name: "rest".into()
+                },
+            ]
+        );
+        assert_eq!(
+            parse_route("/blog/:slug?").unwrap()[1],
+            RouteSegment::Optional {
+                name: "slug".into()
+            }
+        );
+        assert!(parse_route("/").unwrap().is_empty());
+
+        let err = |route| parse_route(route).unwrap_err().to_string();
+        assert!(err("/files/*/edit").contains("catch-all segment must be last"));
+        assert!(err("/:lang?/about").contains("optional segment followed by a required one"));
+        assert!(err("/users/:").contains("parameter without a name"));
+        assert!(parse_route("/:lang?/:page*").is_ok());
     }
 
     #[test]
     fn test_canonicalize_page_id() {
-        assert_eq!(canonicalize_page_id("index.zen"), "index");
-        assert_eq!(canonicalize_page_id("/pages/About.zen"), "about");
+        assert_eq!(canonicalize_page_id("index.zen", None), "index");
+        assert_eq!(
+            canonicalize_page_id("/pages/About.zen", None),
+            "pages-_about"
+        );
+        assert_eq!(
+            canonicalize_page_id("./src/Blog/index.zen", None),
+            "src-_blog-index"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_page_id_distinguishes_directories() {
+        let blog = canonicalize_page_id("src/blog/index.zen", None);
+        let docs = canonicalize_page_id("src/docs/index.zen", None);
+        assert_ne!(blog, docs);
+        assert!(check_scope_collisions(["src/blog/index.zen", "src/docs/index.zen"], None).is_ok());
+    }
+
+    #[test]
+    fn test_canonicalize_page_id_is_injective() {
+        let ids = [
+            "src/blog/index.zen",
+            "src/blog-index.zen",
+            "src/blog_-index.zen",
+            "src-blog/index.zen",
+            "src/Home.zen",
+            "src/home.zen",
+            "src/_home.zen",
+            "a/x.zen",
+            "../a/x.zen",
+        ]
+        .map(|path| canonicalize_page_id(path, None));
+        let distinct: std::collections::HashSet<&String> = ids.iter().collect();
+        assert_eq!(distinct.len(), ids.len(), "{:?}", ids);
+        assert_eq!(ids[1], "src-blog_-index");
+        assert_eq!(ids[4], "src-_home");
+        assert_eq!(ids[6], "src-___home");
+        assert_eq!(ids[8], "..-a-x");
+
+        // `..` only survives when nothing precedes it to cancel
+        assert_eq!(canonicalize_page_id("src/../a/x.zen", None), "a-x");
+    }
+
+    #[test]
+    fn test_canonicalize_page_id_os_independent() {
+        assert_eq!(
+            canonicalize_page_id("src\\blog\\index.zen", None),
+            canonicalize_page_id("src/blog/index.zen", None)
+        );
+        assert_eq!(
+            canonicalize_page_id("C:\\site\\about.zen", None),
+            canonicalize_page_id("/site/about.zen", None)
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_page_id_relative_to_root() {
+        // The same page under two checkouts gets the same id
+        for root in ["/home/ci/site", "C:\\Users\\dev\\site\\"] {
+            let page = format!("{}/src/about.zen", root.trim_end_matches('\\'));
+            assert_eq!(
+                canonicalize_page_id(&page, Some(Path::new(root))),
+                "src-about"
+            );
+        }
+        // A path outside the root is used as given
+        assert_eq!(
+            canonicalize_page_id("/elsewhere/about.zen", Some(Path::new("/home/ci/site"))),
+            "elsewhere-about"
+        );
+    }
+
+    #[test]
+    fn test_extract_style_blocks() {
+        let source =
+            "<style>\n.a { color: red; }\n</style><h1>{t}</h1><STYLE media=\"print\">.b{}</STYLE>";
+        assert_eq!(
+            extract_style_blocks(source).as_deref(),
+            Some(".a { color: red; }\n.b{}")
+        );
+        assert_eq!(extract_style_blocks("<h1>{t}</h1><style> </style>"), None);
+    }
+
+    #[test]
+    fn test_uses_component() {
+        let source = "<Card title=\"a\" /><Card/><CardList>";
+        assert!(uses_component(source, "Card"));
+        assert!(uses_component(source, "CardList"));
+        assert!(!uses_component(source, "Car"));
+        assert!(!uses_component(source, "Button"));
+    }
+
+    #[test]
+    fn test_stitch_css_dedupes_component_styles() {
+        let card = ".card { padding: 1rem; }".to_string();
+        assert_eq!(
+            stitch_css(
+                &[card.clone(), ".btn {}".into(), card.clone()],
+                Some(".hero {}".into())
+            )
+            .as_deref(),
+            Some(".card { padding: 1rem; }\n.btn {}\n.hero {}")
+        );
+        assert_eq!(
+            stitch_css(&[], Some(".hero {}".into())).as_deref(),
+            Some(".hero {}")
+        );
+        assert_eq!(stitch_css(&[], None), None);
+        assert_eq!(
+            component_css_key("src/components/Card.zen", None),
+            "component:src-components-card"
+        );
     }
 
     #[test]
     fn test_validate_expressions_match() {
         let compiled = vec!["a".into(), "b".into()];
         let metadata = vec!["a".into(), "b".into()];
-        assert!(validate_expressions(&compiled, &metadata).is_ok());
+        assert!(validate_expressions(&compiled, &metadata, "").is_ok());
     }
 
     #[test]
     fn test_validate_expressions_length_mismatch() {
         let compiled = vec!["a".into()];
         let metadata = vec!["a".into(), "b".into()];
-        assert!(validate_expressions(&compiled, &metadata).is_err());
+        assert!(validate_expressions(&compiled, &metadata, "").is_err());
     }
 
     #[test]
     fn test_validate_expressions_content_mismatch() {
         let compiled = vec!["a".into(), "c".into()];
         let metadata = vec!["a".into(), "b".into()];
-        let err = validate_expressions(&compiled, &metadata).unwrap_err();
+        let html = r#"<h1 data-zx-e="0"></h1><p class="lead" data-zx-e="1"></p>"#;
+        let err = validate_expressions(&compiled, &metadata, html).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with(r#"(near <p class="lead" data-zx-e="1">)"#));
         match err {
-            BundleError::ExpressionContentMismatch { index, .. } => assert_eq!(index, 1),
+            BundleError::ExpressionContentMismatch { index, near, .. } => {
+                assert_eq!(index, 1);
+                assert_eq!(near.as_deref(), Some(r#"<p class="lead" data-zx-e="1">"#));
+            }
             _ => panic!("Expected ExpressionContentMismatch"),
         }
     }
 
+    #[test]
+    fn test_expression_context() {
+        let html = r#"<div data-zx-e="0 2"><button data-zx-on-click='1'>Go</button></div>"#;
+        assert_eq!(
+            expression_context(html, 1).as_deref(),
+            Some("<button data-zx-on-click='1'>")
+        );
+        assert_eq!(
+            expression_context(html, 2).as_deref(),
+            Some(r#"<div data-zx-e="0 2">"#)
+        );
+        assert_eq!(expression_context(html, 3), None);
+
+        let long = format!(r#"<p data-zx-e="0" class="{}">"#, "x".repeat(100));
+        let near = expression_context(&long, 0).unwrap();
+        assert_eq!(near.len(), 83);
+        assert!(near.ends_with("..."));
+    }
+
+    #[test]
+    fn test_is_bare_specifier() {
+        assert!(is_bare_specifier("gsap"));
+        assert!(is_bare_specifier("@scope/pkg/sub"));
+        assert!(!is_bare_specifier("./local.js"));
+        assert!(!is_bare_specifier("../up.js"));
+        assert!(!is_bare_specifier("/abs/file.js"));
+        assert!(!is_bare_specifier("page.zen"));
+        assert!(!is_bare_specifier("\0zenith:css:home"));
+    }
+
+    #[test]
+    fn test_is_external() {
+        let patterns = vec!["gsap".to_string(), "@motion/*".to_string()];
+        assert!(is_external("gsap", &patterns));
+        assert!(is_external("gsap/ScrollTrigger", &patterns));
+        assert!(!is_external("gsap-lite", &patterns));
+        assert!(is_external("@motion/core", &patterns));
+        assert!(is_external("@motion/core/dist/index.js", &patterns));
+        assert!(!is_external("@motionless/core", &patterns));
+        assert!(!is_external("./gsap", &patterns));
+        assert!(!is_external("lodash", &patterns));
+        assert!(!is_external("gsap", &[]));
+
+        let patterns = vec!["*-icons".to_string()];
+        assert!(is_external("lucide-icons", &patterns));
+        assert!(!is_external("lucide-icons-extra", &patterns));
+    }
+
+    #[test]
+    fn test_manual_chunk_regex() {
+        let re = Regex::new(&manual_chunk_regex(&[
+            "gsap".to_string(),
+            "@motion/*".to_string(),
+            "*-icons".to_string(),
+        ]))
+        .unwrap();
+        assert!(re.is_match("/app/node_modules/gsap/dist/gsap.js"));
+        assert!(re.is_match(r"C:\app\node_modules\gsap\index.js"));
+        assert!(!re.is_match("/app/node_modules/gsap-lite/index.js"));
+        assert!(re.is_match("/app/node_modules/@motion/core/dist/index.js"));
+        assert!(re.is_match(r"C:\app\node_modules\@motion\dom\index.js"));
+        assert!(!re.is_match("/app/node_modules/@motion-extra/core/index.js"));
+        assert!(re.is_match("/app/node_modules/lucide-icons/index.js"));
+        // `*` stays within the package name
+        assert!(!re.is_match("/app/node_modules/lucide/dist/x-icons/index.js"));
+        assert!(!re.is_match("/app/src/anim/lucide-icons.js"));
+    }
+
+    #[test]
+    fn test_validate_marker_bindings() {
+        assert!(validate_marker_bindings([], 2).is_ok());
+        assert!(validate_marker_bindings([1, 0], 2).is_ok());
+
+        match validate_marker_bindings([0], 2).unwrap_err() {
+            BundleError::MarkerMismatch { expected, got } => assert_eq!((expected, got), (2, 1)),
+            other => panic!("Expected MarkerMismatch, got {other:?}"),
+        }
+
+        let err = validate_marker_bindings([0, 2], 2).unwrap_err().to_string();
+        assert!(err.contains("index out of bounds: 2"), "{err}");
+
+        let err = validate_marker_bindings([1, 1], 2).unwrap_err().to_string();
+        assert!(err.contains("Duplicate marker binding index 1"), "{err}");
+    }
+
+    #[test]
+    fn test_check_event_handlers_flags_non_callables() {
+        use zenith_compiler::compiler::{EventBinding, StateBinding};
+
+        let expressions = [
+            "handleClick",
+            "() => count.set(1)",
+            "'submit'",
+            "count",
+            "increment()",
+            "onToggle",
+            "toggle",
+            "submit",
+        ];
+        let output = CompilerOutput {
+            expressions: expressions.iter().map(|e| e.to_string()).collect(),
+            event_bindings: (0..expressions.len())
+                .map(|index| EventBinding {
+                    index,
+                    event: "click".into(),
+                    selector: format!("[data-zx-on-click=\"{index}\"]"),
+                })
+                .collect(),
+            hoisted: zenith_compiler::compiler::Hoisted {
+                state: vec![
+                    StateBinding {
+                        key: "count".into(),
+                        value: "0".into(),
+                    },
+                    StateBinding {
+                        key: "onToggle".into(),
+                        value: "() => open.set(!open.get())".into(),
+                    },
+                    StateBinding {
+                        key: "toggle".into(),
+                        value: "toggle".into(),
+                    },
+                    StateBinding {
+                        key: "label".into(),
+                        value: "'Send'".into(),
+                    },
+                ],
+                ..Default::default()
+            },
+            // How compiler IR refers to handlers: through their state
+            // entry. `submit` is bound to a string instead
+            expression_bindings: serde_json::from_value(serde_json::json!([
+                { "marker_index": 6, "state_index": 2 },
+                { "marker_index": 7, "state_index": 3 }
+            ]))
+            .unwrap(),
+            ..Default::default()
+        };
+
+        let diagnostics = check_event_handlers(&output);
+        let flagged: Vec<&str> = diagnostics
+            .iter()
+            .map(|d| d.context.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![
+                "[data-zx-on-click=\"2\"]",
+                "[data-zx-on-click=\"3\"]",
+                "[data-zx-on-click=\"4\"]",
+                "[data-zx-on-click=\"7\"]"
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.level == DiagnosticLevel::Warning
+                && d.code.as_deref() == Some(codes::EVENT_HANDLER_NOT_CALLABLE)));
+        assert!(diagnostics[0].message.contains("Event binding 2"));
+        assert!(diagnostics[2].message.contains("`increment()`"));
+        assert!(diagnostics[2].message.contains("() =>"));
+    }
+
     #[test]
     fn test_generate_virtual_entry() {
         let output = CompilerOutput {
@@ -349,12 +2117,170 @@ mod tests {
             marker_bindings: Default::default(),
             event_bindings: Default::default(),
         };
-        let entry = generate_virtual_entry(&output);
+        let entry = generate_virtual_entry(&output, DEFAULT_CONTRACT_VERSION);
         assert!(entry.contains("__zenith_html"));
         assert!(entry.contains("__zenith_expr"));
         assert!(entry.contains("\"title\""));
         // Inside a JS template literal, double quotes are NOT escaped
         assert!(entry.contains("data-zx-e=\"0\""));
+        assert!(entry.contains("export const __zenith_contract = \"v0\";"));
+    }
+
+    #[test]
+    fn test_validate_contract_version() {
+        assert!(validate_contract_version(DEFAULT_CONTRACT_VERSION).is_ok());
+        let err = validate_contract_version("v9").unwrap_err().to_string();
+        assert!(err.contains("'v9'"), "{}", err);
+        assert!(err.contains("supported: v0"), "{}", err);
+    }
+
+    #[test]
+    fn test_generate_hydration_entry() {
+        let output = CompilerOutput {
+            ir_version: 1,
+            html: "<h1 data-zx-e=\"0\"></h1><p data-zx-e=\"1\"></p>".into(),
+            expressions: vec!["title".into(), "count".into()],
+            ..Default::default()
+        };
+        let entry =
+            generate_hydration_entry(&output, "/assets/runtime.js", "v0", true, None).unwrap();
+
+        // Contract exports come first, untouched
+        assert!(entry.starts_with(&generate_virtual_entry(&output, "v0")));
+        assert!(entry.contains(r#"from "/assets/runtime.js";"#));
+        // One marker per expression, derived from the placeholders
+        assert!(entry.contains(
+            r#"const __zenith_markers = [{"index":0,"kind":"text","selector":"[data-zx-e~=\"0\"]"},{"index":1,"kind":"text","selector":"[data-zx-e~=\"1\"]"}];"#
+        ));
+        assert!(entry.contains("const __zenith_state_values = Object.freeze([]);"));
+        assert!(entry.contains(
+            r#"Object.freeze([{"literal":"title","marker_index":0},{"literal":"count","marker_index":1}])"#
+        ));
+        assert!(entry.contains("hydrate({\n  root: document,\n  ir_version: 1,"));
+        assert!(validate_js_syntax(&entry, "entry").is_ok());
+
+        let named =
+            generate_hydration_entry(&output, "/assets/runtime.js", "v0", false, None).unwrap();
+        assert!(named.starts_with(&generate_named_entry(&output, "v0")));
+        assert!(!named.contains("__zenith_page"));
+
+        // An expression without a placeholder cannot be given a marker
+        let unplaced = CompilerOutput {
+            html: "<h1 data-zx-e=\"0\"></h1>".into(),
+            ..output
+        };
+        let err = generate_hydration_entry(&unplaced, "/assets/runtime.js", "v0", true, None)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("missing marker for expression index 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_generate_hydration_entry_with_bindings_sidecar() {
+        let output = CompilerOutput {
+            ir_version: 1,
+            html: "<h1 data-zx-e=\"0\"></h1>".into(),
+            expressions: vec!["title".into()],
+            hoisted: zenith_compiler::compiler::Hoisted {
+                state: vec![zenith_compiler::compiler::StateBinding {
+                    key: "title".into(),
+                    value: "'Hello'".into(),
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let json = generate_bindings_json(&output).unwrap();
+        assert_eq!(
+            json,
+            r#"{"events":[],"expressions":[{"literal":"title","marker_index":0}],"markers":[{"index":0,"kind":"text","selector":"[data-zx-e~=\"0\"]"}],"signals":[],"state":["title"]}"#
+        );
+        let file = bindings_filename("index", &json);
+        assert_eq!(file, format!("index.bindings.{}.json", content_hash(&json)));
+
+        let entry =
+            generate_hydration_entry(&output, "/assets/runtime.js", "v0", true, Some(&file))
+                .unwrap();
+        assert!(entry.contains(&format!(r#"fetch(new URL("./{}", import.meta.url))"#, file)));
+        assert!(entry.contains("if (!response.ok)"), "{}", entry);
+        assert!(entry.contains(".catch((error) =>"), "{}", entry);
+        assert!(entry.contains("const __zenith_state_values = Object.freeze([\n  'Hello',\n]);"));
+        assert!(!entry.contains("__zenith_markers"));
+        assert!(!entry.contains("__zenith_expression_bindings"));
+        assert!(validate_js_syntax(&entry, "entry").is_ok());
+    }
+
+    #[test]
+    fn test_validate_js_syntax_accepts_module() {
+        let code = "import { a } from './a.js';\nexport const b = a + 1;\n";
+        assert!(validate_js_syntax(code, "entry").is_ok());
+    }
+
+    #[test]
+    fn test_validate_js_syntax_points_at_error() {
+        let code = "const ok = 1;\nconst = ;\n";
+        let err = validate_js_syntax(code, "entry").unwrap_err().to_string();
+        assert!(err.contains("entry:2:"), "{}", err);
+        assert!(err.contains("`const = ;`"), "{}", err);
+    }
+
+    #[test]
+    fn test_scope_id_distinct_and_stable() {
+        let home = scope_id("home");
+        let about = scope_id("about");
+
+        assert_ne!(home, about);
+        assert_eq!(home, scope_id("home"));
+        // Pinned: changing the derivation would invalidate every emitted stylesheet
+        assert_eq!(home, "zx-d2c8c28e");
+        assert!(home.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+    }
+
+    #[test]
+    fn test_check_scope_collisions() {
+        assert!(check_scope_collisions(["/pages/home.zen", "/pages/about.zen"], None).is_ok());
+        assert!(check_scope_collisions(["/pages/Home.zen", "/pages/home.zen"], None).is_ok());
+        let err = check_scope_collisions(["/pages/home.zen", "pages/home.zen"], None).unwrap_err();
+        assert!(err.to_string().contains("Page id collision"), "{}", err);
+        let err = check_scope_collisions(["/pages/home.zen", "/pages/home.zen"], None).unwrap_err();
+        assert!(err.to_string().contains("listed more than once"), "{}", err);
+    }
+
+    #[test]
+    fn test_static_import_closure_excludes_dynamic_chunks() {
+        use std::collections::HashMap;
+
+        // entry → core → shared (static); entry ⇢ anim (dynamic, absent from the map)
+        let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+        graph.insert("index.js".into(), vec!["runtime-core.js".into()]);
+        graph.insert("runtime-core.js".into(), vec!["shared.js".into()]);
+        graph.insert("shared.js".into(), vec![]);
+        graph.insert("runtime-anim.js".into(), vec!["anim-dep.js".into()]);
+
+        assert_eq!(
+            static_import_closure("index.js", &graph),
+            vec!["runtime-core.js".to_string(), "shared.js".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_static_import_closure_sorted_and_acyclic() {
+        use std::collections::HashMap;
+
+        let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+        graph.insert("index.js".into(), vec!["z.js".into(), "a.js".into()]);
+        graph.insert("a.js".into(), vec!["index.js".into(), "z.js".into()]);
+        graph.insert("z.js".into(), vec!["a.js".into()]);
+
+        assert_eq!(
+            static_import_closure("index.js", &graph),
+            vec!["a.js".to_string(), "z.js".to_string()]
+        );
     }
 
     #[test]
@@ -363,6 +2289,12 @@ mod tests {
         assert!(validate_placeholders(html, 2).is_ok());
     }
 
+    #[test]
+    fn test_validate_placeholders_camel_case_and_numeric_names() {
+        let html = r#"<button data-zx-on-Click="0">Go</button><img data-zx-attr2="1"><p data-zx-on_hover=2></p>"#;
+        assert!(validate_placeholders(html, 3).is_ok());
+    }
+
     #[test]
     fn test_validate_placeholders_with_events() {
         let html = r#"<button data-zx-on-click="0"></button>"#;
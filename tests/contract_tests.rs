@@ -6,6 +6,7 @@
 
 use sha2::{Digest, Sha256};
 use std::io::Write;
+use zenith_bundler::testing::MockCompilerOutput;
 use zenith_bundler::{bundle_page, BuildMode, BundleOptions, BundlePlan, CompilerOutput};
 
 // ---------------------------------------------------------------------------
@@ -35,61 +36,98 @@ const CONTRACT_INPUT: &str = r#"<div id="app"><h1>{title}</h1><button on:click={
 // 8.2 — Contract Snapshot Tests
 // ===========================================================================
 
-/// Contract symbol names are frozen. Renaming any of these fails the test.
-#[tokio::test]
-async fn contract_public_symbols_snapshot() {
-    let file = create_temp_zen(CONTRACT_INPUT);
-    let path = file.path().to_string_lossy().to_string();
-
-    let plan = BundlePlan {
-        page_path: path,
-        out_dir: None,
-        mode: BuildMode::Dev,
-    };
-    let result = bundle_page(plan, BundleOptions::default()).await.unwrap();
-
+/// Assert the frozen contract symbols are present in a bundled entry.
+///
+/// With `default_export == false` the `__zenith_page` default must be
+/// absent; every named export is still frozen.
+fn assert_frozen_symbols(entry_js: &str, default_export: bool) {
     // Frozen symbol names — any rename breaks this
     assert!(
-        result.entry_js.contains("__zenith_html"),
+        entry_js.contains("__zenith_html"),
         "FROZEN: __zenith_html symbol missing"
     );
     assert!(
-        result.entry_js.contains("__zenith_expr"),
+        entry_js.contains("__zenith_expr"),
         "FROZEN: __zenith_expr symbol missing"
     );
     assert!(
-        result.entry_js.contains("__zenith_page"),
-        "FROZEN: __zenith_page symbol missing"
-    );
-    assert!(
-        result.entry_js.contains("__zenith_contract"),
+        entry_js.contains("__zenith_contract"),
         "FROZEN: __zenith_contract symbol missing"
     );
 
     // Frozen binding types (Rolldown ESM: const decls + collected export)
     assert!(
-        result.entry_js.contains("const __zenith_html"),
+        entry_js.contains("const __zenith_html"),
         "FROZEN: __zenith_html must be const binding"
     );
     assert!(
-        result.entry_js.contains("const __zenith_expr"),
+        entry_js.contains("const __zenith_expr"),
         "FROZEN: __zenith_expr must be const binding"
     );
     assert!(
-        result.entry_js.contains("const __zenith_contract"),
+        entry_js.contains("const __zenith_contract"),
         "FROZEN: __zenith_contract must be const binding"
     );
-    assert!(
-        result.entry_js.contains("__zenith_page as default"),
-        "FROZEN: __zenith_page must be exported as default"
-    );
+
+    if default_export {
+        assert!(
+            entry_js.contains("__zenith_page"),
+            "FROZEN: __zenith_page symbol missing"
+        );
+        assert!(
+            entry_js.contains("__zenith_page as default"),
+            "FROZEN: __zenith_page must be exported as default"
+        );
+    } else {
+        assert!(
+            !entry_js.contains("export default") && !entry_js.contains("as default"),
+            "default_export: false must not emit a default export"
+        );
+    }
+
     // Rolldown collects exports at the end
     assert!(
-        result.entry_js.contains("export {"),
+        entry_js.contains("export {"),
         "FROZEN: Rolldown ESM must have collected export statement"
     );
 }
 
+/// Contract symbol names are frozen. Renaming any of these fails the test.
+#[tokio::test]
+async fn contract_public_symbols_snapshot() {
+    let file = create_temp_zen(CONTRACT_INPUT);
+    let path = file.path().to_string_lossy().to_string();
+
+    let plan = BundlePlan {
+        page_path: path,
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let result = bundle_page(plan, BundleOptions::default()).await.unwrap();
+
+    assert_frozen_symbols(&result.entry_js, true);
+}
+
+/// Library-style modules keep every named export but drop the default.
+#[tokio::test]
+async fn contract_symbols_without_default_export() {
+    let file = create_temp_zen(CONTRACT_INPUT);
+    let path = file.path().to_string_lossy().to_string();
+
+    let plan = BundlePlan {
+        page_path: path,
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = BundleOptions {
+        default_export: false,
+        ..Default::default()
+    };
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    assert_frozen_symbols(&result.entry_js, false);
+}
+
 /// Bundler never inspects AST — it receives CompilerOutput and passes through.
 /// Test: compile the same source twice, output must be byte-identical.
 /// If the bundler inspected AST, it could produce different interpretations.
@@ -273,18 +311,7 @@ async fn golden_e2e_with_strict_metadata() {
     let file = create_temp_zen(input);
     let path = file.path().to_string_lossy().to_string();
 
-    let metadata = CompilerOutput {
-        ir_version: 1,
-        html: String::new(),
-        expressions: vec!["title".into(), "body".into()],
-        hoisted: Default::default(),
-        components_scripts: Default::default(),
-        component_instances: Default::default(),
-        signals: Default::default(),
-        expression_bindings: Default::default(),
-        marker_bindings: Default::default(),
-        event_bindings: Default::default(),
-    };
+    let metadata = CompilerOutput::mock("", &["title", "body"]);
 
     let plan = BundlePlan {
         page_path: path,
@@ -1,6 +1,8 @@
 use sha2::{Digest, Sha256};
 use std::io::Write;
-use zenith_bundler::{bundle_page, BuildMode, BundleOptions, BundlePlan, BundleResult};
+use zenith_bundler::{
+    bundle_page, bundle_page_blocking, BuildMode, BundleOptions, BundlePlan, BundleResult,
+};
 
 // ---------------------------------------------------------------------------
 // Helpers
@@ -65,6 +67,35 @@ async fn deterministic_build_identical_bytes() {
     assert_eq!(hash1, hash2, "Builds must produce identical bytes");
 }
 
+#[tokio::test]
+async fn deterministic_dev_sourcemap() {
+    let file = create_temp_zen("<main><h1>{title}</h1></main>");
+    let path = file.path().to_string_lossy().to_string();
+    let build = || {
+        bundle_page(
+            BundlePlan {
+                page_path: path.clone(),
+                out_dir: None,
+                mode: BuildMode::Dev,
+            },
+            BundleOptions::default(),
+        )
+    };
+
+    let map1 = build().await.unwrap().source_map.expect("Dev emits a sourcemap");
+    let map2 = build().await.unwrap().source_map.expect("Dev emits a sourcemap");
+    assert_eq!(sha256(&map1), sha256(&map2), "Sourcemap JSON must be deterministic");
+
+    let map: serde_json::Value = serde_json::from_str(&map1).unwrap();
+    let sources = map["sources"].as_array().unwrap();
+    let file_name = file.path().file_name().unwrap().to_string_lossy().to_string();
+    assert!(
+        sources.iter().any(|s| s.as_str().is_some_and(|s| s.ends_with(&file_name))),
+        "Sourcemap must reference the original .zen file: {:?}",
+        sources
+    );
+}
+
 #[tokio::test]
 async fn deterministic_expressions_order() {
     let content = r#"<div title={a}><span class={b}>{c}</span><p>{d}</p></div>"#;
@@ -192,6 +223,26 @@ async fn template_literal_escape_snapshot() {
     );
 }
 
+#[tokio::test]
+async fn script_end_tag_escape_snapshot() {
+    // A code sample showing a script tag must not close an inline <script>
+    // the bundle is later embedded in
+    let (_, result) = bundle(
+        r#"<pre data-sample="<script src=/app.js></script>">{sample}</pre>"#,
+        "page.zen",
+    )
+    .await;
+
+    assert!(
+        result.entry_js.contains(r#"<\/script>"#),
+        "</script must be escaped in template literal"
+    );
+    assert!(
+        !result.entry_js.to_ascii_lowercase().contains("</script"),
+        "No raw </script may survive in the entry"
+    );
+}
+
 #[tokio::test]
 async fn expression_string_escape_snapshot() {
     // Quotes, backslashes, newlines in EXPRESSIONS (strings)
@@ -267,6 +318,49 @@ async fn newline_normalization_stable() {
     );
 }
 
+#[tokio::test]
+async fn bom_normalization_stable() {
+    // BOM-prefixed input vs plain input -> Identical Output SHA
+    // Same file name in separate dirs, as in `newline_normalization_stable`.
+
+    let dir_plain = tempfile::tempdir().unwrap();
+    let dir_bom = tempfile::tempdir().unwrap();
+
+    let path_plain = dir_plain.path().join("page.zen");
+    let path_bom = dir_bom.path().join("page.zen");
+
+    let plain_input = "<div>\n<p>{greeting}</p>\n</div>";
+    let bom_input = format!("\u{FEFF}{}", plain_input);
+
+    std::fs::write(&path_plain, plain_input).unwrap();
+    std::fs::write(&path_bom, bom_input).unwrap();
+
+    let plan_plain = BundlePlan {
+        page_path: path_plain.to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let res_plain = bundle_page(plan_plain, BundleOptions::default())
+        .await
+        .unwrap();
+
+    let plan_bom = BundlePlan {
+        page_path: path_bom.to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let res_bom = bundle_page(plan_bom, BundleOptions::default())
+        .await
+        .unwrap();
+
+    assert!(!res_bom.entry_js.contains('\u{FEFF}'));
+    assert_eq!(
+        sha256(&res_plain.entry_js),
+        sha256(&res_bom.entry_js),
+        "Output must be identical whether or not the source starts with a BOM"
+    );
+}
+
 #[tokio::test]
 async fn os_independent_hash_snapshot() {
     // Verify that bundling the same content from different directory structures
@@ -351,3 +445,69 @@ async fn hash_stays_stable_when_compiler_normalizes_expression_whitespace() {
     assert!(res_compact.entry_js.contains("\"a\""));
     assert!(res_loose.entry_js.contains("\"a\""));
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn blocking_pool_compilation_is_byte_stable() {
+    // `.zen` modules compile on the blocking pool; completion order must not
+    // leak into the output, whichever runtime drives the build.
+    let content = r#"<main><h1>{title}</h1><p>{body}</p><button on:click={go}>Go</button></main>"#;
+    let file = create_temp_zen(content);
+    let path = file.path().to_string_lossy().to_string();
+    let plan = || BundlePlan {
+        page_path: path.clone(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+
+    let mut hashes = Vec::new();
+    for _ in 0..5 {
+        let result = bundle_page(plan(), BundleOptions::default()).await.unwrap();
+        hashes.push(sha256(&result.entry_js));
+    }
+
+    // Baseline from the current-thread runtime `bundle_page_blocking` spins up
+    let blocking_plan = plan();
+    let blocking = tokio::task::spawn_blocking(move || {
+        bundle_page_blocking(blocking_plan, BundleOptions::default())
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    hashes.push(sha256(&blocking.entry_js));
+
+    assert!(
+        hashes.windows(2).all(|pair| pair[0] == pair[1]),
+        "Output bytes must not depend on compile scheduling: {:?}",
+        hashes
+    );
+}
+
+#[tokio::test]
+async fn normalized_expression_whitespace_is_opt_in_and_hash_stable() {
+    let dir = tempfile::tempdir().unwrap();
+    let path_tight = dir.path().join("tight.zen");
+    let path_spread = dir.path().join("spread.zen");
+    std::fs::write(&path_tight, "<div>{a + b}</div>").unwrap();
+    std::fs::write(&path_spread, "<div>{ a  +\n  b }</div>").unwrap();
+
+    let build = |path: &std::path::Path| {
+        let plan = BundlePlan {
+            page_path: path.to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        };
+        bundle_page(
+            plan,
+            BundleOptions {
+                normalize_expression_whitespace: true,
+                ..Default::default()
+            },
+        )
+    };
+    let tight = build(&path_tight).await.unwrap();
+    let spread = build(&path_spread).await.unwrap();
+
+    assert_eq!(sha256(&tight.entry_js), sha256(&spread.entry_js));
+    assert!(spread.entry_js.contains("\"a + b\""), "{}", spread.entry_js);
+    assert_eq!(spread.expressions, vec!["a + b"]);
+}
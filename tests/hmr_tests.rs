@@ -5,11 +5,16 @@
 
 use std::io::Write;
 use std::sync::Arc;
+use std::time::Duration;
+use zenith_bundler::dev::{DevServer, RebuildEvent};
 use zenith_bundler::plugin::css_cache::CssCache;
 use zenith_bundler::plugin::zenith_loader::{
     compile_zen_source, ZenithLoaderConfig, HMR_FOOTER, HMR_MARKER,
 };
-use zenith_bundler::{bundle_page, BuildMode, BundleOptions, BundlePlan};
+use zenith_bundler::utils;
+use zenith_bundler::{
+    bundle_page, BuildMode, BundleOptions, BundlePlan, CancellationToken, MinifyMode,
+};
 
 // ---------------------------------------------------------------------------
 // Helpers
@@ -29,8 +34,21 @@ fn dev_config() -> ZenithLoaderConfig {
     ZenithLoaderConfig {
         components: None,
         metadata: None,
+        module_metadata: None,
         strict: false,
         is_dev: true,
+        default_export: true,
+        sourcemap: false,
+        sourcemap_sources_content: false,
+        content_resolver: None,
+        compile_cache: None,
+        contract_version: utils::DEFAULT_CONTRACT_VERSION.to_string(),
+        runtime_import: None,
+        external: Vec::new(),
+        normalize_expression_whitespace: false,
+        project_root: None,
+        externalize_bindings: false,
+        compile_permits: None,
     }
 }
 
@@ -38,8 +56,21 @@ fn prod_config() -> ZenithLoaderConfig {
     ZenithLoaderConfig {
         components: None,
         metadata: None,
+        module_metadata: None,
         strict: false,
         is_dev: false,
+        default_export: true,
+        sourcemap: false,
+        sourcemap_sources_content: false,
+        content_resolver: None,
+        compile_cache: None,
+        contract_version: utils::DEFAULT_CONTRACT_VERSION.to_string(),
+        runtime_import: None,
+        external: Vec::new(),
+        normalize_expression_whitespace: false,
+        project_root: None,
+        externalize_bindings: false,
+        compile_permits: None,
     }
 }
 
@@ -285,6 +316,57 @@ fn css_dirty_tracking_thread_safe() {
     }
 }
 
+/// Draining returns every dirty page with its current CSS and clears them.
+#[test]
+fn css_drain_changed_returns_dirty_pages() {
+    let cache = CssCache::new();
+    cache.insert("page_b", ".b { color: blue }".into());
+    cache.insert("page_a", ".a { color: red }".into());
+    cache.insert("page_c", ".c { }".into());
+    cache.invalidate("page_c");
+
+    assert_eq!(
+        cache.drain_changed(),
+        vec![
+            ("page_a".to_string(), Some(".a { color: red }".to_string())),
+            ("page_b".to_string(), Some(".b { color: blue }".to_string())),
+            ("page_c".to_string(), None),
+        ]
+    );
+    assert!(
+        cache.drain_changed().is_empty(),
+        "Dirty set should be empty after a drain"
+    );
+    assert!(!cache.has_changed("page_a"));
+    assert_eq!(cache.get("page_a").unwrap(), ".a { color: red }");
+}
+
+/// Concurrent inserts are never lost between drains.
+#[test]
+fn css_drain_changed_loses_no_updates() {
+    use std::collections::HashSet;
+    use std::thread;
+
+    let cache = Arc::new(CssCache::new());
+    let writer = {
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || {
+            for i in 0..200 {
+                cache.insert(&format!("page_{}", i), format!(".p{} {{ }}", i));
+            }
+        })
+    };
+
+    let mut seen = HashSet::new();
+    while !writer.is_finished() {
+        seen.extend(cache.drain_changed().into_iter().map(|(id, _)| id));
+    }
+    writer.join().unwrap();
+    seen.extend(cache.drain_changed().into_iter().map(|(id, _)| id));
+
+    assert_eq!(seen.len(), 200, "Every insert should surface in a drain");
+}
+
 /// Bundle in dev mode vs prod mode: expressions identical.
 #[tokio::test]
 async fn dev_and_prod_expressions_identical() {
@@ -377,7 +459,7 @@ async fn hmr_file_sha_identical_except_footer() {
             mode: BuildMode::Prod,
         },
         BundleOptions {
-            minify: Some(false),
+            minify: Some(MinifyMode::Off),
             ..Default::default()
         },
     )
@@ -406,3 +488,98 @@ async fn hmr_file_sha_identical_except_footer() {
         "Dev output (stripped) must match Prod output content (ignoring whitespace)"
     );
 }
+
+#[tokio::test]
+async fn recompiling_styled_page_marks_css_dirty_only_on_change() {
+    let file = create_temp_zen("<style>.a { color: red; }</style><p class=\"a\">{x}</p>");
+    let page_path = file.path().to_string_lossy().to_string();
+    let page_id = utils::canonicalize_page_id(&page_path, None);
+    let css_cache = Arc::new(CssCache::new());
+    let build = || {
+        bundle_page(
+            BundlePlan {
+                page_path: page_path.clone(),
+                out_dir: None,
+                mode: BuildMode::Dev,
+            },
+            BundleOptions {
+                css_cache: Some(Arc::clone(&css_cache)),
+                ..Default::default()
+            },
+        )
+    };
+
+    build().await.unwrap();
+    let changed = css_cache.drain_changed();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].0, page_id);
+
+    // An unchanged recompile triggers no reload
+    build().await.unwrap();
+    assert!(css_cache.drain_changed().is_empty());
+
+    std::fs::write(
+        file.path(),
+        "<style>.a { color: blue; }</style><p class=\"a\">{x}</p>",
+    )
+    .unwrap();
+    build().await.unwrap();
+    let changed = css_cache.drain_changed();
+    assert_eq!(changed.len(), 1);
+    assert!(changed[0].1.as_deref().unwrap().contains("blue"));
+}
+
+// ---------------------------------------------------------------------------
+// Native dev server
+// ---------------------------------------------------------------------------
+
+async fn next_build(
+    events: &mut tokio::sync::broadcast::Receiver<RebuildEvent>,
+) -> (
+    Vec<std::path::PathBuf>,
+    Arc<Vec<zenith_bundler::BundleResult>>,
+) {
+    match tokio::time::timeout(Duration::from_secs(10), events.recv()).await {
+        Ok(Ok(RebuildEvent::Built { changed, results })) => (changed, results),
+        other => panic!("Expected a successful rebuild, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn dev_server_rebuilds_on_change_and_drops_stale_css() {
+    let root = tempfile::tempdir().unwrap();
+    let page = root.path().join("page.zen");
+    std::fs::write(
+        &page,
+        "<style>.a { color: red; }</style><p class=\"a\">{x}</p>",
+    )
+    .unwrap();
+    let plan = BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let server = Arc::new(
+        DevServer::new(root.path(), vec![plan], BundleOptions::default())
+            .with_debounce(Duration::from_millis(20)),
+    );
+    let mut events = server.subscribe();
+    let cancel = CancellationToken::new();
+    let running = tokio::spawn({
+        let (server, cancel) = (Arc::clone(&server), cancel.clone());
+        async move { server.run(cancel).await }
+    });
+
+    let (changed, results) = next_build(&mut events).await;
+    assert!(changed.is_empty());
+    assert!(results[0].css.as_deref().unwrap().contains(".a"));
+
+    std::fs::write(&page, "<p>{x}</p>").unwrap();
+    let (changed, results) = next_build(&mut events).await;
+    assert!(changed.iter().any(|path| path.ends_with("page.zen")));
+    assert_eq!(results[0].css, None);
+    assert!(server.css_cache().is_empty());
+
+    cancel.cancel();
+    running.await.unwrap().unwrap();
+}
@@ -1,6 +1,14 @@
+use std::collections::HashMap;
 use std::io::Write;
+use zenith_bundler::bundle::MANIFEST_FILE;
+use zenith_bundler::html;
+use zenith_bundler::router;
+use zenith_bundler::testing::{bundle_str, MockCompilerOutput};
+use zenith_bundler::utils::{content_hash, AssetKind};
 use zenith_bundler::{
-    bundle_page, BuildMode, BundleError, BundleOptions, BundlePlan, CompilerOutput,
+    bundle_page, bundle_page_blocking, bundle_page_cancellable, bundle_pages, codes, BuildManifest,
+    BuildMode, BundleError, BundleOptions, BundlePlan, BundleStats, CancellationToken,
+    CompilerOutput, ComponentDef, ContentResolver, DiagnosticLevel, MinifyMode,
 };
 
 /// Create a temp .zen file with the given content.
@@ -41,15 +49,9 @@ async fn bundle_simple_page() {
 
 #[tokio::test]
 async fn bundle_static_page_no_expressions() {
-    let file = create_temp_zen("<div>Hello World</div>");
-    let plan = BundlePlan {
-        page_path: file.path().to_string_lossy().to_string(),
-        out_dir: None,
-        mode: BuildMode::Dev,
-    };
-    let opts = BundleOptions::default();
-
-    let result = bundle_page(plan, opts).await.unwrap();
+    let result = bundle_str("<div>Hello World</div>", BuildMode::Dev)
+        .await
+        .unwrap();
 
     assert!(result.expressions.is_empty());
     assert!(result.entry_js.contains("__zenith_html"));
@@ -57,17 +59,12 @@ async fn bundle_static_page_no_expressions() {
 
 #[tokio::test]
 async fn bundle_multiple_expressions() {
-    let file = create_temp_zen(
+    let result = bundle_str(
         r#"<div id="app"><h1>{title}</h1><button on:click={increment}>Count: {count}</button></div>"#,
-    );
-    let plan = BundlePlan {
-        page_path: file.path().to_string_lossy().to_string(),
-        out_dir: None,
-        mode: BuildMode::Dev,
-    };
-    let opts = BundleOptions::default();
-
-    let result = bundle_page(plan, opts).await.unwrap();
+        BuildMode::Dev,
+    )
+    .await
+    .unwrap();
 
     assert_eq!(result.expressions, vec!["title", "increment", "count"]);
 }
@@ -86,18 +83,7 @@ async fn strict_mode_matching_metadata_passes() {
     };
 
     // Provide matching metadata
-    let metadata = CompilerOutput {
-        ir_version: 1,
-        html: String::new(), // HTML not used for expression comparison
-        expressions: vec!["title".into()],
-        hoisted: Default::default(),
-        components_scripts: Default::default(),
-        component_instances: Default::default(),
-        signals: Default::default(),
-        expression_bindings: Default::default(),
-        marker_bindings: Default::default(),
-        event_bindings: Default::default(),
-    };
+    let metadata = CompilerOutput::mock("", &["title"]);
 
     let opts = BundleOptions {
         metadata: Some(metadata),
@@ -119,18 +105,7 @@ async fn strict_mode_mismatched_count_fails() {
     };
 
     // Provide wrong metadata (expects 2 expressions)
-    let metadata = CompilerOutput {
-        ir_version: 1,
-        html: String::new(),
-        expressions: vec!["title".into(), "extra".into()],
-        hoisted: Default::default(),
-        components_scripts: Default::default(),
-        component_instances: Default::default(),
-        signals: Default::default(),
-        expression_bindings: Default::default(),
-        marker_bindings: Default::default(),
-        event_bindings: Default::default(),
-    };
+    let metadata = CompilerOutput::mock("", &["title", "extra"]);
 
     let opts = BundleOptions {
         metadata: Some(metadata),
@@ -140,7 +115,9 @@ async fn strict_mode_mismatched_count_fails() {
 
     let result = bundle_page(plan, opts).await;
     assert!(result.is_err());
-    match result.unwrap_err() {
+    let err = result.unwrap_err();
+    assert_eq!(err.code(), "E_EXPR_COUNT");
+    match err {
         BundleError::ExpressionMismatch { expected, got } => {
             assert_eq!(expected, 2);
             assert_eq!(got, 1);
@@ -159,18 +136,7 @@ async fn strict_mode_mismatched_content_fails() {
     };
 
     // Right count, wrong content
-    let metadata = CompilerOutput {
-        ir_version: 1,
-        html: String::new(),
-        expressions: vec!["wrong_name".into()],
-        hoisted: Default::default(),
-        components_scripts: Default::default(),
-        component_instances: Default::default(),
-        signals: Default::default(),
-        expression_bindings: Default::default(),
-        marker_bindings: Default::default(),
-        event_bindings: Default::default(),
-    };
+    let metadata = CompilerOutput::mock("", &["wrong_name"]);
 
     let opts = BundleOptions {
         metadata: Some(metadata),
@@ -180,7 +146,9 @@ async fn strict_mode_mismatched_content_fails() {
 
     let result = bundle_page(plan, opts).await;
     assert!(result.is_err());
-    match result.unwrap_err() {
+    let err = result.unwrap_err();
+    assert_eq!(err.code(), "E_EXPR_CONTENT");
+    match err {
         BundleError::ExpressionContentMismatch { index, .. } => {
             assert_eq!(index, 0);
         }
@@ -188,6 +156,46 @@ async fn strict_mode_mismatched_content_fails() {
     }
 }
 
+#[tokio::test]
+async fn strict_mode_rejects_diverging_imported_component() {
+    let dir = tempfile::tempdir().unwrap();
+    let card = dir.path().join("card.zen");
+    std::fs::write(&card, "<span>{label}</span>").unwrap();
+    let page = dir.path().join("page.zen");
+    std::fs::write(
+        &page,
+        format!(
+            "<script>import \"{}\";</script><h1>{{title}}</h1>",
+            card.to_string_lossy().replace('\\', "/")
+        ),
+    )
+    .unwrap();
+    let plan = || BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = |label: &str| BundleOptions {
+        module_metadata: Some(HashMap::from([(
+            card.to_string_lossy().to_string(),
+            CompilerOutput::mock("", &[label]),
+        )])),
+        strict: true,
+        ..Default::default()
+    };
+
+    assert!(bundle_page(plan(), opts("label")).await.is_ok());
+
+    // The page has no metadata; the component it imports diverges from its own
+    match bundle_page(plan(), opts("wrong_name")).await.unwrap_err() {
+        BundleError::ExpressionContentMismatch { expected, got, .. } => {
+            assert_eq!(expected, "wrong_name");
+            assert_eq!(got, "label");
+        }
+        e => panic!("Expected ExpressionContentMismatch, got: {:?}", e),
+    }
+}
+
 // ============================================================================
 // M1: File not found error
 // ============================================================================
@@ -282,3 +290,1403 @@ async fn no_post_concat_in_bundle() {
     assert!(!result.entry_js.contains("<script"));
     assert!(!result.entry_js.contains("document.write"));
 }
+
+#[tokio::test]
+async fn user_region_comments_survive_rolldown_region_stripping() {
+    let file = create_temp_zen(
+        "<script>\n//#region counters\nglobalThis.count = 1;\n//#endregion\n</script><p>{x}</p>",
+    );
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+
+    let result = bundle_page(plan, BundleOptions::default()).await.unwrap();
+
+    assert!(
+        result.entry_js.contains("//#region counters"),
+        "{}",
+        result.entry_js
+    );
+    assert_eq!(result.entry_js.matches("//#region").count(), 1);
+    assert_eq!(result.entry_js.matches("//#endregion").count(), 1);
+}
+
+#[tokio::test]
+async fn preserved_region_comments_name_modules_without_absolute_paths() {
+    let file = create_temp_zen("<p>{x}</p>");
+    let page_path = file.path().to_string_lossy().to_string();
+    let file_name = file
+        .path()
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    let plan = BundlePlan {
+        page_path: page_path.clone(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = BundleOptions {
+        preserve_region_comments: true,
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    assert!(
+        result
+            .entry_js
+            .contains(&format!("//#region {}", file_name)),
+        "{}",
+        result.entry_js
+    );
+    assert!(!result.entry_js.contains(&page_path), "{}", result.entry_js);
+    assert_eq!(
+        result.entry_js.matches("//#region").count(),
+        result.entry_js.matches("//#endregion").count()
+    );
+}
+
+// ============================================================================
+// M1: Blocking entry point
+// ============================================================================
+
+#[test]
+fn blocking_bundle_matches_async_bundle() {
+    let file = create_temp_zen(r#"<div id="app"><h1>{title}</h1><p>{count}</p></div>"#);
+    let page_path = file.path().to_string_lossy().to_string();
+
+    let blocking = bundle_page_blocking(
+        BundlePlan {
+            page_path: page_path.clone(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        },
+        BundleOptions::default(),
+    )
+    .unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let async_result = runtime
+        .block_on(bundle_page(
+            BundlePlan {
+                page_path,
+                out_dir: None,
+                mode: BuildMode::Prod,
+            },
+            BundleOptions::default(),
+        ))
+        .unwrap();
+
+    assert_eq!(blocking.entry_js, async_result.entry_js);
+    assert_eq!(blocking.expressions, async_result.expressions);
+}
+
+// ============================================================================
+// M1: Multi-page concurrency
+// ============================================================================
+
+#[tokio::test]
+async fn bundle_pages_output_independent_of_concurrency() {
+    let files: Vec<_> = (0..6)
+        .map(|i| {
+            create_temp_zen(&format!(
+                "<section><h2>{{title}}</h2><p>page {}</p></section>",
+                i
+            ))
+        })
+        .collect();
+    let plans = || {
+        files
+            .iter()
+            .map(|f| BundlePlan {
+                page_path: f.path().to_string_lossy().to_string(),
+                out_dir: None,
+                mode: BuildMode::Prod,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let serial = bundle_pages(
+        plans(),
+        BundleOptions {
+            concurrency: Some(1),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let parallel = bundle_pages(
+        plans(),
+        BundleOptions {
+            concurrency: Some(8),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(serial.len(), 6);
+    assert_eq!(
+        serde_json::to_string(&serial).unwrap(),
+        serde_json::to_string(&parallel).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn bundle_pages_shared_graph_is_deterministic_and_ordered() {
+    let files: Vec<_> = ["<h1>{a}</h1>", "<p>static</p>", "<span>{b}</span>"]
+        .iter()
+        .map(|src| create_temp_zen(src))
+        .collect();
+    let plans = || {
+        files
+            .iter()
+            .map(|f| BundlePlan {
+                page_path: f.path().to_string_lossy().to_string(),
+                out_dir: None,
+                mode: BuildMode::Prod,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let first = bundle_pages(plans(), BundleOptions::default())
+        .await
+        .unwrap();
+    let second = bundle_pages(plans(), BundleOptions::default())
+        .await
+        .unwrap();
+
+    for ((plan, a), b) in plans().iter().zip(&first).zip(&second) {
+        assert_eq!(a.page_path, plan.page_path);
+        assert_eq!(a.entry_js, b.entry_js, "entry chunk drifted between builds");
+    }
+    assert_eq!(first[0].expressions, vec!["a".to_string()]);
+    assert!(first[1].expressions.is_empty());
+    assert_eq!(first[2].expressions, vec!["b".to_string()]);
+}
+
+#[tokio::test]
+async fn bundle_pages_rejects_mixed_build_modes() {
+    let dev = create_temp_zen("<h1>dev</h1>");
+    let prod = create_temp_zen("<h1>prod</h1>");
+    let plans = vec![
+        BundlePlan {
+            page_path: dev.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Dev,
+        },
+        BundlePlan {
+            page_path: prod.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        },
+    ];
+
+    let err = bundle_pages(plans, BundleOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+}
+
+#[tokio::test]
+async fn bundle_pages_rejects_repeated_pages() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = || BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+
+    let err = bundle_pages(vec![plan(), plan()], BundleOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+    assert!(err.to_string().contains("more than once"), "{}", err);
+}
+
+#[tokio::test]
+async fn bundle_pages_rejects_metadata_for_several_pages() {
+    let home = create_temp_zen("<h1>{title}</h1>");
+    let about = create_temp_zen("<h1>{heading}</h1>");
+    let plans = [&home, &about]
+        .iter()
+        .map(|file| BundlePlan {
+            page_path: file.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        })
+        .collect();
+    let opts = BundleOptions {
+        metadata: Some(CompilerOutput::mock("", &["title"])),
+        ..Default::default()
+    };
+
+    let err = bundle_pages(plans, opts).await.unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+}
+
+#[tokio::test]
+async fn unknown_contract_version_fails_build() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+    let opts = BundleOptions {
+        contract_version: "v2".into(),
+        ..Default::default()
+    };
+
+    let err = bundle_page(plan, opts).await.unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+    assert!(err.to_string().contains("'v2'"), "{}", err);
+}
+
+#[tokio::test]
+async fn minify_mode_is_reported_per_page() {
+    let file = create_temp_zen("<h1>{title}</h1>\n<p>{count}</p>");
+    let build = |mode: BuildMode, minify: Option<MinifyMode>| {
+        let plan = BundlePlan {
+            page_path: file.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode,
+        };
+        bundle_page(
+            plan,
+            BundleOptions {
+                minify,
+                ..Default::default()
+            },
+        )
+    };
+    let reported = |result: &zenith_bundler::BundleResult| {
+        result
+            .diagnostics
+            .iter()
+            .find(|d| d.code.as_deref() == Some(codes::MINIFY_MODE))
+            .map(|d| d.message.clone())
+            .expect("minify mode diagnostic")
+    };
+
+    let dev = build(BuildMode::Dev, None).await.unwrap();
+    assert_eq!(reported(&dev), "JS minification: Off");
+    let prod = build(BuildMode::Prod, None).await.unwrap();
+    assert_eq!(reported(&prod), "JS minification: Full");
+
+    let off = build(BuildMode::Prod, Some(MinifyMode::Off)).await.unwrap();
+    let whitespace = build(BuildMode::Prod, Some(MinifyMode::Whitespace))
+        .await
+        .unwrap();
+    assert_eq!(reported(&whitespace), "JS minification: Whitespace");
+    assert!(whitespace.entry_js.len() < off.entry_js.len());
+    // Whitespace-only keeps identifiers intact
+    assert!(whitespace.entry_js.contains("__zenith_html"));
+}
+
+#[tokio::test]
+async fn duplicate_manual_chunk_fails_build() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+    let opts = BundleOptions {
+        manual_chunks: vec![
+            ("runtime-anim".into(), vec!["gsap".into()]),
+            ("runtime-anim".into(), vec!["@motion/*".into()]),
+        ],
+        ..Default::default()
+    };
+
+    let err = bundle_page(plan, opts).await.unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+    assert!(err.to_string().contains("'runtime-anim'"), "{}", err);
+}
+
+#[tokio::test]
+async fn size_budgets_fail_with_shipped_byte_counts() {
+    let file = create_temp_zen("<style>.a { color: red; }</style><h1 class=\"a\">{title}</h1>");
+    let build = |max_entry_bytes, max_css_bytes| {
+        let plan = BundlePlan {
+            page_path: file.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        };
+        bundle_page(
+            plan,
+            BundleOptions {
+                max_entry_bytes,
+                max_css_bytes,
+                ..Default::default()
+            },
+        )
+    };
+
+    let ok = build(Some(1 << 20), Some(1 << 20)).await.unwrap();
+    let css_bytes = ok.css.as_ref().unwrap().len();
+
+    match build(Some(1), None).await.unwrap_err() {
+        BundleError::BudgetExceeded {
+            kind,
+            limit,
+            actual,
+        } => {
+            assert_eq!(kind, AssetKind::Entry);
+            assert_eq!(limit, 1);
+            assert_eq!(actual, ok.entry_js.len());
+        }
+        err => panic!("expected BudgetExceeded, got {}", err),
+    }
+    match build(None, Some(css_bytes - 1)).await.unwrap_err() {
+        BundleError::BudgetExceeded { kind, actual, .. } => {
+            assert_eq!(kind, AssetKind::Css);
+            assert_eq!(actual, css_bytes);
+        }
+        err => panic!("expected BudgetExceeded, got {}", err),
+    }
+    // A budget is inclusive
+    build(Some(ok.entry_js.len()), Some(css_bytes))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn no_manual_chunks_without_configuration() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+
+    let result = bundle_page(plan, BundleOptions::default()).await.unwrap();
+    assert!(result.manual_chunks.is_empty());
+}
+
+#[tokio::test]
+async fn ssg_document_preloads_static_chunks_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    for (package, body) in [
+        ("core-lib", "export const boot = () => 1;\n"),
+        ("anim-lib", "export const play = () => 2;\n"),
+    ] {
+        let package_dir = dir.path().join("node_modules").join(package);
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(
+            package_dir.join("package.json"),
+            format!(
+                r#"{{"name":"{}","type":"module","main":"index.js"}}"#,
+                package
+            ),
+        )
+        .unwrap();
+        std::fs::write(package_dir.join("index.js"), body).unwrap();
+    }
+    let page = dir.path().join("page.zen");
+    std::fs::write(
+        &page,
+        "<script>import { boot } from \"core-lib\"; boot(); \
+         globalThis.loadAnim = () => import(\"anim-lib\");</script><p>{title}</p>",
+    )
+    .unwrap();
+    let plan = BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+    let opts = BundleOptions {
+        runtime_import: Some("/assets/runtime.js".into()),
+        manual_chunks: vec![
+            ("runtime-core".into(), vec!["core-*".into()]),
+            ("runtime-anim".into(), vec!["anim-lib".into()]),
+        ],
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+    let chunk = |name: &str| {
+        result
+            .manual_chunks
+            .iter()
+            .find(|(chunk, _)| chunk == name)
+            .map(|(_, file)| file.clone())
+            .unwrap_or_else(|| panic!("no {} chunk in {:?}", name, result.manual_chunks))
+    };
+    let (core, anim) = (chunk("runtime-core"), chunk("runtime-anim"));
+    assert!(result.preload_chunks.contains(&core));
+    assert!(!result.preload_chunks.contains(&anim));
+
+    let document = result.html.as_deref().unwrap();
+    assert!(document.contains(&format!(
+        r#"<link rel="modulepreload" href="/assets/{}">"#,
+        core
+    )));
+    assert!(!document.contains(&anim), "{}", document);
+}
+
+#[tokio::test]
+async fn ssg_writes_shared_chunks_beside_the_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("shared.js"),
+        "export const greet = (name) => `hi ${name}`;\n",
+    )
+    .unwrap();
+    let plans = ["home", "about"]
+        .iter()
+        .map(|name| {
+            let page = dir.path().join(format!("{}.zen", name));
+            std::fs::write(
+                &page,
+                format!(
+                    "<script>import {{ greet }} from \"./shared.js\"; \
+                     globalThis.msg = greet(\"{}\");</script><p>{{title}}</p>",
+                    name
+                ),
+            )
+            .unwrap();
+            BundlePlan {
+                page_path: page.to_string_lossy().to_string(),
+                out_dir: Some(out.path().to_path_buf()),
+                mode: BuildMode::SSG,
+            }
+        })
+        .collect();
+    let opts = BundleOptions {
+        emit_manifest: true,
+        ..Default::default()
+    };
+
+    let results = bundle_pages(plans, opts).await.unwrap();
+    let manifest: BuildManifest =
+        serde_json::from_str(&std::fs::read_to_string(out.path().join(MANIFEST_FILE)).unwrap())
+            .unwrap();
+    assert!(!manifest.chunks.is_empty());
+
+    let relative_import = regex::Regex::new(r#"(?:from|import)\s*["']\./([^"']+)["']"#).unwrap();
+    for result in &results {
+        assert!(
+            !result.preload_chunks.is_empty(),
+            "{:?}",
+            result.preload_chunks
+        );
+        let (_, entry) = result
+            .assets
+            .iter()
+            .find(|(name, _)| name.ends_with(".js"))
+            .unwrap();
+        let entry_js = std::fs::read_to_string(out.path().join(entry)).unwrap();
+
+        // Every chunk the entry imports, and every chunk its document
+        // preloads, is on disk where the browser will look for it
+        let imports: Vec<&str> = relative_import
+            .captures_iter(&entry_js)
+            .map(|caps| caps.get(1).unwrap().as_str())
+            .collect();
+        assert!(!imports.is_empty(), "{}", entry_js);
+        for import in imports {
+            let chunk = format!("assets/{}", import);
+            assert!(out.path().join(&chunk).is_file(), "missing {}", chunk);
+            assert!(manifest.chunks.contains(&chunk), "{:?}", manifest.chunks);
+        }
+        for chunk in &result.preload_chunks {
+            assert!(out.path().join("assets").join(chunk).is_file());
+        }
+    }
+}
+
+#[tokio::test]
+async fn externalized_bindings_are_written_beside_the_entry() {
+    let file = create_temp_zen("<h1>{title}</h1><p>{count}</p>");
+    let out = tempfile::tempdir().unwrap();
+    let plan = || BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+    let opts = BundleOptions {
+        runtime_import: Some("/assets/runtime.js".into()),
+        externalize_bindings: true,
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan(), opts.clone()).await.unwrap();
+    let json = result.bindings.as_deref().unwrap();
+    let tables: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(tables["expressions"].as_array().unwrap().len(), 2);
+
+    // Referenced by content hash, relative to the entry it sits beside
+    let page_id = zenith_bundler::utils::canonicalize_page_id(&result.page_path, None);
+    let sidecar = zenith_bundler::utils::bindings_filename(&page_id, json);
+    assert!(result.entry_js.contains(&format!("\"./{}\"", sidecar)));
+    assert!(!result.entry_js.contains("__zenith_expression_bindings"));
+    let written = out.path().join("assets").join(&sidecar);
+    assert_eq!(std::fs::read_to_string(written).unwrap(), json);
+    assert!(result.assets.contains(&(
+        format!("{}.bindings.json", page_id),
+        format!("assets/{}", sidecar)
+    )));
+
+    // Deterministic across builds
+    let again = bundle_page(plan(), opts.clone()).await.unwrap();
+    assert_eq!(again.bindings.as_deref(), Some(json));
+    assert_eq!(again.entry_js, result.entry_js);
+
+    for opts in [
+        BundleOptions {
+            runtime_import: None,
+            ..opts.clone()
+        },
+        BundleOptions {
+            inline_assets: true,
+            ..opts.clone()
+        },
+    ] {
+        match bundle_page(plan(), opts).await {
+            Err(BundleError::ValidationError(msg)) => {
+                assert!(msg.contains("externalize_bindings"), "{}", msg)
+            }
+            other => panic!("Expected ValidationError, got {:?}", other),
+        }
+    }
+
+    // An in-memory build writes no sidecar for the entry to fetch
+    let in_memory = BundlePlan {
+        mode: BuildMode::Prod,
+        ..plan()
+    };
+    match bundle_page(in_memory, opts).await {
+        Err(BundleError::ValidationError(msg)) => assert!(msg.contains("write_to_disk"), "{}", msg),
+        other => panic!("Expected ValidationError, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn ssg_document_maps_externals_before_entry_script() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let page = dir.path().join("page.zen");
+    std::fs::write(
+        &page,
+        "<script>import { gsap } from \"gsap\"; globalThis.gsap = gsap;</script><p>{title}</p>",
+    )
+    .unwrap();
+    let plan = BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+    let opts = BundleOptions {
+        external: vec!["gsap".into()],
+        import_map: HashMap::from([(
+            "gsap".to_string(),
+            "https://cdn.example.com/gsap.js".to_string(),
+        )]),
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+    let document = result.html.as_deref().unwrap();
+    let map = document
+        .find(r#"<script type="importmap">{"imports":{"gsap":"https://cdn.example.com/gsap.js"}}</script>"#)
+        .unwrap_or_else(|| panic!("no import map in {}", document));
+    let entry = document.find(r#"<script type="module""#).unwrap();
+    assert!(map < entry, "{}", document);
+    assert_eq!(document.matches("importmap").count(), 1);
+}
+
+#[tokio::test]
+async fn import_map_for_bundled_specifier_fails_build() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+    let opts = BundleOptions {
+        import_map: HashMap::from([("gsap".to_string(), "/vendor/gsap.js".to_string())]),
+        ..Default::default()
+    };
+
+    let err = bundle_page(plan, opts).await.unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+    assert!(err.to_string().contains("'gsap'"), "{}", err);
+}
+
+// ============================================================================
+// M1: Custom content resolver
+// ============================================================================
+
+#[tokio::test]
+async fn content_resolver_supplies_zen_source() {
+    let resolver = ContentResolver::new(|id| {
+        (id == "virtual/pages/about.zen")
+            .then(|| "<main>\r\n<h1>{headline}</h1>\r\n</main>".to_string())
+    });
+    let plan = BundlePlan {
+        page_path: "virtual/pages/about.zen".into(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+    let opts = BundleOptions {
+        content_resolver: Some(resolver),
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    assert_eq!(result.expressions, vec!["headline"]);
+    assert!(result.entry_js.contains("__zenith_html"));
+    assert!(!result.entry_js.contains('\r'));
+}
+
+#[tokio::test]
+async fn preloaded_component_sources_need_no_files() {
+    let def = |path: &str, source: &str| ComponentDef {
+        path: path.into(),
+        source: Some(source.to_string()),
+    };
+    let components = HashMap::from([
+        (
+            "Home".to_string(),
+            def(
+                "virtual/pages/home.zen",
+                "<main><Card /><h1>{greeting}</h1></main>",
+            ),
+        ),
+        (
+            "Card".to_string(),
+            def(
+                "virtual/components/card.zen",
+                "<style>.card { padding: 1rem; }</style><div class=\"card\"></div>",
+            ),
+        ),
+    ]);
+    let plan = BundlePlan {
+        page_path: "virtual/pages/home.zen".into(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = BundleOptions {
+        components: Some(components),
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    assert_eq!(result.expressions, vec!["greeting"]);
+    assert!(result.css.as_deref().unwrap().contains(".card"));
+}
+
+// ============================================================================
+// M1: Sourcemap sourcesContent
+// ============================================================================
+
+#[tokio::test]
+async fn sourcemap_embeds_normalized_zen_source() {
+    let source = "<main>\r\n  <h1>{title}</h1>\r\n</main>\r\n";
+    let file = create_temp_zen(source);
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = BundleOptions {
+        strict: false,
+        sourcemap_sources_content: true,
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    let map: serde_json::Value =
+        serde_json::from_str(result.source_map.as_deref().expect("Dev emits a sourcemap")).unwrap();
+    let contents = map["sourcesContent"].as_array().unwrap();
+    assert!(
+        contents
+            .iter()
+            .any(|c| c.as_str() == Some(&source.replace("\r\n", "\n"))),
+        "sourcesContent must hold the compiled .zen source: {:?}",
+        contents
+    );
+}
+
+#[tokio::test]
+async fn sourcemap_omits_sources_content_by_default() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = BundleOptions {
+        strict: false,
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    let map: serde_json::Value =
+        serde_json::from_str(result.source_map.as_deref().unwrap()).unwrap();
+    let contents = map["sourcesContent"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    assert!(!contents
+        .iter()
+        .any(|c| c.as_str() == Some("<h1>{title}</h1>")));
+}
+
+// ============================================================================
+// M1: Diagnostic codes
+// ============================================================================
+
+#[tokio::test]
+async fn fail_on_codes_promotes_matching_diagnostic() {
+    let file = create_temp_zen("<div>Static content</div>");
+    let plan = || BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+
+    let ok = bundle_page(plan(), BundleOptions::default()).await.unwrap();
+    assert!(ok
+        .diagnostics
+        .iter()
+        .any(|d| d.code.as_deref() == Some(codes::STATIC_PAGE)));
+
+    let opts = BundleOptions {
+        fail_on_codes: vec![codes::STATIC_PAGE.to_string()],
+        ..Default::default()
+    };
+    match bundle_page(plan(), opts).await {
+        Err(BundleError::FailOnCode { code, .. }) => assert_eq!(code, codes::STATIC_PAGE),
+        other => panic!("Expected FailOnCode, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn rolldown_warnings_surface_as_diagnostics() {
+    let dir = tempfile::tempdir().unwrap();
+    let page = dir.path().join("page.zen");
+    std::fs::write(
+        &page,
+        "<script>import { greet } from \"./missing.js\";</script><p>{greet()}</p>",
+    )
+    .unwrap();
+    let plan = || BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+    let opts = |strict| BundleOptions {
+        strict,
+        runtime_import: Some("/assets/runtime.js".into()),
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan(), opts(false)).await.unwrap();
+    let warning = result
+        .diagnostics
+        .iter()
+        .find(|d| d.code.as_deref() == Some(codes::UNRESOLVED_IMPORT))
+        .expect("unresolved import should be reported");
+    assert_eq!(warning.level, DiagnosticLevel::Warning);
+    assert!(warning.message.contains("missing.js"));
+
+    // Strict mode escalates the configured subset
+    match bundle_page(plan(), opts(true)).await {
+        Err(BundleError::RolldownWarning { code, .. }) => {
+            assert_eq!(code, codes::UNRESOLVED_IMPORT)
+        }
+        other => panic!("Expected RolldownWarning, got {:?}", other),
+    }
+
+    // ... and only that subset
+    let relaxed = BundleOptions {
+        strict_warnings: Vec::new(),
+        ..opts(true)
+    };
+    assert!(bundle_page(plan(), relaxed).await.is_ok());
+}
+
+// ============================================================================
+// M1: Page CSS
+// ============================================================================
+
+#[tokio::test]
+async fn style_blocks_populate_result_css_per_page() {
+    let styled =
+        create_temp_zen("<style>.hero { color: red; }</style><h1 class=\"hero\">{title}</h1>");
+    let plain = create_temp_zen("<p>{body}</p>");
+    let plans = vec![
+        BundlePlan {
+            page_path: styled.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        },
+        BundlePlan {
+            page_path: plain.path().to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Prod,
+        },
+    ];
+
+    let results = bundle_pages(plans, BundleOptions::default()).await.unwrap();
+
+    assert_eq!(results[0].css.as_deref(), Some(".hero{color:red}"));
+    assert!(results[1].css.is_none(), "CSS bled into a style-free page");
+}
+
+#[tokio::test]
+async fn reused_component_css_emitted_once() {
+    let card = create_temp_zen("<style>.card { padding: 1rem; }</style><div class=\"card\"></div>");
+    let page = create_temp_zen(
+        "<style>.hero { color: red; }</style>\
+         <h1 class=\"hero\">{title}</h1><Card /><Card />",
+    );
+    let components = HashMap::from([(
+        "Card".to_string(),
+        ComponentDef {
+            path: card.path().to_path_buf(),
+            source: None,
+        },
+    )]);
+    let plan = BundlePlan {
+        page_path: page.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Dev,
+    };
+    let opts = BundleOptions {
+        components: Some(components),
+        ..Default::default()
+    };
+
+    let css = bundle_page(plan, opts).await.unwrap().css.unwrap();
+    assert_eq!(
+        css.matches(".card { padding: 1rem; }").count(),
+        1,
+        "{}",
+        css
+    );
+    assert!(
+        css.find(".card").unwrap() < css.find(".hero").unwrap(),
+        "Component CSS should precede page CSS: {}",
+        css
+    );
+}
+
+#[tokio::test]
+async fn css_pruned_in_prod_and_untouched_in_dev() {
+    let source = "<style>.used { color: red; }\n.unused { color: blue; }</style>\
+                  <p class=\"used\">{text}</p>";
+    let file = create_temp_zen(source);
+    let plan = |mode| BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode,
+    };
+
+    let prod = bundle_page(plan(BuildMode::Prod), BundleOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(prod.css.as_deref(), Some(".used{color:red}"));
+
+    let dev = bundle_page(plan(BuildMode::Dev), BundleOptions::default())
+        .await
+        .unwrap();
+    let dev_css = dev.css.unwrap();
+    assert!(dev_css.contains(".unused { color: blue; }"), "{}", dev_css);
+}
+
+// ============================================================================
+// M1: SSG output
+// ============================================================================
+
+#[tokio::test]
+async fn ssg_writes_hashed_assets_and_html_document() {
+    let src = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let page = src.path().join("pages").join("about.zen");
+    std::fs::create_dir_all(page.parent().unwrap()).unwrap();
+    std::fs::write(
+        &page,
+        "<style>.lead { color: red; }</style><p class=\"lead\">{text}</p>",
+    )
+    .unwrap();
+    let plan = || BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+
+    let result = bundle_page(plan(), BundleOptions::default()).await.unwrap();
+
+    let html_path = out.path().join("about").join("index.html");
+    let written = std::fs::read_to_string(&html_path).unwrap();
+    assert_eq!(result.html.as_deref(), Some(written.as_str()));
+    assert!(written.starts_with("<!DOCTYPE html>"));
+
+    let assets: Vec<String> = std::fs::read_dir(out.path().join("assets"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    let js = assets.iter().find(|name| name.ends_with(".js")).unwrap();
+    let css = assets.iter().find(|name| name.ends_with(".css")).unwrap();
+    assert!(written.contains(&format!(
+        r#"<script type="module" src="/assets/{}"></script>"#,
+        js
+    )));
+    assert!(written.contains(&format!(
+        r#"<link rel="stylesheet" href="/assets/{}">"#,
+        css
+    )));
+
+    // Rebuilding yields the same document without stacking tags
+    let again = bundle_page(plan(), BundleOptions::default()).await.unwrap();
+    assert_eq!(again.html, result.html);
+    assert_eq!(written.matches("<script").count(), 1);
+}
+
+#[tokio::test]
+async fn ssg_writes_under_custom_out_dir() {
+    let src = tempfile::tempdir().unwrap();
+    let root = tempfile::tempdir().unwrap();
+    let out = root.path().join("site").join("public");
+    let page = src.path().join("contact.zen");
+    std::fs::write(&page, "<p>{email}</p>").unwrap();
+    let plan = BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: Some(out.clone()),
+        mode: BuildMode::SSG,
+    };
+
+    let result = bundle_page(plan, BundleOptions::default()).await.unwrap();
+
+    let written = std::fs::read_to_string(out.join("contact").join("index.html")).unwrap();
+    assert_eq!(result.html.as_deref(), Some(written.as_str()));
+    assert!(out.join("assets").is_dir());
+}
+
+#[tokio::test]
+async fn inline_assets_writes_self_contained_ssg_document() {
+    let src = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let page = src.path().join("pages").join("about.zen");
+    std::fs::create_dir_all(page.parent().unwrap()).unwrap();
+    std::fs::write(
+        &page,
+        "<style>.lead { color: red; }</style><p class=\"lead\">{text}</p>",
+    )
+    .unwrap();
+    let plan = BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+    let opts = BundleOptions {
+        inline_assets: true,
+        emit_manifest: true,
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    let written = std::fs::read_to_string(out.path().join("about").join("index.html")).unwrap();
+    assert_eq!(result.html.as_deref(), Some(written.as_str()));
+    assert!(!out.path().join("assets").exists());
+    let page_id = zenith_bundler::utils::canonicalize_page_id(&result.page_path, None);
+    assert_eq!(
+        result.assets,
+        vec![(format!("{}.html", page_id), "about/index.html".to_string())]
+    );
+
+    // Entry and CSS inline, nothing linked
+    assert!(written.contains(r#"<script type="module">"#));
+    assert!(written.contains(&html::escape_inline_script(&result.entry_js)));
+    assert!(written.contains("<style>"));
+    assert!(written.contains("color"));
+    assert!(!written.contains(" src=\""));
+    assert!(!written.contains("rel=\"stylesheet\""));
+
+    let manifest: BuildManifest =
+        serde_json::from_str(&std::fs::read_to_string(out.path().join(MANIFEST_FILE)).unwrap())
+            .unwrap();
+    assert_eq!(manifest.pages[0].entry, "about/index.html");
+    assert_eq!(manifest.pages[0].css, None);
+}
+
+#[tokio::test]
+async fn inline_assets_rejects_entries_that_import_chunks() {
+    let src = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    std::fs::write(src.path().join("lazy.js"), "export const later = 1;\n").unwrap();
+    let page = src.path().join("page.zen");
+    std::fs::write(
+        &page,
+        "<script>globalThis.load = () => import(\"./lazy.js\");</script><p>{text}</p>",
+    )
+    .unwrap();
+    let plan = BundlePlan {
+        page_path: page.to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+    let opts = BundleOptions {
+        inline_assets: true,
+        ..Default::default()
+    };
+
+    let err = bundle_page(plan, opts).await.unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+    assert!(err.to_string().contains("imports the chunks"), "{}", err);
+    assert_eq!(std::fs::read_dir(out.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn html_template_wraps_written_page() {
+    let out = tempfile::tempdir().unwrap();
+    let template = out.path().join("index.html");
+    std::fs::write(
+        &template,
+        "<!DOCTYPE html><html><head><title>Site</title></head>\
+         <body><div id=\"app\"><!-- zenith:page --></div></body></html>",
+    )
+    .unwrap();
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let plan = || BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: Some(out.path().join("dist")),
+        mode: BuildMode::Prod,
+    };
+    let opts = || BundleOptions {
+        write_to_disk: true,
+        html_template: Some(template.clone()),
+        ..Default::default()
+    };
+
+    let first = bundle_page(plan(), opts()).await.unwrap();
+    let second = bundle_page(plan(), opts()).await.unwrap();
+
+    let html = first.html.expect("template builds emit HTML");
+    assert!(html.contains("<title>Site</title>"));
+    assert!(html.contains("<div id=\"app\"><h1"));
+    assert_eq!(html.matches("<script type=\"module\"").count(), 1);
+    assert_eq!(second.html.as_deref(), Some(html.as_str()));
+}
+
+#[tokio::test]
+async fn hashed_filenames_are_stable_and_recorded() {
+    let src = tempfile::tempdir().unwrap();
+    let page = src.path().join("home.zen");
+    std::fs::write(
+        &page,
+        "<style>.hero { color: red; }</style><h1 class=\"hero\">{title}</h1>",
+    )
+    .unwrap();
+    let build = |out: std::path::PathBuf| {
+        let plan = BundlePlan {
+            page_path: page.to_string_lossy().to_string(),
+            out_dir: Some(out),
+            mode: BuildMode::Prod,
+        };
+        let opts = BundleOptions {
+            write_to_disk: true,
+            hashed_filenames: true,
+            ..Default::default()
+        };
+        bundle_page(plan, opts)
+    };
+
+    let out_a = tempfile::tempdir().unwrap();
+    let out_b = tempfile::tempdir().unwrap();
+    let first = build(out_a.path().to_path_buf()).await.unwrap();
+    let second = build(out_b.path().to_path_buf()).await.unwrap();
+
+    assert_eq!(first.assets, second.assets, "hashes drifted between runs");
+    let id = zenith_bundler::utils::canonicalize_page_id(&first.page_path, None);
+    let js = &first
+        .assets
+        .iter()
+        .find(|(logical, _)| *logical == format!("{}.js", id))
+        .unwrap()
+        .1;
+    assert_eq!(
+        js,
+        &format!("pages/{}.{}.js", id, content_hash(&first.entry_js))
+    );
+    assert_eq!(
+        std::fs::read_to_string(out_a.path().join(js)).unwrap(),
+        first.entry_js
+    );
+
+    let css = &first
+        .assets
+        .iter()
+        .find(|(logical, _)| *logical == format!("{}.css", id))
+        .unwrap()
+        .1;
+    assert!(out_a.path().join(css).is_file());
+    assert!(!out_a
+        .path()
+        .join("pages")
+        .join(format!("{}.js", id))
+        .exists());
+}
+
+#[tokio::test]
+async fn analyze_reports_stats_without_writing() {
+    let file =
+        create_temp_zen("<style>.hero { color: red; }</style><h1 class=\"hero\">{title}</h1>");
+    let out = tempfile::tempdir().unwrap();
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::SSG,
+    };
+    let opts = BundleOptions {
+        write_to_disk: true,
+        emit_manifest: true,
+        analyze: true,
+        ..Default::default()
+    };
+
+    let result = bundle_page(plan, opts).await.unwrap();
+
+    assert_eq!(
+        result.stats,
+        Some(BundleStats {
+            expression_count: 1,
+            entry_js_bytes: result.entry_js.len(),
+            css_bytes: result.css.as_ref().unwrap().len(),
+            chunk_count: 1 + result.preload_chunks.len(),
+            component_instance_count: 0,
+        })
+    );
+    assert!(result.assets.is_empty());
+    assert!(result.html.is_none());
+    assert_eq!(
+        std::fs::read_dir(out.path()).unwrap().count(),
+        0,
+        "analyze build wrote to out_dir"
+    );
+}
+
+#[tokio::test]
+async fn compiled_output_attached_only_when_requested() {
+    let file = create_temp_zen("<button on:click={inc}>{count}</button>");
+    let plan = || BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: None,
+        mode: BuildMode::Prod,
+    };
+
+    let plain = bundle_page(plan(), BundleOptions::default()).await.unwrap();
+    assert!(plain.compiled.is_none());
+
+    let opts = BundleOptions {
+        include_compiled: true,
+        ..Default::default()
+    };
+    let result = bundle_page(plan(), opts).await.unwrap();
+    let compiled = result.compiled.expect("compiled output requested");
+    assert_eq!(compiled.expressions, result.expressions);
+    assert!(compiled.html.contains("data-zx-e"), "{}", compiled.html);
+}
+
+// ============================================================================
+// M1: Build manifest
+// ============================================================================
+
+#[tokio::test]
+async fn build_manifest_lists_written_pages_sorted() {
+    let src = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let pages_dir = src.path().join("pages");
+    std::fs::create_dir_all(&pages_dir).unwrap();
+    let zeta = pages_dir.join("zeta.zen");
+    let alpha = pages_dir.join("alpha.zen");
+    std::fs::write(&zeta, "<h1>{a}</h1><p>{b}</p>").unwrap();
+    std::fs::write(
+        &alpha,
+        "<style>.x { color: red; }</style><p class=\"x\">static</p>",
+    )
+    .unwrap();
+    let plans = || {
+        [&zeta, &alpha]
+            .iter()
+            .map(|page| BundlePlan {
+                page_path: page.to_string_lossy().to_string(),
+                out_dir: Some(out.path().to_path_buf()),
+                mode: BuildMode::Prod,
+            })
+            .collect::<Vec<_>>()
+    };
+    let opts = || BundleOptions {
+        write_to_disk: true,
+        emit_manifest: true,
+        ..Default::default()
+    };
+
+    let results = bundle_pages(plans(), opts()).await.unwrap();
+    let manifest_path = out.path().join(MANIFEST_FILE);
+    let first = std::fs::read_to_string(&manifest_path).unwrap();
+    let manifest: BuildManifest = serde_json::from_str(&first).unwrap();
+
+    // Pages are sorted by id regardless of plan order
+    let ids: Vec<&str> = manifest.pages.iter().map(|p| p.page_id.as_str()).collect();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+    assert_eq!(manifest.pages.len(), 2);
+
+    let zeta_entry = manifest
+        .pages
+        .iter()
+        .find(|p| p.page_id.ends_with("zeta"))
+        .unwrap();
+    assert_eq!(zeta_entry.expression_count, 2);
+    assert_eq!(zeta_entry.css, None);
+    assert_eq!(zeta_entry.hash, content_hash(&results[0].entry_js));
+    assert!(out.path().join(&zeta_entry.entry).is_file());
+
+    let alpha_entry = manifest
+        .pages
+        .iter()
+        .find(|p| p.page_id.ends_with("alpha"))
+        .unwrap();
+    assert_eq!(alpha_entry.expression_count, 0);
+    assert!(out.path().join(alpha_entry.css.as_ref().unwrap()).is_file());
+
+    // The pages share no modules, so there are no chunks besides the entries
+    assert!(manifest.chunks.is_empty(), "{:?}", manifest.chunks);
+
+    // Byte-identical on rebuild
+    bundle_pages(plans(), opts()).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&manifest_path).unwrap(), first);
+}
+
+#[tokio::test]
+async fn build_manifest_not_written_unless_requested() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let out = tempfile::tempdir().unwrap();
+    let plan = BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::Prod,
+    };
+    let opts = BundleOptions {
+        write_to_disk: true,
+        ..Default::default()
+    };
+
+    bundle_page(plan, opts).await.unwrap();
+    assert!(!out.path().join(MANIFEST_FILE).exists());
+}
+
+#[tokio::test]
+async fn cancelled_token_aborts_before_anything_is_written() {
+    let file = create_temp_zen("<h1>{title}</h1>");
+    let out = tempfile::tempdir().unwrap();
+    let plan = || BundlePlan {
+        page_path: file.path().to_string_lossy().to_string(),
+        out_dir: Some(out.path().to_path_buf()),
+        mode: BuildMode::Prod,
+    };
+    let opts = || BundleOptions {
+        write_to_disk: true,
+        ..Default::default()
+    };
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let err = bundle_page_cancellable(plan(), opts(), Some(token))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, BundleError::Cancelled), "got {err:?}");
+    assert_eq!(std::fs::read_dir(out.path()).unwrap().count(), 0);
+
+    // A live token builds exactly like `bundle_page`
+    let live = bundle_page_cancellable(plan(), opts(), Some(CancellationToken::new()))
+        .await
+        .unwrap();
+    let plain = bundle_page(plan(), opts()).await.unwrap();
+    assert_eq!(live.entry_js, plain.entry_js);
+}
+
+#[tokio::test]
+async fn router_manifest_built_from_in_memory_results() {
+    let src = tempfile::tempdir().unwrap();
+    let post = src.path().join("post.zen");
+    let home = src.path().join("home.zen");
+    std::fs::write(&post, "<h1>{params.slug}</h1>").unwrap();
+    std::fs::write(&home, "<p>home</p>").unwrap();
+    let plans = [&post, &home]
+        .iter()
+        .map(|page| BundlePlan {
+            page_path: page.to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Dev,
+        })
+        .collect::<Vec<_>>();
+    let opts = BundleOptions {
+        include_compiled: true,
+        ..Default::default()
+    };
+    let results = bundle_pages(plans, opts).await.unwrap();
+    let routes = vec![
+        (
+            post.to_string_lossy().to_string(),
+            "/blog/:slug".to_string(),
+        ),
+        (home.to_string_lossy().to_string(), "/".to_string()),
+    ];
+
+    let json = router::build_router_manifest(&results, &routes).unwrap();
+    let manifest: router::RouterManifest = serde_json::from_str(&json).unwrap();
+    let paths: Vec<&str> = manifest.routes.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(paths, vec!["/", "/blog/:slug"]);
+
+    let blog = &manifest.routes[1];
+    assert_eq!(blog.output, "blog/_slug/index.html");
+    assert_eq!(blog.expressions, results[0].expressions);
+    assert!(blog.html.contains("data-zx-e"), "{}", blog.html);
+    assert_eq!(
+        blog.content_hash.as_deref(),
+        Some(router::route_content_hash(&blog.html, &blog.expressions).as_str())
+    );
+
+    // The page markup is only available with `include_compiled`
+    let bare = bundle_pages(
+        vec![BundlePlan {
+            page_path: home.to_string_lossy().to_string(),
+            out_dir: None,
+            mode: BuildMode::Dev,
+        }],
+        BundleOptions::default(),
+    )
+    .await
+    .unwrap();
+    let err = router::build_router_manifest(&bare, &routes[1..]).unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+
+    // Two pages cannot share a route's paths or its shell
+    let colliding = vec![
+        routes[0].clone(),
+        (
+            home.to_string_lossy().to_string(),
+            "/blog/:slug?".to_string(),
+        ),
+    ];
+    let err = router::build_router_manifest(&results, &colliding).unwrap_err();
+    assert!(matches!(err, BundleError::ValidationError(_)), "{}", err);
+    assert!(err.to_string().contains("Route collision"), "{}", err);
+}
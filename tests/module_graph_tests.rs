@@ -11,6 +11,7 @@ use std::io::Write;
 use std::sync::Arc;
 use std::thread;
 use zenith_bundler::plugin::css_cache::CssCache;
+use zenith_bundler::testing::MockCompilerOutput;
 use zenith_bundler::utils;
 use zenith_bundler::{
     bundle_page, BuildMode, BundleError, BundleOptions, BundlePlan, CompilerOutput,
@@ -391,18 +392,7 @@ async fn strict_inline_and_rolldown_mirror_count() {
     let file = create_temp_zen("<h1>{title}</h1>");
     let path = file.path().to_string_lossy().to_string();
 
-    let metadata = CompilerOutput {
-        ir_version: 1,
-        html: String::new(),
-        expressions: vec!["title".into(), "extra".into()],
-        hoisted: Default::default(),
-        components_scripts: Default::default(),
-        component_instances: Default::default(),
-        signals: Default::default(),
-        expression_bindings: Default::default(),
-        marker_bindings: Default::default(),
-        event_bindings: Default::default(),
-    };
+    let metadata = CompilerOutput::mock("", &["title", "extra"]);
 
     let plan = BundlePlan {
         page_path: path,
@@ -434,18 +424,7 @@ async fn strict_inline_and_rolldown_mirror_content() {
     let file = create_temp_zen("<h1>{title}</h1>");
     let path = file.path().to_string_lossy().to_string();
 
-    let metadata = CompilerOutput {
-        ir_version: 1,
-        html: String::new(),
-        expressions: vec!["wrong_name".into()],
-        hoisted: Default::default(),
-        components_scripts: Default::default(),
-        component_instances: Default::default(),
-        signals: Default::default(),
-        expression_bindings: Default::default(),
-        marker_bindings: Default::default(),
-        event_bindings: Default::default(),
-    };
+    let metadata = CompilerOutput::mock("", &["wrong_name"]);
 
     let plan = BundlePlan {
         page_path: path,
@@ -466,10 +445,12 @@ async fn strict_inline_and_rolldown_mirror_content() {
             index,
             expected,
             got,
+            near,
         } => {
             assert_eq!(index, 0);
             assert_eq!(expected, "wrong_name");
             assert_eq!(got, "title");
+            assert!(near.unwrap().contains("data-zx-e=\"0\""));
         }
         e => panic!("Expected ExpressionContentMismatch variant, got: {:?}", e),
     }
@@ -193,3 +193,43 @@ async fn large_template_stability() {
     );
     println!("Large Template Build Time: {:?}", duration);
 }
+
+// ---------------------------------------------------------------------------
+// 9.5 Chunked Disk Writes
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+#[ignore]
+async fn large_output_written_via_chunked_path() {
+    // Well above the streaming threshold (1 MiB) so the chunked writer is used.
+    let mut content = String::from("<main>");
+    for i in 0..40_000 {
+        content.push_str(&format!(
+            "<p class=\"row-{}\">Row {} of the large page</p>",
+            i, i
+        ));
+    }
+    content.push_str("<h1>{title}</h1></main>");
+
+    let file = create_temp_zen(&content);
+    let out_dir = tempfile::tempdir().unwrap();
+    let page_path = file.path().to_string_lossy().to_string();
+
+    let plan = BundlePlan {
+        page_path: page_path.clone(),
+        out_dir: Some(out_dir.path().to_path_buf()),
+        mode: BuildMode::Prod,
+    };
+    let opts = BundleOptions {
+        write_to_disk: true,
+        ..Default::default()
+    };
+    let res = bundle_page(plan, opts).await.unwrap();
+    assert!(res.entry_js.len() > 1024 * 1024);
+
+    let page_id = zenith_bundler::utils::canonicalize_page_id(&page_path, None);
+    let written =
+        std::fs::read_to_string(out_dir.path().join("pages").join(format!("{}.js", page_id)))
+            .unwrap();
+    assert_eq!(written, res.entry_js);
+}